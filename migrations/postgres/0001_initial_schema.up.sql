@@ -0,0 +1,21 @@
+CREATE TABLE IF NOT EXISTS categories (
+    name TEXT PRIMARY KEY,
+    active BOOLEAN NOT NULL DEFAULT TRUE
+);
+
+CREATE TABLE IF NOT EXISTS questions (
+    id BIGSERIAL PRIMARY KEY,
+    text TEXT NOT NULL,
+    category TEXT NOT NULL REFERENCES categories (name) ON UPDATE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS questions_category_idx ON questions (category);
+
+CREATE TABLE IF NOT EXISTS choices (
+    id BIGSERIAL PRIMARY KEY,
+    question_id BIGINT NOT NULL REFERENCES questions (id) ON DELETE CASCADE,
+    text TEXT NOT NULL,
+    correct BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE INDEX IF NOT EXISTS choices_question_id_idx ON choices (question_id);
@@ -0,0 +1,2 @@
+ALTER TABLE questions DROP COLUMN deleted_on;
+ALTER TABLE categories DROP COLUMN deleted_on;
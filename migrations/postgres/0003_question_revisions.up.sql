@@ -0,0 +1,21 @@
+CREATE TABLE IF NOT EXISTS question_revisions (
+    question_id BIGINT NOT NULL REFERENCES questions (id) ON DELETE CASCADE,
+    revision INTEGER NOT NULL,
+    author TEXT NOT NULL,
+    created_on TIMESTAMPTZ NOT NULL DEFAULT now(),
+    text TEXT NOT NULL,
+    category TEXT NOT NULL,
+    -- JSON array of the question's choices at the time of the revision.
+    choices TEXT NOT NULL,
+    PRIMARY KEY (question_id, revision)
+);
+
+-- Existing questions start with a single revision describing their current state.
+INSERT INTO question_revisions (question_id, revision, author, text, category, choices)
+SELECT q.id, 1, 'unknown', q.text, q.category,
+    COALESCE(
+        (SELECT json_agg(json_build_object('id', c.id, 'title', c.text, 'correct', c.correct) ORDER BY c.id)
+         FROM choices c WHERE c.question_id = q.id),
+        '[]'
+    )::text
+FROM questions q;
@@ -0,0 +1,7 @@
+-- Former names of renamed or merged categories, so that old links keep resolving.
+CREATE TABLE IF NOT EXISTS category_aliases (
+    alias TEXT PRIMARY KEY,
+    category TEXT NOT NULL REFERENCES categories (name) ON UPDATE CASCADE ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS category_aliases_category_idx ON category_aliases (category);
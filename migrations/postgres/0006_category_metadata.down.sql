@@ -0,0 +1,5 @@
+ALTER TABLE categories DROP COLUMN featured;
+ALTER TABLE categories DROP COLUMN display_order;
+ALTER TABLE categories DROP COLUMN colour;
+ALTER TABLE categories DROP COLUMN icon;
+ALTER TABLE categories DROP COLUMN description;
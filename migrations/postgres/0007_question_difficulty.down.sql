@@ -0,0 +1,3 @@
+ALTER TABLE question_revisions DROP COLUMN difficulty;
+DROP INDEX IF EXISTS questions_category_difficulty_idx;
+ALTER TABLE questions DROP COLUMN difficulty;
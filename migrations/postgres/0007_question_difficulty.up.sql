@@ -0,0 +1,6 @@
+ALTER TABLE questions ADD COLUMN difficulty TEXT NOT NULL DEFAULT 'medium' CHECK (difficulty IN ('easy', 'medium', 'hard'));
+
+CREATE INDEX IF NOT EXISTS questions_category_difficulty_idx ON questions (category, difficulty);
+
+-- Revisions recorded before difficulties existed have none.
+ALTER TABLE question_revisions ADD COLUMN difficulty TEXT NULL;
@@ -0,0 +1,2 @@
+ALTER TABLE question_revisions DROP COLUMN question_type;
+ALTER TABLE questions DROP COLUMN question_type;
@@ -0,0 +1,2 @@
+ALTER TABLE question_revisions DROP COLUMN free_text;
+ALTER TABLE questions DROP COLUMN free_text;
@@ -0,0 +1,6 @@
+-- The items of ordering questions and the pairs of matching questions, as JSON objects. NULL for other question types.
+ALTER TABLE questions ADD COLUMN ordering TEXT NULL;
+ALTER TABLE questions ADD COLUMN matching TEXT NULL;
+
+ALTER TABLE question_revisions ADD COLUMN ordering TEXT NULL;
+ALTER TABLE question_revisions ADD COLUMN matching TEXT NULL;
@@ -0,0 +1,2 @@
+ALTER TABLE question_revisions DROP COLUMN numeric;
+ALTER TABLE questions DROP COLUMN numeric;
@@ -0,0 +1,4 @@
+-- The target value, tolerance and unit of a numeric question, as a JSON object. NULL for other question types.
+ALTER TABLE questions ADD COLUMN numeric TEXT NULL;
+
+ALTER TABLE question_revisions ADD COLUMN numeric TEXT NULL;
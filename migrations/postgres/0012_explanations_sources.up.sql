@@ -0,0 +1,9 @@
+ALTER TABLE choices ADD COLUMN explanation TEXT NULL;
+
+ALTER TABLE questions ADD COLUMN explanation TEXT NULL;
+-- JSON array of the question's sources.
+ALTER TABLE questions ADD COLUMN sources TEXT NOT NULL DEFAULT '[]';
+
+-- Revisions recorded before questions had sources have none.
+ALTER TABLE question_revisions ADD COLUMN explanation TEXT NULL;
+ALTER TABLE question_revisions ADD COLUMN sources TEXT NULL;
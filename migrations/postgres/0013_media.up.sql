@@ -0,0 +1,6 @@
+-- JSON arrays of the media attached to each question and choice.
+ALTER TABLE questions ADD COLUMN media TEXT NOT NULL DEFAULT '[]';
+ALTER TABLE choices ADD COLUMN media TEXT NOT NULL DEFAULT '[]';
+
+-- Revisions recorded before questions had media have none.
+ALTER TABLE question_revisions ADD COLUMN media TEXT NULL;
@@ -0,0 +1,4 @@
+ALTER TABLE question_revisions DROP COLUMN tags;
+DROP INDEX IF EXISTS question_tags_tag_id_idx;
+DROP TABLE IF EXISTS question_tags;
+DROP TABLE IF EXISTS tags;
@@ -0,0 +1,16 @@
+-- Free-form labels that questions share, in addition to their category.
+CREATE TABLE IF NOT EXISTS tags (
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS question_tags (
+    question_id BIGINT NOT NULL REFERENCES questions (id) ON DELETE CASCADE,
+    tag_id BIGINT NOT NULL REFERENCES tags (id) ON DELETE CASCADE,
+    PRIMARY KEY (question_id, tag_id)
+);
+
+CREATE INDEX IF NOT EXISTS question_tags_tag_id_idx ON question_tags (tag_id);
+
+-- JSON array of the question's tags. Revisions recorded before questions had tags have none.
+ALTER TABLE question_revisions ADD COLUMN tags TEXT NULL;
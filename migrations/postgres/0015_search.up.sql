@@ -0,0 +1,8 @@
+-- The words of each question's text (weight A) and choice titles (weight B), kept up to date when a question is saved.
+ALTER TABLE questions ADD COLUMN search_vector tsvector NOT NULL DEFAULT ''::tsvector;
+
+UPDATE questions q SET search_vector =
+    setweight(to_tsvector('english', q.text), 'A') ||
+    setweight(to_tsvector('english', COALESCE((SELECT string_agg(c.text, ' / ' ORDER BY c.id) FROM choices c WHERE c.question_id = q.id), '')), 'B');
+
+CREATE INDEX IF NOT EXISTS questions_search_vector_idx ON questions USING GIN (search_vector);
@@ -0,0 +1,8 @@
+-- Player accounts. Usernames are stored lowercased, so that they are unique regardless of case,
+-- and passwords are stored as Argon2 PHC strings.
+CREATE TABLE IF NOT EXISTS players (
+    id TEXT PRIMARY KEY,
+    username TEXT NOT NULL UNIQUE,
+    password_hash TEXT NOT NULL,
+    created_on TIMESTAMPTZ NOT NULL
+);
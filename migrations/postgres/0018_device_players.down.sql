@@ -0,0 +1,7 @@
+DROP INDEX IF EXISTS quiz_sessions_player_id_idx;
+ALTER TABLE quiz_sessions DROP COLUMN IF EXISTS player_id;
+
+DELETE FROM players WHERE username IS NULL;
+ALTER TABLE players DROP COLUMN IF EXISTS device_id;
+ALTER TABLE players ALTER COLUMN password_hash SET NOT NULL;
+ALTER TABLE players ALTER COLUMN username SET NOT NULL;
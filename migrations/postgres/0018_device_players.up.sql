@@ -0,0 +1,9 @@
+-- Anonymous players are identified by a device id generated by the client, and have no username or password
+-- until they are linked to an account.
+ALTER TABLE players ALTER COLUMN username DROP NOT NULL;
+ALTER TABLE players ALTER COLUMN password_hash DROP NOT NULL;
+ALTER TABLE players ADD COLUMN IF NOT EXISTS device_id TEXT NULL UNIQUE;
+
+-- The player who started each quiz. Sessions started without a token have none.
+ALTER TABLE quiz_sessions ADD COLUMN IF NOT EXISTS player_id TEXT NULL;
+CREATE INDEX IF NOT EXISTS quiz_sessions_player_id_idx ON quiz_sessions (player_id);
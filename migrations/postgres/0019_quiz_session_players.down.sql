@@ -0,0 +1 @@
+ALTER TABLE quiz_sessions DROP CONSTRAINT IF EXISTS quiz_sessions_player_id_fkey;
@@ -0,0 +1,5 @@
+-- Sessions may only belong to existing players. Sessions of players that no longer exist are kept without one.
+UPDATE quiz_sessions SET player_id = NULL
+    WHERE player_id IS NOT NULL AND player_id NOT IN (SELECT id FROM players);
+ALTER TABLE quiz_sessions
+    ADD CONSTRAINT quiz_sessions_player_id_fkey FOREIGN KEY (player_id) REFERENCES players (id);
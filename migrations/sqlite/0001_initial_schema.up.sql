@@ -0,0 +1,21 @@
+CREATE TABLE IF NOT EXISTS categories (
+    name TEXT PRIMARY KEY,
+    active BOOLEAN NOT NULL DEFAULT 1
+);
+
+CREATE TABLE IF NOT EXISTS questions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    text TEXT NOT NULL,
+    category TEXT NOT NULL REFERENCES categories (name) ON UPDATE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS questions_category_idx ON questions (category);
+
+CREATE TABLE IF NOT EXISTS choices (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    question_id INTEGER NOT NULL REFERENCES questions (id) ON DELETE CASCADE,
+    text TEXT NOT NULL,
+    correct BOOLEAN NOT NULL DEFAULT 0
+);
+
+CREATE INDEX IF NOT EXISTS choices_question_id_idx ON choices (question_id);
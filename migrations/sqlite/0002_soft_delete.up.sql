@@ -0,0 +1,2 @@
+ALTER TABLE categories ADD COLUMN deleted_on TEXT NULL;
+ALTER TABLE questions ADD COLUMN deleted_on TEXT NULL;
@@ -0,0 +1,24 @@
+CREATE TABLE IF NOT EXISTS question_revisions (
+    question_id INTEGER NOT NULL REFERENCES questions (id) ON DELETE CASCADE,
+    revision INTEGER NOT NULL,
+    author TEXT NOT NULL,
+    created_on TEXT NOT NULL,
+    text TEXT NOT NULL,
+    category TEXT NOT NULL,
+    -- JSON array of the question's choices at the time of the revision.
+    choices TEXT NOT NULL,
+    PRIMARY KEY (question_id, revision)
+);
+
+-- Existing questions start with a single revision describing their current state.
+INSERT INTO question_revisions (question_id, revision, author, created_on, text, category, choices)
+SELECT q.id, 1, 'unknown', strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now'), q.text, q.category,
+    COALESCE(
+        (SELECT json_group_array(json_object(
+            'id', c.id,
+            'title', c.text,
+            'correct', json(CASE WHEN c.correct THEN 'true' ELSE 'false' END)))
+         FROM (SELECT * FROM choices WHERE question_id = q.id ORDER BY id) c),
+        '[]'
+    )
+FROM questions q;
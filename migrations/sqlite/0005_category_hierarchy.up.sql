@@ -0,0 +1,3 @@
+ALTER TABLE categories ADD COLUMN parent TEXT NULL REFERENCES categories (name) ON UPDATE CASCADE ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS categories_parent_idx ON categories (parent);
@@ -0,0 +1,5 @@
+ALTER TABLE categories ADD COLUMN description TEXT NULL;
+ALTER TABLE categories ADD COLUMN icon TEXT NULL;
+ALTER TABLE categories ADD COLUMN colour TEXT NULL;
+ALTER TABLE categories ADD COLUMN display_order INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE categories ADD COLUMN featured BOOLEAN NOT NULL DEFAULT 0;
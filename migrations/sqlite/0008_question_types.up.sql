@@ -0,0 +1,5 @@
+-- Question types are validated by the application, so that new types do not require a schema change.
+ALTER TABLE questions ADD COLUMN question_type TEXT NOT NULL DEFAULT 'single_choice';
+
+-- Revisions recorded before questions had a type have none.
+ALTER TABLE question_revisions ADD COLUMN question_type TEXT NULL;
@@ -0,0 +1,4 @@
+-- The accepted answers of a free-text question, as a JSON object. NULL for other question types.
+ALTER TABLE questions ADD COLUMN free_text TEXT NULL;
+
+ALTER TABLE question_revisions ADD COLUMN free_text TEXT NULL;
@@ -0,0 +1,4 @@
+ALTER TABLE question_revisions DROP COLUMN matching;
+ALTER TABLE question_revisions DROP COLUMN ordering;
+ALTER TABLE questions DROP COLUMN matching;
+ALTER TABLE questions DROP COLUMN ordering;
@@ -0,0 +1,5 @@
+ALTER TABLE question_revisions DROP COLUMN sources;
+ALTER TABLE question_revisions DROP COLUMN explanation;
+ALTER TABLE questions DROP COLUMN sources;
+ALTER TABLE questions DROP COLUMN explanation;
+ALTER TABLE choices DROP COLUMN explanation;
@@ -0,0 +1,3 @@
+ALTER TABLE question_revisions DROP COLUMN media;
+ALTER TABLE choices DROP COLUMN media;
+ALTER TABLE questions DROP COLUMN media;
@@ -0,0 +1,10 @@
+-- Full-text index of each question's text and choice titles, keyed by question id and kept up to date when a question is saved.
+CREATE VIRTUAL TABLE IF NOT EXISTS question_search USING fts5(
+    text,
+    choices,
+    tokenize = 'porter unicode61 remove_diacritics 2'
+);
+
+INSERT INTO question_search (rowid, text, choices)
+SELECT q.id, q.text, COALESCE((SELECT group_concat(text, ' / ') FROM (SELECT c.text FROM choices c WHERE c.question_id = q.id ORDER BY c.id)), '')
+FROM questions q;
@@ -0,0 +1,18 @@
+-- Quizzes played by players. The questions are kept as a JSON array of ids in the order they are asked,
+-- without a foreign key, so that a session's history survives its questions being purged.
+CREATE TABLE IF NOT EXISTS quiz_sessions (
+    id TEXT PRIMARY KEY,
+    category TEXT NOT NULL,
+    question_ids TEXT NOT NULL,
+    started_on TEXT NOT NULL,
+    finished_on TEXT NULL
+);
+
+CREATE TABLE IF NOT EXISTS quiz_answers (
+    session_id TEXT NOT NULL REFERENCES quiz_sessions (id) ON DELETE CASCADE,
+    question_id INTEGER NOT NULL,
+    choice_id INTEGER NOT NULL,
+    correct BOOLEAN NOT NULL,
+    answered_on TEXT NOT NULL,
+    PRIMARY KEY (session_id, question_id)
+);
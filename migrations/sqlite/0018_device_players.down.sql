@@ -0,0 +1,13 @@
+DROP INDEX IF EXISTS quiz_sessions_player_id_idx;
+ALTER TABLE quiz_sessions DROP COLUMN player_id;
+
+CREATE TABLE players_old (
+    id TEXT PRIMARY KEY,
+    username TEXT NOT NULL UNIQUE,
+    password_hash TEXT NOT NULL,
+    created_on TEXT NOT NULL
+);
+INSERT INTO players_old (id, username, password_hash, created_on)
+    SELECT id, username, password_hash, created_on FROM players WHERE username IS NOT NULL;
+DROP TABLE players;
+ALTER TABLE players_old RENAME TO players;
@@ -0,0 +1,17 @@
+-- Anonymous players are identified by a device id generated by the client, and have no username or password
+-- until they are linked to an account. SQLite cannot drop NOT NULL constraints, so the table is rebuilt.
+CREATE TABLE players_new (
+    id TEXT PRIMARY KEY,
+    username TEXT NULL UNIQUE,
+    password_hash TEXT NULL,
+    device_id TEXT NULL UNIQUE,
+    created_on TEXT NOT NULL
+);
+INSERT INTO players_new (id, username, password_hash, created_on)
+    SELECT id, username, password_hash, created_on FROM players;
+DROP TABLE players;
+ALTER TABLE players_new RENAME TO players;
+
+-- The player who started each quiz. Sessions started without a token have none.
+ALTER TABLE quiz_sessions ADD COLUMN player_id TEXT NULL;
+CREATE INDEX IF NOT EXISTS quiz_sessions_player_id_idx ON quiz_sessions (player_id);
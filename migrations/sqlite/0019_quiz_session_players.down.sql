@@ -0,0 +1,27 @@
+CREATE TABLE quiz_sessions_old (
+    id TEXT PRIMARY KEY,
+    category TEXT NOT NULL,
+    question_ids TEXT NOT NULL,
+    started_on TEXT NOT NULL,
+    finished_on TEXT NULL,
+    player_id TEXT NULL
+);
+INSERT INTO quiz_sessions_old (id, category, question_ids, started_on, finished_on, player_id)
+    SELECT id, category, question_ids, started_on, finished_on, player_id FROM quiz_sessions;
+
+CREATE TABLE quiz_answers_old (
+    session_id TEXT NOT NULL REFERENCES quiz_sessions_old (id) ON DELETE CASCADE,
+    question_id INTEGER NOT NULL,
+    choice_id INTEGER NOT NULL,
+    correct BOOLEAN NOT NULL,
+    answered_on TEXT NOT NULL,
+    PRIMARY KEY (session_id, question_id)
+);
+INSERT INTO quiz_answers_old (session_id, question_id, choice_id, correct, answered_on)
+    SELECT session_id, question_id, choice_id, correct, answered_on FROM quiz_answers;
+
+DROP TABLE quiz_answers;
+DROP TABLE quiz_sessions;
+ALTER TABLE quiz_sessions_old RENAME TO quiz_sessions;
+ALTER TABLE quiz_answers_old RENAME TO quiz_answers;
+CREATE INDEX IF NOT EXISTS quiz_sessions_player_id_idx ON quiz_sessions (player_id);
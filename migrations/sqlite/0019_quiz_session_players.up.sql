@@ -0,0 +1,32 @@
+-- Sessions may only belong to existing players. Sessions of players that no longer exist are kept without one.
+-- SQLite cannot add a foreign key to a table, so it is rebuilt. Its answers are rebuilt too: they reference
+-- the sessions, and dropping the sessions would otherwise delete them.
+CREATE TABLE quiz_sessions_new (
+    id TEXT PRIMARY KEY,
+    category TEXT NOT NULL,
+    question_ids TEXT NOT NULL,
+    started_on TEXT NOT NULL,
+    finished_on TEXT NULL,
+    player_id TEXT NULL REFERENCES players (id)
+);
+INSERT INTO quiz_sessions_new (id, category, question_ids, started_on, finished_on, player_id)
+    SELECT id, category, question_ids, started_on, finished_on,
+        CASE WHEN player_id IN (SELECT id FROM players) THEN player_id END
+    FROM quiz_sessions;
+
+CREATE TABLE quiz_answers_new (
+    session_id TEXT NOT NULL REFERENCES quiz_sessions_new (id) ON DELETE CASCADE,
+    question_id INTEGER NOT NULL,
+    choice_id INTEGER NOT NULL,
+    correct BOOLEAN NOT NULL,
+    answered_on TEXT NOT NULL,
+    PRIMARY KEY (session_id, question_id)
+);
+INSERT INTO quiz_answers_new (session_id, question_id, choice_id, correct, answered_on)
+    SELECT session_id, question_id, choice_id, correct, answered_on FROM quiz_answers;
+
+DROP TABLE quiz_answers;
+DROP TABLE quiz_sessions;
+ALTER TABLE quiz_sessions_new RENAME TO quiz_sessions;
+ALTER TABLE quiz_answers_new RENAME TO quiz_answers;
+CREATE INDEX IF NOT EXISTS quiz_sessions_player_id_idx ON quiz_sessions (player_id);
@@ -1,16 +1,21 @@
+use chrono::{DateTime, Utc};
 use juniper::FieldResult;
 use models::*;
 use repositories::*;
+use sampling;
 use serde_derive::{Deserialize, Serialize};
 use services::*;
+use std::sync::Arc;
 
-#[derive(Serialize, Deserialize, Debug, GraphQLObject)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct PaginatedQuestions {
     pub data: Vec<Question>,
     pub page: i32,
     pub size: i32,
     pub page_count: i32,
     pub last: bool,
+    /// Why each question was found, in the same order as `data`. Only set by `searchQuestions`.
+    pub highlights: Vec<SearchHighlight>,
 }
 
 impl PaginatedQuestions {
@@ -34,18 +39,306 @@ impl PaginatedQuestions {
             size: size,
             page_count: page_count,
             last: last,
+            highlights: vec![],
         };
     }
+
+    pub fn with_highlights(mut self, highlights: Vec<SearchHighlight>) -> PaginatedQuestions {
+        self.highlights = highlights;
+        self
+    }
 }
 
+/// The page of `adminQuestions`, showing the questions with their answers.
+pub struct AdminQuestions(pub PaginatedQuestions);
+
+/// A question as players see it, without its answers, explanation or sources.
+/// Administrators see the whole `Question` through `adminQuestions`; players check their answers one at a time.
+/// The seed is that of `randomQuestions`, which shuffles ordering and matching items the same way every time;
+/// without one they are shuffled anew.
+pub struct PublicQuestion(pub Question, pub Option<i32>);
+
+impl PublicQuestion {
+    fn shuffler(&self) -> sampling::SplitMix64 {
+        match self.1 {
+            Some(seed) => sample_shuffler(seed, self.0.id.unwrap_or_default()),
+            None => sampling::SplitMix64::unseeded(),
+        }
+    }
+}
+
+/// The questions picked by `randomQuestions`, with the seed that picks them again.
+pub struct QuestionSample {
+    pub seed: i32,
+    pub questions: Vec<Question>,
+}
+
+#[derive(Debug)]
+pub struct Trash {
+    pub categories: Vec<DeletedCategory>,
+    pub questions: Vec<DeletedQuestion>,
+}
+
+/// The services are shared by every request; the principal is that of the request being resolved.
 pub struct Context {
-    pub categories_service: CategoriesService,
-    pub questions_service: QuestionsService,
-    pub authorization_service: AuthorizationService,
+    pub categories_service: Arc<CategoriesService>,
+    pub questions_service: Arc<QuestionsService>,
+    pub quiz_service: Arc<QuizService>,
+    pub media_service: Arc<MediaService>,
+    pub authorization_service: Arc<AuthorizationService>,
+    pub players_service: Arc<PlayersService>,
+    pub principal: Principal,
+}
+
+impl Context {
+    /// The context of a request with the given `Authorization` header.
+    pub fn for_request(&self, authorization: Option<&str>) -> Context {
+        Context {
+            categories_service: self.categories_service.clone(),
+            questions_service: self.questions_service.clone(),
+            quiz_service: self.quiz_service.clone(),
+            media_service: self.media_service.clone(),
+            authorization_service: self.authorization_service.clone(),
+            players_service: self.players_service.clone(),
+            principal: self.players_service.authenticate(authorization),
+        }
+    }
 }
 
 impl juniper::Context for Context {}
 
+graphql_object!(Category: Context |&self| {
+    field title() -> &str {
+        &self.title
+    }
+
+    field description() -> Option<&str> {
+        self.description.as_ref().map(|d| d.as_str())
+    }
+
+    field icon() -> Option<&str> {
+        self.icon.as_ref().map(|i| i.as_str())
+    }
+
+    field colour() -> Option<&str> {
+        self.colour.as_ref().map(|c| c.as_str())
+    }
+
+    field display_order() -> i32 {
+        self.display_order
+    }
+
+    field featured() -> bool {
+        self.featured
+    }
+
+    field parent(&executor) -> FieldResult<Option<Category>> {
+        let context = executor.context();
+        match self.parent {
+            Some(ref parent) => Ok(context.categories_service.category(parent)?),
+            None => Ok(None),
+        }
+    }
+
+    field children(&executor) -> FieldResult<Vec<Category>> {
+        let context = executor.context();
+        Ok(context.categories_service.children(&self.title)?)
+    }
+});
+
+// Question resolves its revision history through the services, so it (and every type that embeds it) needs the Context.
+graphql_object!(Question: Context |&self| {
+    field id() -> Option<i32> {
+        self.id
+    }
+
+    field question() -> &str {
+        &self.question
+    }
+
+    field category() -> &str {
+        &self.category
+    }
+
+    field difficulty() -> Difficulty {
+        self.difficulty
+    }
+
+    field question_type() -> QuestionType {
+        self.question_type
+    }
+
+    field choices() -> &Vec<Choice> {
+        &self.choices
+    }
+
+    field explanation() -> &Option<String> {
+        &self.explanation
+    }
+
+    field sources() -> &Vec<Source> {
+        &self.sources
+    }
+
+    field media(variant: Option<MediaVariant>, width: Option<i32>) -> Vec<Attachment> {
+        let variant = MediaVariant::requested(variant, width).unwrap_or_default();
+        self.media.iter().map(|attachment| attachment.with_variant(variant)).collect()
+    }
+
+    field tags() -> &Vec<String> {
+        &self.tags
+    }
+
+    field free_text() -> &Option<FreeTextAnswer> {
+        &self.free_text
+    }
+
+    field ordering() -> &Option<OrderingAnswer> {
+        &self.ordering
+    }
+
+    field matching() -> &Option<MatchingAnswer> {
+        &self.matching
+    }
+
+    field numeric() -> &Option<NumericAnswer> {
+        &self.numeric
+    }
+
+    field revisions(&executor) -> FieldResult<Vec<QuestionRevision>> {
+        let context = executor.context();
+        match self.id {
+            Some(id) => Ok(context.questions_service.revisions(id)?),
+            None => Ok(vec![]),
+        }
+    }
+});
+
+graphql_object!(PublicQuestion: Context |&self| {
+    field id() -> Option<i32> {
+        self.0.id
+    }
+
+    field question() -> &str {
+        &self.0.question
+    }
+
+    field category() -> &str {
+        &self.0.category
+    }
+
+    field difficulty() -> Difficulty {
+        self.0.difficulty
+    }
+
+    field question_type() -> QuestionType {
+        self.0.question_type
+    }
+
+    field choices() -> Vec<PublicChoice> {
+        self.0.choices.iter().map(PublicChoice::new).collect()
+    }
+
+    field media(variant: Option<MediaVariant>, width: Option<i32>) -> Vec<Attachment> {
+        let variant = MediaVariant::requested(variant, width).unwrap_or_default();
+        self.0.media.iter().map(|attachment| attachment.with_variant(variant)).collect()
+    }
+
+    field tags() -> &Vec<String> {
+        &self.0.tags
+    }
+
+    field ordering() -> Option<PublicOrdering> {
+        self.0.ordering.as_ref().map(|ordering| ordering.public(&mut self.shuffler()))
+    }
+
+    field matching() -> Option<PublicMatching> {
+        self.0.matching.as_ref().map(|matching| matching.public(&mut self.shuffler()))
+    }
+
+    field numeric() -> Option<PublicNumeric> {
+        self.0.numeric.as_ref().map(NumericAnswer::public)
+    }
+});
+
+graphql_object!(QuestionSample: Context |&self| {
+    field seed() -> i32 {
+        self.seed
+    }
+
+    field questions() -> Vec<PublicQuestion> {
+        self.questions.iter().cloned().map(|question| PublicQuestion(question, Some(self.seed))).collect()
+    }
+});
+
+graphql_object!(PaginatedQuestions: Context |&self| {
+    field data() -> Vec<PublicQuestion> {
+        self.data.iter().cloned().map(|question| PublicQuestion(question, None)).collect()
+    }
+
+    field page() -> i32 {
+        self.page
+    }
+
+    field size() -> i32 {
+        self.size
+    }
+
+    field page_count() -> i32 {
+        self.page_count
+    }
+
+    field last() -> bool {
+        self.last
+    }
+
+    field highlights() -> &Vec<SearchHighlight> {
+        &self.highlights
+    }
+});
+
+graphql_object!(AdminQuestions: Context |&self| {
+    field data() -> &Vec<Question> {
+        &self.0.data
+    }
+
+    field page() -> i32 {
+        self.0.page
+    }
+
+    field size() -> i32 {
+        self.0.size
+    }
+
+    field page_count() -> i32 {
+        self.0.page_count
+    }
+
+    field last() -> bool {
+        self.0.last
+    }
+});
+
+graphql_object!(DeletedQuestion: Context |&self| {
+    field question() -> &Question {
+        &self.question
+    }
+
+    field deleted_on() -> &DateTime<Utc> {
+        &self.deleted_on
+    }
+});
+
+graphql_object!(Trash: Context |&self| {
+    field categories() -> &Vec<DeletedCategory> {
+        &self.categories
+    }
+
+    field questions() -> &Vec<DeletedQuestion> {
+        &self.questions
+    }
+});
+
 pub struct Query;
 
 graphql_object!(Query: Context |&self| {
@@ -60,32 +353,151 @@ graphql_object!(Query: Context |&self| {
         Ok(categories)
     }
 
-    field questions(&executor, category: String, page: Option<i32>, size: Option<i32>) -> FieldResult<PaginatedQuestions>{
+    field category_tree(&executor) -> FieldResult<Vec<CategoryNode>> {
+        let context = executor.context();
+        Ok(context.categories_service.category_tree()?)
+    }
+
+    field questions(&executor, category: String, include_descendants: Option<bool>, difficulties: Option<Vec<Difficulty>>, tags: Option<Vec<String>>, page: Option<i32>, size: Option<i32>) -> FieldResult<PaginatedQuestions>{
         let real_page = page.unwrap_or(1);
         let real_size = size.unwrap_or(10);
+        let filter = QuestionFilter{
+            category: category,
+            include_descendants: include_descendants.unwrap_or(false),
+            difficulties: difficulties.unwrap_or(vec![]),
+            tags: normalize_tags(&tags.unwrap_or(vec![])),
+            question_types: vec![],
+        };
         
         let context = executor.context();
-        let total_records = context.questions_service.count_questions(&category)? as i32;
-        let questions = context.questions_service.questions(&category,real_page as i64,real_size as i64)?;
+        let total_records = context.questions_service.count_questions(&filter)? as i32;
+        let questions = context.questions_service.questions(&filter,real_page as i64,real_size as i64)?;
         
         Ok(PaginatedQuestions::new(questions, real_page, total_records , real_size))
     }
+
+    field admin_questions(&executor, category: String, include_descendants: Option<bool>, difficulties: Option<Vec<Difficulty>>, tags: Option<Vec<String>>, page: Option<i32>, size: Option<i32>, digest: String) -> FieldResult<AdminQuestions> {
+        let context = executor.context();
+        context.authorization_service.verify_digest(&digest,"GET","/graphql")?;
+
+        let real_page = page.unwrap_or(1);
+        let real_size = size.unwrap_or(10);
+        let filter = QuestionFilter{
+            category: category,
+            include_descendants: include_descendants.unwrap_or(false),
+            difficulties: difficulties.unwrap_or(vec![]),
+            tags: normalize_tags(&tags.unwrap_or(vec![])),
+            question_types: vec![],
+        };
+
+        let total_records = context.questions_service.count_questions(&filter)? as i32;
+        let questions = context.questions_service.questions(&filter,real_page as i64,real_size as i64)?;
+
+        Ok(AdminQuestions(PaginatedQuestions::new(questions, real_page, total_records, real_size)))
+    }
+
+    field random_questions(&executor, category: String, count: i32, seed: Option<i32>, exclude_ids: Option<Vec<i32>>, include_descendants: Option<bool>, difficulties: Option<Vec<Difficulty>>, tags: Option<Vec<String>>) -> FieldResult<QuestionSample> {
+        let seed = seed.unwrap_or_else(sampling::random_seed);
+        let filter = QuestionFilter{
+            category: category,
+            include_descendants: include_descendants.unwrap_or(false),
+            difficulties: difficulties.unwrap_or(vec![]),
+            tags: normalize_tags(&tags.unwrap_or(vec![])),
+            question_types: vec![],
+        };
+
+        let context = executor.context();
+        let questions = context.questions_service.random_questions(&filter, count, seed, &exclude_ids.unwrap_or(vec![]))?;
+
+        Ok(QuestionSample{
+            seed: seed,
+            questions: questions,
+        })
+    }
+
+    field search_questions(&executor, text: String, category: Option<String>, page: Option<i32>, size: Option<i32>) -> FieldResult<PaginatedQuestions> {
+        let real_page = page.unwrap_or(1);
+        let real_size = size.unwrap_or(10);
+        let search = SearchFilter{
+            text: text,
+            category: category,
+        };
+
+        let context = executor.context();
+        let total_records = context.questions_service.count_search_results(&search)? as i32;
+        let results = context.questions_service.search_questions(&search, real_page as i64, real_size as i64)?;
+        let (questions, highlights) = results.into_iter().unzip();
+
+        Ok(PaginatedQuestions::new(questions, real_page, total_records, real_size).with_highlights(highlights))
+    }
+
+    field tags(&executor) -> FieldResult<Vec<TagCount>> {
+        let context = executor.context();
+        Ok(context.questions_service.tags()?)
+    }
+
+    field check_answer(&executor, question_id: i32, choice_id: i32) -> FieldResult<ChoiceCheck> {
+        let context = executor.context();
+        Ok(context.questions_service.check_choice_answer(question_id, choice_id)?)
+    }
+
+    field check_text_answer(&executor, question_id: i32, answer: String) -> FieldResult<AnswerCheck> {
+        let context = executor.context();
+        Ok(context.questions_service.check_text_answer(question_id, &answer)?)
+    }
+
+    field check_ordering_answer(&executor, question_id: i32, items: Vec<String>) -> FieldResult<AnswerCheck> {
+        let context = executor.context();
+        Ok(context.questions_service.check_ordering_answer(question_id, &items)?)
+    }
+
+    field check_matching_answer(&executor, question_id: i32, pairs: Vec<NewMatchingPair>) -> FieldResult<AnswerCheck> {
+        let context = executor.context();
+        let pairs: Vec<MatchingPair> = pairs.iter().map(NewMatchingPair::to_matching_pair).collect();
+        Ok(context.questions_service.check_matching_answer(question_id, &pairs)?)
+    }
+
+    field check_numeric_answer(&executor, question_id: i32, value: f64, unit: Option<String>) -> FieldResult<AnswerCheck> {
+        let context = executor.context();
+        Ok(context.questions_service.check_numeric_answer(question_id, value, unit.as_ref().map(|unit| unit.as_str()))?)
+    }
+
+    field me(&executor) -> FieldResult<Player> {
+        let context = executor.context();
+        let player_id = context.principal.player_id()?;
+        Ok(context.players_service.player(player_id)?.ok_or(AuthorizationError::InvalidToken)?)
+    }
+
+    field quiz_history(&executor) -> FieldResult<Vec<QuizResult>> {
+        let context = executor.context();
+        let player_id = context.principal.player_id()?;
+        context.players_service.player(player_id)?.ok_or(AuthorizationError::InvalidToken)?;
+        Ok(context.quiz_service.history(player_id)?)
+    }
+
+    field trash(&executor, digest: String) -> FieldResult<Trash> {
+        let context = executor.context();
+        context.authorization_service.verify_digest(&digest,"GET","/graphql")?;
+
+        Ok(Trash{
+            categories: context.categories_service.deleted_categories()?,
+            questions: context.questions_service.deleted_questions()?,
+        })
+    }
 });
 
 pub struct Mutation;
 
 graphql_object!(Mutation: Context |&self| {
-    field create_category(&executor, name: String, digest: String) -> FieldResult<Category> {
+    field create_category(&executor, name: String, parent: Option<String>, digest: String) -> FieldResult<Category> {
         let context = executor.context();
         context.authorization_service.verify_digest(&digest,"GET","/graphql")?;
 
-        let category = Category{
-            title: name,
-        };
+        let category = Category::new(name, parent);
         
         context.categories_service.save_category(&category)?;
 
-        Ok(category)
+        Ok(context.categories_service.category(&category.title)?.unwrap_or(category))
     }
 
     field activate_category(&executor, name: String, active: bool, digest: String) -> FieldResult<SaveCategoryStatus> {
@@ -98,18 +510,131 @@ graphql_object!(Mutation: Context |&self| {
 
     field create_question(&executor, new_question: NewQuestion, digest: String) -> FieldResult<Question>{
         let context = executor.context();
-        context.authorization_service.verify_digest(&digest,"GET","/graphql")?;
+        let author = context.authorization_service.verify_digest(&digest,"GET","/graphql")?;
+
+        let mut new_question = new_question;
+        context.media_service.store_uploads(new_question.attachments_mut())?;
+
+        let question = context.questions_service.save_question(&new_question.to_question(), &author)?;
+        Ok(question)
+    }
+
+    field update_question(&executor, id: i32, patch: QuestionPatch, digest: String) -> FieldResult<Question>{
+        let context = executor.context();
+        let author = context.authorization_service.verify_digest(&digest,"GET","/graphql")?;
 
-        let question = context.questions_service.save_question(&Question{
-            id: None,
-            question: new_question.question,
-            category: new_question.category,
-            choices: new_question.choices.iter().map(|choice| Choice{
-                id: None,
-                title: choice.title.clone(),
-                correct: choice.correct,
-            }).collect(),
-        })?;
+        let mut patch = patch;
+        context.media_service.store_uploads(patch.attachments_mut())?;
+
+        let question = context.questions_service.update_question(id, &patch, &author)?;
         Ok(question)
     }
+
+    field revert_question(&executor, id: i32, revision: i32, digest: String) -> FieldResult<Question>{
+        let context = executor.context();
+        let author = context.authorization_service.verify_digest(&digest,"GET","/graphql")?;
+
+        let question = context.questions_service.revert_question(id, revision, &author)?;
+        Ok(question)
+    }
+
+    field delete_question(&executor, id: i32, digest: String) -> FieldResult<bool> {
+        let context = executor.context();
+        context.authorization_service.verify_digest(&digest,"GET","/graphql")?;
+
+        Ok(context.questions_service.delete_question(id)?)
+    }
+
+    field restore_question(&executor, id: i32, digest: String) -> FieldResult<bool> {
+        let context = executor.context();
+        context.authorization_service.verify_digest(&digest,"GET","/graphql")?;
+
+        Ok(context.questions_service.restore_question(id)?)
+    }
+
+    field rename_category(&executor, from: String, to: String, digest: String) -> FieldResult<Category> {
+        let context = executor.context();
+        context.authorization_service.verify_digest(&digest,"GET","/graphql")?;
+
+        Ok(context.categories_service.rename_category(&from, &to)?)
+    }
+
+    field merge_categories(&executor, sources: Vec<String>, target: String, digest: String) -> FieldResult<Category> {
+        let context = executor.context();
+        context.authorization_service.verify_digest(&digest,"GET","/graphql")?;
+
+        Ok(context.categories_service.merge_categories(&sources, &target)?)
+    }
+
+    field update_category(&executor, name: String, patch: CategoryPatch, digest: String) -> FieldResult<Category> {
+        let context = executor.context();
+        context.authorization_service.verify_digest(&digest,"GET","/graphql")?;
+
+        Ok(context.categories_service.update_category(&name, &patch)?)
+    }
+
+    field set_category_parent(&executor, name: String, parent: Option<String>, digest: String) -> FieldResult<Category> {
+        let context = executor.context();
+        context.authorization_service.verify_digest(&digest,"GET","/graphql")?;
+
+        Ok(context.categories_service.set_category_parent(&name, parent.as_ref().map(|p| p.as_str()))?)
+    }
+
+    field delete_category(&executor, name: String, digest: String) -> FieldResult<bool> {
+        let context = executor.context();
+        context.authorization_service.verify_digest(&digest,"GET","/graphql")?;
+
+        Ok(context.categories_service.delete_category(&name)?)
+    }
+
+    field restore_category(&executor, name: String, digest: String) -> FieldResult<bool> {
+        let context = executor.context();
+        context.authorization_service.verify_digest(&digest,"GET","/graphql")?;
+
+        Ok(context.categories_service.restore_category(&name)?)
+    }
+
+    field register(&executor, username: String, password: String) -> FieldResult<AuthTokens> {
+        let context = executor.context();
+        Ok(context.players_service.register(&username, &password)?)
+    }
+
+    field login(&executor, username: String, password: String) -> FieldResult<AuthTokens> {
+        let context = executor.context();
+        Ok(context.players_service.login(&username, &password)?)
+    }
+
+    field refresh_tokens(&executor, refresh_token: String) -> FieldResult<AuthTokens> {
+        let context = executor.context();
+        Ok(context.players_service.refresh_tokens(&refresh_token)?)
+    }
+
+    field device_login(&executor, device_id: String) -> FieldResult<AuthTokens> {
+        let context = executor.context();
+        Ok(context.players_service.device_login(&device_id)?)
+    }
+
+    field link_account(&executor, username: String, password: String) -> FieldResult<AuthTokens> {
+        let context = executor.context();
+        let player_id = context.principal.player_id()?;
+        Ok(context.players_service.link_account(player_id, &username, &password)?)
+    }
+
+    field start_quiz(&executor, category: String, count: i32, include_descendants: Option<bool>) -> FieldResult<StartedQuiz> {
+        let context = executor.context();
+        let player_id = context.players_service.existing_player_id(&context.principal)?;
+        Ok(context.quiz_service.start(&category, count, include_descendants.unwrap_or(false), player_id)?)
+    }
+
+    field submit_answer(&executor, session_id: String, question_id: i32, choice_id: i32) -> FieldResult<GradedAnswer> {
+        let context = executor.context();
+        let player_id = context.players_service.existing_player_id(&context.principal)?;
+        Ok(context.quiz_service.submit_answer(&session_id, question_id, choice_id, player_id)?)
+    }
+
+    field finish_quiz(&executor, session_id: String) -> FieldResult<QuizResult> {
+        let context = executor.context();
+        let player_id = context.players_service.existing_player_id(&context.principal)?;
+        Ok(context.quiz_service.finish(&session_id, player_id)?)
+    }
 });
@@ -1,78 +1,167 @@
 mod controllers;
+mod media;
+mod migrations;
 mod models;
+mod multipart;
 mod repositories;
+mod sampling;
 mod services;
+mod thumbnails;
+mod tokens;
 
+extern crate argon2;
+extern crate base64;
 extern crate futures;
 extern crate futures_cpupool;
 extern crate hyper;
+extern crate image;
 extern crate tokio_io_pool;
 #[macro_use]
 extern crate juniper;
 #[macro_use]
 extern crate juniper_codegen;
 
+extern crate hmac;
 extern crate md5;
 extern crate postgres;
 extern crate r2d2;
 extern crate r2d2_postgres;
+extern crate r2d2_sqlite;
+#[macro_use]
+extern crate rusqlite;
 extern crate serde;
 extern crate serde_derive;
 extern crate serde_json;
+extern crate sha2;
 extern crate uuid;
+extern crate chrono;
 
 extern crate log;
 extern crate simple_logger;
+extern crate unicode_normalization;
 extern crate url;
 
 use std::env;
+use std::process;
 use std::sync::Arc;
 
-use hyper::header::HeaderMap;
+use hyper::header::{HeaderMap, AUTHORIZATION};
 use hyper::rt::Future;
 use hyper::service::service_fn;
 use hyper::{Body, Response, Server};
 use hyper::{Method, StatusCode};
 use juniper::RootNode;
 
-use r2d2::Pool;
-use r2d2_postgres::{PostgresConnectionManager, TlsMode};
-
+use chrono::{Duration, Utc};
 use futures::future;
 use futures_cpupool::CpuPool;
-use log::{error, info};
+use log::{error, info, warn};
+use uuid::Uuid;
 
 use controllers::*;
+use migrations::Migrator;
 use repositories::*;
 use services::*;
 
+const DEFAULT_TRASH_RETENTION_DAYS: i64 = 30;
+const DEFAULT_MEDIA_DIR: &str = "./media";
+const DEFAULT_MEDIA_MAX_BYTES: usize = 5 * 1024 * 1024;
+
 fn main() {
     simple_logger::init_with_level(log::Level::Debug).unwrap();
 
+    let conn_string = env::var("DB_CONN_STRING").expect("DB_CONN_STRING required");
+    println!("DB_CONN_STRING: {}", conn_string);
+
+    let storage = match Storage::connect(&conn_string) {
+        Ok(storage) => storage,
+        Err(e) => {
+            error!("Failed to connect to the storage: {}", e);
+            process::exit(1);
+        }
+    };
+
+    // The in-memory storage starts empty every time and has no schema to migrate.
+    let migrator = match storage {
+        Storage::Postgres(ref pool) => Some(Migrator::postgres(pool.clone())),
+        Storage::Sqlite(ref pool) => Some(Migrator::sqlite(pool.clone())),
+        Storage::InMemory(_) => None,
+    };
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(|arg| arg.as_str()) == Some("migrate") {
+        let command = args.get(1).map(|arg| arg.as_str()).unwrap_or("status");
+        match migrator {
+            Some(ref migrator) => process::exit(migrate(migrator, command)),
+            None => {
+                eprintln!("Storage '{}' does not use migrations.", conn_string);
+                process::exit(0);
+            }
+        }
+    }
+
+    if let Some(ref migrator) = migrator {
+        match migrator.up() {
+            Ok(count) => info!("Applied {} pending migration(s).", count),
+            Err(e) => {
+                error!("Failed to apply migrations: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    let categories_repository = storage.categories_repository();
+    let questions_repository = storage.questions_repository();
+    let quiz_repository = storage.quiz_repository();
+    let players_repository = storage.players_repository();
+
+    let media_dir = env::var("MEDIA_DIR").unwrap_or(DEFAULT_MEDIA_DIR.to_owned());
+    let media_max_bytes = env::var("MEDIA_MAX_BYTES")
+        .map(|bytes| bytes.parse().expect("invalid MEDIA_MAX_BYTES"))
+        .unwrap_or(DEFAULT_MEDIA_MAX_BYTES);
+    let media_storage: Arc<dyn MediaStorage> = Arc::new(LocalMediaStorage::new(&media_dir).unwrap());
+
+    multipart::clear_upload_dir();
+
+    let categories_service = CategoriesService::new(categories_repository);
+    let quiz_service = QuizService::new(quiz_repository, questions_repository.clone());
+    let questions_service = QuestionsService::new(questions_repository, media_storage.clone());
+    let media_service = MediaService::new(media_storage, media_max_bytes);
+
+    if args.first().map(|arg| arg.as_str()) == Some("purge") {
+        let retention_days = args
+            .get(1)
+            .map(|days| days.parse().expect("invalid retention days"))
+            .unwrap_or(DEFAULT_TRASH_RETENTION_DAYS);
+        process::exit(purge(&categories_service, &questions_service, retention_days));
+    }
+
     let admin_username = env::var("ADMIN_USERNAME").expect("ADMIN_USERNAME required");
     let admin_password = env::var("ADMIN_PASSWORD").expect("ADMIN_PASSWORD required");
-    let conn_string = env::var("DB_CONN_STRING").expect("DB_CONN_STRING required");
     let listen_addr = env::var_os("LISTEN_ADDRESS")
         .map(|addr| addr.into_string().expect("invalid LISTEN_ADDRESS"))
         .unwrap_or("127.0.0.1:3000".to_owned());
 
-    println!("DB_CONN_STRING: {}", conn_string);
     println!("LISTEN_ADDRESS: {}", listen_addr);
+    println!("MEDIA_DIR: {}", media_dir);
 
-    let manager = PostgresConnectionManager::new(conn_string, TlsMode::None).unwrap();
-    let pool = Pool::new(manager).unwrap();
-
-    let categories_repository = CategoriesRepository::new(pool.clone());
-    let questions_repository = QuestionsRepository::new(pool.clone());
+    // Player tokens are signed with this key, so every server behind a load balancer needs the same one.
+    let token_secret = env::var("TOKEN_SECRET").unwrap_or_else(|_| {
+        warn!("TOKEN_SECRET not set, player tokens will be invalid after a restart.");
+        format!("{}{}", Uuid::new_v4().to_simple(), Uuid::new_v4().to_simple())
+    });
 
-    let categories_service = CategoriesService::new(categories_repository);
-    let questions_service = QuestionsService::new(questions_repository);
     let authorization_service = AuthorizationService::new(&admin_username, &admin_password);
+    let players_service = PlayersService::new(players_repository, token_secret.as_bytes());
 
     let context = Arc::new(Context {
-        categories_service: categories_service,
-        questions_service: questions_service,
-        authorization_service: authorization_service,
+        categories_service: Arc::new(categories_service),
+        questions_service: Arc::new(questions_service),
+        quiz_service: Arc::new(quiz_service),
+        media_service: Arc::new(media_service),
+        authorization_service: Arc::new(authorization_service),
+        players_service: Arc::new(players_service),
+        principal: Principal::Anonymous,
     });
 
     let cpu_pool = CpuPool::new(4);
@@ -85,7 +174,11 @@ fn main() {
         service_fn(move |req| -> Box<Future<Item = _, Error = _> + Send> {
             let cpu_pool = cpu_pool.clone();
             let root_node = root_node.clone();
-            let ctx = ctx.clone();
+            let authorization = req
+                .headers()
+                .get(AUTHORIZATION)
+                .and_then(|header| header.to_str().ok());
+            let ctx = Arc::new(ctx.for_request(authorization));
             match (req.method(), req.uri().path()) {
                 (&Method::GET, "/") => Box::new(juniper_hyper::graphiql("/graphql")),
                 (&Method::GET, "/authenticate") => {
@@ -101,9 +194,17 @@ fn main() {
                 (&Method::GET, "/graphql") => {
                     Box::new(juniper_hyper::graphql(cpu_pool, root_node, ctx, req))
                 }
+                (&Method::POST, "/graphql") if multipart::is_multipart(&req) => {
+                    multipart::graphql(cpu_pool, root_node, ctx, req)
+                }
                 (&Method::POST, "/graphql") => {
                     Box::new(juniper_hyper::graphql(cpu_pool, root_node, ctx, req))
                 }
+                (&Method::POST, "/media") => media::upload(cpu_pool, ctx, req),
+                (&Method::GET, path) if path.starts_with("/media/") => {
+                    let media_id = path["/media/".len()..].to_owned();
+                    media::download(cpu_pool, ctx, media_id, req.uri().query())
+                }
                 _ => {
                     let mut response = Response::new(Body::empty());
                     *response.status_mut() = StatusCode::NOT_FOUND;
@@ -131,3 +232,63 @@ fn main() {
 
     tokio_io_pool::run(server);
 }
+
+/// Runs `quizzical migrate <up|down|status>` and returns the process exit code.
+fn migrate(migrator: &Migrator, command: &str) -> i32 {
+    let result = match command {
+        "up" => migrator
+            .up()
+            .map(|count| println!("Applied {} migration(s).", count)),
+        "down" => migrator.down().map(|reverted| match reverted {
+            Some(version) => println!("Reverted migration {:04}.", version),
+            None => println!("No migrations to revert."),
+        }),
+        "status" => migrator.status().map(|statuses| {
+            for status in statuses {
+                println!("{}", status);
+            }
+        }),
+        _ => {
+            eprintln!("usage: quizzical migrate <up|down|status>");
+            return 2;
+        }
+    };
+
+    match result {
+        Ok(_) => 0,
+        Err(e) => {
+            eprintln!("migrate {} failed: {}", command, e);
+            1
+        }
+    }
+}
+
+/// Runs `quizzical purge [retention-days]`, permanently removing questions and categories
+/// that have been in the trash for longer than the retention period. Returns the process exit code.
+fn purge(
+    categories_service: &CategoriesService,
+    questions_service: &QuestionsService,
+    retention_days: i64,
+) -> i32 {
+    let before = Utc::now() - Duration::days(retention_days);
+
+    let result = questions_service.purge_questions(before).and_then(|questions| {
+        categories_service
+            .purge_categories(before)
+            .map(|categories| (questions, categories))
+    });
+
+    match result {
+        Ok((questions, categories)) => {
+            println!(
+                "Purged {} question(s) and {} category(ies) deleted before {}.",
+                questions, categories, before
+            );
+            0
+        }
+        Err(e) => {
+            eprintln!("purge failed: {}", e);
+            1
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use std::sync::Arc;
+
+use futures::future::{self, Either};
+use futures::{Future, Stream};
+use futures_cpupool::CpuPool;
+use hyper::header::{AUTHORIZATION, CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE};
+use hyper::{Body, Request, Response, StatusCode};
+use serde_derive::Serialize;
+
+use controllers::Context;
+use models::{MediaType, MediaVariant};
+use services::{ServiceError, UploadedMedia};
+
+/// The JSON body returned by `POST /media`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UploadResponse {
+    id: String,
+    url: String,
+    mime_type: &'static str,
+    kind: &'static str,
+    size: usize,
+}
+
+impl UploadResponse {
+    fn new(media: UploadedMedia) -> UploadResponse {
+        UploadResponse {
+            url: format!("/media/{}", media.media_id),
+            id: media.media_id,
+            mime_type: media.media_type.mime_type,
+            kind: media.media_type.kind.as_str(),
+            size: media.size,
+        }
+    }
+}
+
+enum BodyError {
+    TooLarge,
+    Hyper(::hyper::Error),
+}
+
+/// Handles `POST /media`. The request body is the raw file and the `Content-Type` header its MIME type.
+/// Uploads are limited to administrators, authenticated with the same digest as the GraphQL mutations.
+pub fn upload(
+    cpu_pool: CpuPool,
+    ctx: Arc<Context>,
+    req: Request<Body>,
+) -> Box<Future<Item = Response<Body>, Error = ::hyper::Error> + Send> {
+    let digest = header(&req, AUTHORIZATION.as_str());
+    if let Err(e) = ctx
+        .authorization_service
+        .verify_digest(&digest, "POST", "/media")
+    {
+        return Box::new(future::ok(error(
+            StatusCode::UNAUTHORIZED,
+            &format!("{}", e),
+        )));
+    }
+
+    let media_type =
+        match MediaType::from_mime_type(&header(&req, CONTENT_TYPE.as_str())) {
+            Some(media_type) => media_type,
+            None => return Box::new(future::ok(error(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "Only PNG, JPEG, GIF and WebP images and MP3, Ogg and WAV audio can be uploaded",
+            ))),
+        };
+
+    let max_bytes = ctx.media_service.max_bytes;
+    let too_large = format!("Media may not be larger than {} bytes", max_bytes);
+
+    // Rejected before reading the body when the client announces its size.
+    let declared_size = header(&req, CONTENT_LENGTH.as_str()).parse::<usize>().ok();
+    if declared_size.map(|size| size > max_bytes).unwrap_or(false) {
+        return Box::new(future::ok(error(StatusCode::PAYLOAD_TOO_LARGE, &too_large)));
+    }
+
+    let body =
+        req.into_body()
+            .map_err(BodyError::Hyper)
+            .fold(Vec::new(), move |mut data, chunk| {
+                if data.len() + chunk.len() > max_bytes {
+                    return Err(BodyError::TooLarge);
+                }
+                data.extend_from_slice(&chunk);
+                Ok(data)
+            });
+
+    Box::new(body.then(move |result| {
+        match result {
+            Ok(data) => Either::A(
+                cpu_pool
+                    .spawn_fn(move || ctx.media_service.upload(media_type, &data))
+                    .then(|result| {
+                        Ok(match result {
+                            Ok(media) => json(StatusCode::CREATED, &UploadResponse::new(media)),
+                            Err(ServiceError::ValidationError(e)) => {
+                                error(StatusCode::BAD_REQUEST, &format!("{}", e))
+                            }
+                            Err(ServiceError::RepositoryError(e)) => {
+                                error(StatusCode::INTERNAL_SERVER_ERROR, &format!("{}", e))
+                            }
+                            Err(ServiceError::AuthorizationError(e)) => {
+                                error(StatusCode::UNAUTHORIZED, &format!("{}", e))
+                            }
+                        })
+                    }),
+            ),
+            Err(BodyError::TooLarge) => {
+                Either::B(future::ok(error(StatusCode::PAYLOAD_TOO_LARGE, &too_large)))
+            }
+            Err(BodyError::Hyper(e)) => Either::B(future::err(e)),
+        }
+    }))
+}
+
+/// Handles `GET /media/<id>` and `GET /media/<id>?variant=<thumbnail|medium>`.
+/// Media never changes once uploaded, so it may be cached indefinitely.
+pub fn download(
+    cpu_pool: CpuPool,
+    ctx: Arc<Context>,
+    media_id: String,
+    query: Option<&str>,
+) -> Box<Future<Item = Response<Body>, Error = ::hyper::Error> + Send> {
+    let requested = query
+        .unwrap_or("")
+        .split('&')
+        .filter_map(|pair| {
+            let mut parts = pair.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some("variant"), Some(name)) => Some(name),
+                _ => None,
+            }
+        })
+        .last();
+    let variant = match requested.map(MediaVariant::from_str) {
+        Some(Some(variant)) => variant,
+        Some(None) => {
+            return Box::new(future::ok(error(
+                StatusCode::BAD_REQUEST,
+                "The variant must be original, thumbnail or medium",
+            )))
+        }
+        None => MediaVariant::Original,
+    };
+
+    Box::new(
+        cpu_pool
+            .spawn_fn(move || ctx.media_service.load(&media_id, variant))
+            .then(|result| {
+                Ok(match result {
+                    Ok(Some((media_type, data))) => {
+                        let mut response = Response::new(Body::from(data));
+                        let headers = response.headers_mut();
+                        headers.insert(CONTENT_TYPE, media_type.mime_type.parse().unwrap());
+                        headers.insert(
+                            CACHE_CONTROL,
+                            "public, max-age=31536000, immutable".parse().unwrap(),
+                        );
+                        response
+                    }
+                    Ok(None) => error(StatusCode::NOT_FOUND, "Media not found"),
+                    Err(ServiceError::ValidationError(e)) => {
+                        error(StatusCode::UNPROCESSABLE_ENTITY, &format!("{}", e))
+                    }
+                    Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, &format!("{}", e)),
+                })
+            }),
+    )
+}
+
+fn header(req: &Request<Body>, name: &str) -> String {
+    req.headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("")
+        .to_owned()
+}
+
+fn json<T: ::serde::Serialize>(status: StatusCode, body: &T) -> Response<Body> {
+    let mut response = Response::new(Body::from(serde_json::to_string(body).unwrap()));
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, "application/json".parse().unwrap());
+    response
+}
+
+#[derive(Serialize)]
+struct ErrorResponse<'a> {
+    error: &'a str,
+}
+
+fn error(status: StatusCode, message: &str) -> Response<Body> {
+    json(status, &ErrorResponse { error: message })
+}
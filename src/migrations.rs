@@ -0,0 +1,349 @@
+use log::info;
+use r2d2::Pool;
+use r2d2_postgres::PostgresConnectionManager;
+use r2d2_sqlite::SqliteConnectionManager;
+use repositories::RepositoryError;
+use std::fmt;
+
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    pub up: &'static str,
+    pub down: &'static str,
+}
+
+macro_rules! migration {
+    ($dialect:expr, $version:expr, $name:expr) => {
+        Migration {
+            version: $version,
+            name: $name,
+            up: include_str!(concat!("../migrations/", $dialect, "/", $name, ".up.sql")),
+            down: include_str!(concat!("../migrations/", $dialect, "/", $name, ".down.sql")),
+        }
+    };
+}
+
+// Migrations are embedded in the binary so that every build knows exactly which schema it expects.
+// New migrations must be appended to both dialects with the next version number; applied migrations must never be edited.
+pub static POSTGRES_MIGRATIONS: &[Migration] = &[
+    migration!("postgres", 1, "0001_initial_schema"),
+    migration!("postgres", 2, "0002_soft_delete"),
+    migration!("postgres", 3, "0003_question_revisions"),
+    migration!("postgres", 4, "0004_category_aliases"),
+    migration!("postgres", 5, "0005_category_hierarchy"),
+    migration!("postgres", 6, "0006_category_metadata"),
+    migration!("postgres", 7, "0007_question_difficulty"),
+    migration!("postgres", 8, "0008_question_types"),
+    migration!("postgres", 9, "0009_free_text_answers"),
+    migration!("postgres", 10, "0010_ordering_matching"),
+    migration!("postgres", 11, "0011_numeric_answers"),
+    migration!("postgres", 12, "0012_explanations_sources"),
+    migration!("postgres", 13, "0013_media"),
+    migration!("postgres", 14, "0014_tags"),
+    migration!("postgres", 15, "0015_search"),
+    migration!("postgres", 16, "0016_quiz_sessions"),
+    migration!("postgres", 17, "0017_players"),
+    migration!("postgres", 18, "0018_device_players"),
+    migration!("postgres", 19, "0019_quiz_session_players"),
+];
+
+pub static SQLITE_MIGRATIONS: &[Migration] = &[
+    migration!("sqlite", 1, "0001_initial_schema"),
+    migration!("sqlite", 2, "0002_soft_delete"),
+    migration!("sqlite", 3, "0003_question_revisions"),
+    migration!("sqlite", 4, "0004_category_aliases"),
+    migration!("sqlite", 5, "0005_category_hierarchy"),
+    migration!("sqlite", 6, "0006_category_metadata"),
+    migration!("sqlite", 7, "0007_question_difficulty"),
+    migration!("sqlite", 8, "0008_question_types"),
+    migration!("sqlite", 9, "0009_free_text_answers"),
+    migration!("sqlite", 10, "0010_ordering_matching"),
+    migration!("sqlite", 11, "0011_numeric_answers"),
+    migration!("sqlite", 12, "0012_explanations_sources"),
+    migration!("sqlite", 13, "0013_media"),
+    migration!("sqlite", 14, "0014_tags"),
+    migration!("sqlite", 15, "0015_search"),
+    migration!("sqlite", 16, "0016_quiz_sessions"),
+    migration!("sqlite", 17, "0017_players"),
+    migration!("sqlite", 18, "0018_device_players"),
+    migration!("sqlite", 19, "0019_quiz_session_players"),
+];
+
+pub struct MigrationStatus {
+    pub name: &'static str,
+    pub applied: bool,
+}
+
+impl fmt::Display for MigrationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:<40} {}",
+            self.name,
+            if self.applied { "applied" } else { "pending" }
+        )
+    }
+}
+
+/// The database operations a migrator needs from a storage backend.
+trait SchemaVersions {
+    fn applied_versions(&self) -> Result<Vec<i32>, RepositoryError>;
+
+    /// Runs `script` and records (or removes) the migration's version in a single transaction.
+    fn run(
+        &self,
+        migration: &Migration,
+        script: &str,
+        applied: bool,
+    ) -> Result<(), RepositoryError>;
+}
+
+struct PostgresSchemaVersions {
+    pool: Pool<PostgresConnectionManager>,
+}
+
+impl SchemaVersions for PostgresSchemaVersions {
+    fn applied_versions(&self) -> Result<Vec<i32>, RepositoryError> {
+        let conn = self.pool.get()?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS schema_version (
+                version INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                applied_on TIMESTAMP NOT NULL DEFAULT now()
+            )",
+            &[],
+        )?;
+
+        let rows = conn.query("SELECT version FROM schema_version ORDER BY version", &[])?;
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    }
+
+    fn run(
+        &self,
+        migration: &Migration,
+        script: &str,
+        applied: bool,
+    ) -> Result<(), RepositoryError> {
+        let conn = self.pool.get()?;
+        let trans = conn.transaction()?;
+
+        trans.batch_execute(script)?;
+        if applied {
+            trans.execute(
+                "INSERT INTO schema_version (version, name) VALUES ($1, $2)",
+                &[&migration.version, &migration.name],
+            )?;
+        } else {
+            trans.execute(
+                "DELETE FROM schema_version WHERE version = $1",
+                &[&migration.version],
+            )?;
+        }
+
+        trans.commit().map_err(|e| e.into())
+    }
+}
+
+struct SqliteSchemaVersions {
+    pool: Pool<SqliteConnectionManager>,
+}
+
+impl SchemaVersions for SqliteSchemaVersions {
+    fn applied_versions(&self) -> Result<Vec<i32>, RepositoryError> {
+        let conn = self.pool.get()?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS schema_version (
+                version INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                applied_on TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )",
+        )?;
+
+        let mut statement = conn.prepare("SELECT version FROM schema_version ORDER BY version")?;
+        let versions = statement
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<i32>, _>>()?;
+        Ok(versions)
+    }
+
+    fn run(
+        &self,
+        migration: &Migration,
+        script: &str,
+        applied: bool,
+    ) -> Result<(), RepositoryError> {
+        let mut conn = self.pool.get()?;
+        let trans = conn.transaction()?;
+
+        trans.execute_batch(script)?;
+        if applied {
+            trans.execute(
+                "INSERT INTO schema_version (version, name) VALUES (?1, ?2)",
+                params![migration.version, migration.name],
+            )?;
+        } else {
+            trans.execute(
+                "DELETE FROM schema_version WHERE version = ?1",
+                params![migration.version],
+            )?;
+        }
+
+        trans.commit().map_err(|e| e.into())
+    }
+}
+
+pub struct Migrator {
+    migrations: &'static [Migration],
+    schema_versions: Box<dyn SchemaVersions>,
+}
+
+impl Migrator {
+    pub fn postgres(pool: Pool<PostgresConnectionManager>) -> Migrator {
+        Migrator {
+            migrations: POSTGRES_MIGRATIONS,
+            schema_versions: Box::new(PostgresSchemaVersions { pool: pool }),
+        }
+    }
+
+    pub fn sqlite(pool: Pool<SqliteConnectionManager>) -> Migrator {
+        Migrator {
+            migrations: SQLITE_MIGRATIONS,
+            schema_versions: Box::new(SqliteSchemaVersions { pool: pool }),
+        }
+    }
+
+    pub fn status(&self) -> Result<Vec<MigrationStatus>, RepositoryError> {
+        let applied = self.schema_versions.applied_versions()?;
+
+        Ok(self
+            .migrations
+            .iter()
+            .map(|migration| MigrationStatus {
+                name: migration.name,
+                applied: applied.contains(&migration.version),
+            })
+            .collect())
+    }
+
+    /// Applies every pending migration in version order, each in its own transaction.
+    /// Returns the number of migrations that were applied.
+    pub fn up(&self) -> Result<usize, RepositoryError> {
+        let applied = self.schema_versions.applied_versions()?;
+        let mut count = 0;
+
+        for migration in self
+            .migrations
+            .iter()
+            .filter(|migration| !applied.contains(&migration.version))
+        {
+            info!("Applying migration {}.", migration.name);
+            self.schema_versions.run(migration, migration.up, true)?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Reverts the most recently applied migration.
+    /// Returns the version that was reverted, or `None` if no migrations have been applied.
+    pub fn down(&self) -> Result<Option<i32>, RepositoryError> {
+        let applied = self.schema_versions.applied_versions()?;
+
+        let migration = match applied
+            .last()
+            .and_then(|version| self.migrations.iter().find(|m| m.version == *version))
+        {
+            Some(migration) => migration,
+            None => return Ok(None),
+        };
+
+        info!("Reverting migration {}.", migration.name);
+        self.schema_versions.run(migration, migration.down, false)?;
+
+        Ok(Some(migration.version))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory_pool() -> Pool<SqliteConnectionManager> {
+        // A single connection, as every connection to `:memory:` opens a database of its own.
+        let manager = SqliteConnectionManager::memory()
+            .with_init(|c| c.execute_batch("PRAGMA foreign_keys = ON;"));
+        Pool::builder().max_size(1).build(manager).unwrap()
+    }
+
+    fn tables(pool: &Pool<SqliteConnectionManager>) -> Vec<String> {
+        let conn = pool.get().unwrap();
+        let mut statement = conn
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name")
+            .unwrap();
+        let names = statement
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<Vec<String>, _>>()
+            .unwrap();
+        names
+    }
+
+    fn applied(migrator: &Migrator) -> Vec<bool> {
+        migrator
+            .status()
+            .unwrap()
+            .iter()
+            .map(|status| status.applied)
+            .collect()
+    }
+
+    #[test]
+    fn dialects_have_the_same_migrations() {
+        assert_eq!(POSTGRES_MIGRATIONS.len(), SQLITE_MIGRATIONS.len());
+        for (index, (postgres, sqlite)) in POSTGRES_MIGRATIONS
+            .iter()
+            .zip(SQLITE_MIGRATIONS)
+            .enumerate()
+        {
+            assert_eq!(postgres.version, index as i32 + 1);
+            assert_eq!(sqlite.version, postgres.version);
+            assert_eq!(sqlite.name, postgres.name);
+        }
+    }
+
+    #[test]
+    fn applies_and_reverts_every_sqlite_migration() {
+        let pool = memory_pool();
+        let migrator = Migrator::sqlite(pool.clone());
+        let count = SQLITE_MIGRATIONS.len();
+
+        assert_eq!(applied(&migrator), vec![false; count]);
+        assert_eq!(migrator.down().unwrap(), None);
+
+        assert_eq!(migrator.up().unwrap(), count);
+        assert_eq!(applied(&migrator), vec![true; count]);
+        assert_eq!(migrator.up().unwrap(), 0);
+        let schema = tables(&pool);
+        assert!(schema.contains(&"quiz_sessions".to_string()));
+
+        for version in (1..count as i32 + 1).rev() {
+            assert_eq!(migrator.down().unwrap(), Some(version));
+            let status = applied(&migrator);
+            assert!(status[..version as usize - 1].iter().all(|a| *a));
+            assert!(status[version as usize - 1..].iter().all(|a| !*a));
+        }
+        assert_eq!(migrator.down().unwrap(), None);
+        assert_eq!(tables(&pool), vec!["schema_version".to_string()]);
+
+        // The down migrations leave nothing behind that would stop the schema from being built again.
+        assert_eq!(migrator.up().unwrap(), count);
+        assert_eq!(tables(&pool), schema);
+        let conn = pool.get().unwrap();
+        let violations: i64 = conn
+            .query_row("SELECT count(*) FROM pragma_foreign_key_check", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(violations, 0);
+    }
+}
@@ -1,63 +1,2261 @@
+use chrono::{DateTime, Utc};
+use juniper::Value;
+use sampling::SplitMix64;
 use serde_derive::{Deserialize, Serialize};
+use std::cmp;
 use std::fmt;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+use url::Url;
 
 #[derive(Debug)]
 pub struct ValidationError {
     message: String,
 }
 
+impl ValidationError {
+    pub fn new(message: String) -> ValidationError {
+        ValidationError { message: message }
+    }
+}
+
 impl std::fmt::Display for ValidationError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", self.message)
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, GraphQLObject)]
-pub struct Category {
-    pub title: String,
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Category {
+    pub title: String,
+    /// The title of the category this category is nested in, if any.
+    pub parent: Option<String>,
+    pub description: Option<String>,
+    /// A reference to the icon shown for the category, e.g. an asset name or URL.
+    pub icon: Option<String>,
+    /// Accent colour as a hex code, e.g. `#1E88E5`.
+    pub colour: Option<String>,
+    /// Categories are listed in ascending display order, then by title.
+    pub display_order: i32,
+    pub featured: bool,
+}
+
+impl Category {
+    /// A category without any metadata.
+    pub fn new(title: String, parent: Option<String>) -> Category {
+        Category {
+            title: title,
+            parent: parent,
+            description: None,
+            icon: None,
+            colour: None,
+            display_order: 0,
+            featured: false,
+        }
+    }
+
+    pub fn validate(category: &Category) -> Result<(), ValidationError> {
+        if let Some(ref colour) = category.colour {
+            let digits = colour.trim_start_matches('#');
+            if !colour.starts_with('#')
+                || !(digits.len() == 3 || digits.len() == 6)
+                || !digits.chars().all(|c| c.is_ascii_hexdigit())
+            {
+                return Err(ValidationError {
+                    message: format!(
+                        "Colour '{}' must be a hex code such as '#1E88E5' or '#FFF'",
+                        colour
+                    ),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Changes to a category's metadata. Fields that are not provided are left unchanged;
+/// an empty description, icon or colour removes it.
+#[derive(GraphQLInputObject)]
+pub struct CategoryPatch {
+    pub description: Option<String>,
+    pub icon: Option<String>,
+    pub colour: Option<String>,
+    pub display_order: Option<i32>,
+    pub featured: Option<bool>,
+}
+
+impl CategoryPatch {
+    pub fn apply(&self, category: &mut Category) {
+        if let Some(ref description) = self.description {
+            category.description = non_empty(description);
+        }
+        if let Some(ref icon) = self.icon {
+            category.icon = non_empty(icon);
+        }
+        if let Some(ref colour) = self.colour {
+            category.colour = non_empty(colour);
+        }
+        if let Some(display_order) = self.display_order {
+            category.display_order = display_order;
+        }
+        if let Some(featured) = self.featured {
+            category.featured = featured;
+        }
+    }
+}
+
+/// Patches clear optional text fields by setting them to an empty string.
+fn non_empty(value: &String) -> Option<String> {
+    match value.trim() {
+        "" => None,
+        trimmed => Some(trimmed.to_string()),
+    }
+}
+
+/// A category and all of its visible descendants, as returned by `categoryTree`.
+#[derive(Debug, GraphQLObject)]
+pub struct CategoryNode {
+    pub title: String,
+    pub children: Vec<CategoryNode>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, GraphQLObject)]
+pub struct Choice {
+    pub id: Option<i32>,
+    pub title: String,
+    pub correct: bool,
+    /// Why the choice is correct or incorrect, shown to players after they answer.
+    pub explanation: Option<String>,
+    /// Missing from revisions recorded before choices had media.
+    #[serde(default)]
+    pub media: Vec<Attachment>,
+}
+
+/// A choice as players see it, without whether it is correct.
+#[derive(Debug, GraphQLObject)]
+pub struct PublicChoice {
+    pub id: i32,
+    pub title: String,
+    pub media: Vec<Attachment>,
+}
+
+impl PublicChoice {
+    pub fn new(choice: &Choice) -> PublicChoice {
+        PublicChoice {
+            id: choice.id.unwrap_or_default(),
+            title: choice.title.clone(),
+            media: choice.media.clone(),
+        }
+    }
+}
+
+/// The result of checking one choice of a question, revealing only whether that choice is correct.
+#[derive(Debug, GraphQLObject)]
+pub struct ChoiceCheck {
+    pub question_id: i32,
+    pub choice_id: i32,
+    pub correct: bool,
+    /// The question's explanation.
+    pub explanation: Option<String>,
+    /// The explanation of the checked choice.
+    pub choice_explanation: Option<String>,
+    pub sources: Vec<Source>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, GraphQLEnum)]
+pub enum MediaKind {
+    Image,
+    Audio,
+}
+
+impl MediaKind {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            MediaKind::Image => "image",
+            MediaKind::Audio => "audio",
+        }
+    }
+}
+
+/// The sizes images are served in. Resized variants are generated when an image is attached and cached
+/// next to the original.
+#[derive(Debug, Clone, Copy, PartialEq, GraphQLEnum)]
+pub enum MediaVariant {
+    Original,
+    Thumbnail,
+    Medium,
+}
+
+impl Default for MediaVariant {
+    fn default() -> Self {
+        MediaVariant::Original
+    }
+}
+
+pub const RESIZED_VARIANTS: [MediaVariant; 2] = [MediaVariant::Thumbnail, MediaVariant::Medium];
+
+impl MediaVariant {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            MediaVariant::Original => "original",
+            MediaVariant::Thumbnail => "thumbnail",
+            MediaVariant::Medium => "medium",
+        }
+    }
+
+    pub fn from_str(name: &str) -> Option<MediaVariant> {
+        match name {
+            "original" => Some(MediaVariant::Original),
+            "thumbnail" => Some(MediaVariant::Thumbnail),
+            "medium" => Some(MediaVariant::Medium),
+            _ => None,
+        }
+    }
+
+    /// The width images are scaled down to. Narrower images keep their size.
+    pub fn width(&self) -> Option<u32> {
+        match *self {
+            MediaVariant::Original => None,
+            MediaVariant::Thumbnail => Some(160),
+            MediaVariant::Medium => Some(640),
+        }
+    }
+
+    /// The smallest variant that is at least `width` pixels wide.
+    pub fn for_width(width: i32) -> MediaVariant {
+        RESIZED_VARIANTS
+            .iter()
+            .cloned()
+            .find(|variant| variant.width().map(|w| w as i64 >= width as i64) == Some(true))
+            .unwrap_or(MediaVariant::Original)
+    }
+
+    /// Picks the variant from a GraphQL `variant` or `width` argument. The variant wins when both are given.
+    pub fn requested(variant: Option<MediaVariant>, width: Option<i32>) -> Option<MediaVariant> {
+        variant.or_else(|| width.map(MediaVariant::for_width))
+    }
+}
+
+/// A type of media that can be uploaded.
+pub struct MediaType {
+    pub mime_type: &'static str,
+    /// Uploaded media is stored under its id followed by this extension.
+    pub extension: &'static str,
+    pub kind: MediaKind,
+    /// The bytes every file of this type starts with, at the given offset.
+    signature: &'static [(usize, &'static [u8])],
+}
+
+pub const MEDIA_TYPES: [MediaType; 7] = [
+    MediaType {
+        mime_type: "image/png",
+        extension: "png",
+        kind: MediaKind::Image,
+        signature: &[(0, b"\x89PNG\r\n\x1a\n")],
+    },
+    MediaType {
+        mime_type: "image/jpeg",
+        extension: "jpg",
+        kind: MediaKind::Image,
+        signature: &[(0, b"\xff\xd8\xff")],
+    },
+    MediaType {
+        mime_type: "image/gif",
+        extension: "gif",
+        kind: MediaKind::Image,
+        signature: &[(0, b"GIF8")],
+    },
+    MediaType {
+        mime_type: "image/webp",
+        extension: "webp",
+        kind: MediaKind::Image,
+        signature: &[(0, b"RIFF"), (8, b"WEBP")],
+    },
+    MediaType {
+        mime_type: "audio/mpeg",
+        extension: "mp3",
+        kind: MediaKind::Audio,
+        signature: &[(0, b"ID3")],
+    },
+    MediaType {
+        mime_type: "audio/ogg",
+        extension: "ogg",
+        kind: MediaKind::Audio,
+        signature: &[(0, b"OggS")],
+    },
+    MediaType {
+        mime_type: "audio/wav",
+        extension: "wav",
+        kind: MediaKind::Audio,
+        signature: &[(0, b"RIFF"), (8, b"WAVE")],
+    },
+];
+
+impl MediaType {
+    /// Ignores parameters such as `; charset=...`.
+    pub fn from_mime_type(mime_type: &str) -> Option<&'static MediaType> {
+        let essence = mime_type
+            .split(';')
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_lowercase();
+        MEDIA_TYPES
+            .iter()
+            .find(|media_type| media_type.mime_type == essence)
+    }
+
+    pub fn from_media_id(media_id: &str) -> Option<&'static MediaType> {
+        let extension = media_id.rsplit('.').next().unwrap_or("");
+        MEDIA_TYPES
+            .iter()
+            .find(|media_type| media_type.extension == extension)
+    }
+
+    /// Checks that `data` looks like a file of this type, so that the declared MIME type cannot be used
+    /// to serve arbitrary content. MP3 files without an ID3 tag start directly with a frame header.
+    pub fn matches(&self, data: &[u8]) -> bool {
+        let signed = self.signature.iter().all(|&(offset, bytes)| {
+            data.len() >= offset + bytes.len() && &data[offset..offset + bytes.len()] == bytes
+        });
+        let mp3_frame = self.mime_type == "audio/mpeg"
+            && data.len() >= 2
+            && data[0] == 0xff
+            && data[1] & 0xe0 == 0xe0;
+        signed || mp3_frame
+    }
+}
+
+/// Media uploaded through `POST /media` and attached to a question or choice.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Attachment {
+    /// The id returned by the upload, e.g. `0b8c3ad1e2c14c5e9b6f1d1e8f4b7a21.png`.
+    pub media_id: String,
+    /// Describes the media for players who cannot see or hear it. Required for images.
+    pub alt_text: Option<String>,
+    /// The variant `url` points to when none is requested. Chosen per request, never stored.
+    #[serde(skip)]
+    pub variant: MediaVariant,
+}
+
+impl Attachment {
+    pub fn media_type(&self) -> Option<&'static MediaType> {
+        MediaType::from_media_id(&self.media_id)
+    }
+
+    /// The path the media is served from, relative to the API's address.
+    /// Audio has no variants, so it is always served as uploaded.
+    pub fn url(&self, variant: MediaVariant) -> String {
+        let kind = self.media_type().map(|media_type| media_type.kind);
+        match variant {
+            MediaVariant::Original => format!("/media/{}", self.media_id),
+            _ if kind != Some(MediaKind::Image) => format!("/media/{}", self.media_id),
+            _ => format!("/media/{}?variant={}", self.media_id, variant.as_str()),
+        }
+    }
+
+    pub fn with_variant(&self, variant: MediaVariant) -> Attachment {
+        Attachment {
+            variant: variant,
+            ..self.clone()
+        }
+    }
+
+    fn validate(&self) -> Result<(), ValidationError> {
+        let media_type = self.media_type().ok_or_else(|| {
+            ValidationError::new(format!("'{}' is not a media id", self.media_id))
+        })?;
+
+        if media_type.kind == MediaKind::Image && self.alt_text.is_none() {
+            return Err(ValidationError::new(format!(
+                "The image '{}' needs alt text",
+                self.media_id
+            )));
+        }
+        Ok(())
+    }
+}
+
+// The kind, MIME type and url are derived from the media id, so the fields are resolved by hand.
+graphql_object!(Attachment: () |&self| {
+    field media_id() -> &str {
+        &self.media_id
+    }
+
+    field kind() -> Option<MediaKind> {
+        self.media_type().map(|media_type| media_type.kind)
+    }
+
+    field mime_type() -> Option<&str> {
+        self.media_type().map(|media_type| media_type.mime_type)
+    }
+
+    field alt_text() -> &Option<String> {
+        &self.alt_text
+    }
+
+    field url(variant: Option<MediaVariant>, width: Option<i32>) -> String {
+        self.url(MediaVariant::requested(variant, width).unwrap_or(self.variant))
+    }
+});
+
+/// A file sent along with a GraphQL multipart request. The value is a token the server substitutes for the
+/// file's placeholder in the request variables, valid until the request completes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Upload(pub String);
+
+graphql_scalar!(Upload {
+    description: "A file sent with the request, following the GraphQL multipart request spec"
+
+    resolve(&self) -> Value {
+        Value::string(&self.0)
+    }
+
+    from_input_value(v: &InputValue) -> Option<Upload> {
+        v.as_string_value().map(|token| Upload(token.to_owned()))
+    }
+});
+
+/// Attaches either media uploaded through `POST /media`, by its id, or a file uploaded with the request.
+#[derive(GraphQLInputObject)]
+pub struct NewAttachment {
+    pub media_id: Option<String>,
+    pub upload: Option<Upload>,
+    pub alt_text: Option<String>,
+}
+
+impl NewAttachment {
+    pub fn to_attachment(&self) -> Attachment {
+        Attachment {
+            media_id: self
+                .media_id
+                .as_ref()
+                .map(|media_id| media_id.trim().to_string())
+                .unwrap_or_default(),
+            alt_text: self.alt_text.as_ref().and_then(non_empty),
+            variant: MediaVariant::Original,
+        }
+    }
+}
+
+/// A reference backing up a question's answer.
+#[derive(Serialize, Deserialize, Debug, Clone, GraphQLObject)]
+pub struct Source {
+    pub url: String,
+    pub title: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, GraphQLEnum)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl Difficulty {
+    /// The name the difficulty is stored as.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard",
+        }
+    }
+
+    pub fn from_str(name: &str) -> Option<Difficulty> {
+        match name {
+            "easy" => Some(Difficulty::Easy),
+            "medium" => Some(Difficulty::Medium),
+            "hard" => Some(Difficulty::Hard),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Difficulty {
+    fn default() -> Difficulty {
+        Difficulty::Medium
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, GraphQLEnum)]
+pub enum QuestionType {
+    /// Exactly one of the choices is correct.
+    SingleChoice,
+    /// One or more of the choices are correct, and all of them must be selected.
+    MultipleChoice,
+    /// Two choices, one of which is correct.
+    TrueFalse,
+    /// No choices; the player types an answer, which is compared with the accepted answers.
+    FreeText,
+    /// The player puts the items in their correct order.
+    Ordering,
+    /// The player matches every item on the left with its item on the right.
+    Matching,
+    /// The player enters a number, which is correct if it is within the tolerance of the target value.
+    Numeric,
+}
+
+impl QuestionType {
+    /// The name the question type is stored as.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            QuestionType::SingleChoice => "single_choice",
+            QuestionType::MultipleChoice => "multiple_choice",
+            QuestionType::TrueFalse => "true_false",
+            QuestionType::FreeText => "free_text",
+            QuestionType::Ordering => "ordering",
+            QuestionType::Matching => "matching",
+            QuestionType::Numeric => "numeric",
+        }
+    }
+
+    pub fn from_str(name: &str) -> Option<QuestionType> {
+        match name {
+            "single_choice" => Some(QuestionType::SingleChoice),
+            "multiple_choice" => Some(QuestionType::MultipleChoice),
+            "true_false" => Some(QuestionType::TrueFalse),
+            "free_text" => Some(QuestionType::FreeText),
+            "ordering" => Some(QuestionType::Ordering),
+            "matching" => Some(QuestionType::Matching),
+            "numeric" => Some(QuestionType::Numeric),
+            _ => None,
+        }
+    }
+}
+
+impl Default for QuestionType {
+    fn default() -> QuestionType {
+        QuestionType::SingleChoice
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Question {
+    pub id: Option<i32>,
+    pub question: String,
+    pub category: String,
+    pub difficulty: Difficulty,
+    pub question_type: QuestionType,
+    pub choices: Vec<Choice>,
+    /// Shown to players after they answer.
+    pub explanation: Option<String>,
+    pub sources: Vec<Source>,
+    pub media: Vec<Attachment>,
+    /// Normalized by `normalize_tags`.
+    pub tags: Vec<String>,
+    /// The accepted answers of a free-text question.
+    pub free_text: Option<FreeTextAnswer>,
+    /// The items of an ordering question.
+    pub ordering: Option<OrderingAnswer>,
+    /// The pairs of a matching question.
+    pub matching: Option<MatchingAnswer>,
+    /// The target value of a numeric question.
+    pub numeric: Option<NumericAnswer>,
+}
+
+/// An answer accepted by a free-text question.
+#[derive(Serialize, Deserialize, Debug, Clone, GraphQLObject)]
+pub struct AcceptedAnswer {
+    pub answer: String,
+    /// Other ways of writing the answer that are also accepted, e.g. "da Vinci" for "Leonardo da Vinci".
+    pub aliases: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, GraphQLObject)]
+pub struct FreeTextAnswer {
+    pub accepted: Vec<AcceptedAnswer>,
+    /// The number of typos (single character insertions, deletions or substitutions) that are forgiven.
+    /// When `None`, the tolerance grows with the length of the accepted answer.
+    pub tolerance: Option<i32>,
+}
+
+/// The result of checking a player's answer.
+#[derive(Debug, GraphQLObject)]
+pub struct AnswerCheck {
+    pub correct: bool,
+    /// The credit earned, from 0 to 1. Ordering and matching answers that are partially correct earn partial credit.
+    pub score: f64,
+    /// The accepted answer (not the alias) that the player's answer matched.
+    pub matched_answer: Option<String>,
+    /// The number of typos between the player's answer and the matched answer or alias.
+    pub distance: Option<i32>,
+}
+
+impl FreeTextAnswer {
+    /// Compares `answer` with every accepted answer and alias, after normalising both.
+    /// The closest match within the tolerance wins.
+    pub fn check(&self, answer: &str) -> AnswerCheck {
+        let given = normalize_answer(answer);
+
+        let mut best: Option<(&str, usize)> = None;
+        for accepted in self.accepted.iter() {
+            let spellings = Some(&accepted.answer)
+                .into_iter()
+                .chain(accepted.aliases.iter());
+
+            for spelling in spellings {
+                let expected = normalize_answer(spelling);
+                if expected.is_empty() {
+                    continue;
+                }
+
+                let distance = edit_distance(&given, &expected);
+                let tolerance = match self.tolerance {
+                    Some(tolerance) => tolerance.max(0) as usize,
+                    None => default_tolerance(&expected),
+                };
+
+                let closer = best.map_or(true, |(_, best_distance)| distance < best_distance);
+                if distance <= tolerance && closer {
+                    best = Some((accepted.answer.as_str(), distance));
+                }
+            }
+        }
+
+        AnswerCheck {
+            correct: best.is_some(),
+            score: if best.is_some() { 1.0 } else { 0.0 },
+            matched_answer: best.map(|(answer, _)| answer.to_string()),
+            distance: best.map(|(_, distance)| distance as i32),
+        }
+    }
+
+    fn validate(&self) -> Result<(), ValidationError> {
+        if self.accepted.is_empty() {
+            return Err(ValidationError::new(
+                "A free-text question needs at least one accepted answer".to_string(),
+            ));
+        }
+
+        let spellings = self.accepted.iter().flat_map(|accepted| {
+            Some(&accepted.answer)
+                .into_iter()
+                .chain(accepted.aliases.iter())
+        });
+        for spelling in spellings {
+            if normalize_answer(spelling).is_empty() {
+                return Err(ValidationError::new(format!(
+                    "'{}' is not a valid answer: it must contain a letter or digit",
+                    spelling
+                )));
+            }
+        }
+
+        match self.tolerance {
+            Some(tolerance) if tolerance < 0 => Err(ValidationError::new(
+                "The tolerance of a free-text question cannot be negative".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Words that are ignored at the start of an answer.
+const LEADING_ARTICLES: [&str; 3] = ["the", "a", "an"];
+
+/// Lowercases the answer, strips accents, treats punctuation as spaces (except apostrophes, which are dropped)
+/// and removes a leading article, so that "The Beatles" and "beatles" are the same answer.
+fn normalize_answer(answer: &str) -> String {
+    let mut folded = String::with_capacity(answer.len());
+    for c in answer.nfkd().filter(|c| !is_combining_mark(*c)) {
+        match c {
+            '\'' | '\u{2019}' => {}
+            'ß' => folded.push_str("ss"),
+            'æ' | 'Æ' => folded.push_str("ae"),
+            'œ' | 'Œ' => folded.push_str("oe"),
+            'ø' | 'Ø' => folded.push('o'),
+            'ł' | 'Ł' => folded.push('l'),
+            'đ' | 'Đ' => folded.push('d'),
+            c if c.is_alphanumeric() => folded.extend(c.to_lowercase()),
+            _ => folded.push(' '),
+        }
+    }
+
+    let mut words: Vec<&str> = folded.split_whitespace().collect();
+    if words.len() > 1 && LEADING_ARTICLES.contains(&words[0]) {
+        words.remove(0);
+    }
+    words.join(" ")
+}
+
+/// One typo is forgiven for every five characters, up to three.
+fn default_tolerance(expected: &str) -> usize {
+    cmp::min(expected.chars().count() / 5, 3)
+}
+
+/// The Levenshtein distance between `a` and `b`, counted in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == *cb { 0 } else { 1 };
+            current[j + 1] = cmp::min(substitution, cmp::min(previous[j + 1], current[j]) + 1);
+        }
+        ::std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, GraphQLObject)]
+pub struct OrderingAnswer {
+    /// The items in their correct order.
+    pub items: Vec<String>,
+}
+
+/// The items of an ordering question as players see them, shuffled rather than in correct order.
+#[derive(Debug, GraphQLObject)]
+pub struct PublicOrdering {
+    pub items: Vec<String>,
+}
+
+impl OrderingAnswer {
+    /// The items shuffled by `rng`. The shuffle starts from the items in alphabetical order rather than in the correct order,
+    /// so that a player who can repeat it, e.g. from the seed of `randomQuestions`, learns nothing by undoing it.
+    pub fn public(&self, rng: &mut SplitMix64) -> PublicOrdering {
+        let mut items = self.items.clone();
+        items.sort();
+        rng.shuffle(&mut items);
+        PublicOrdering { items: items }
+    }
+
+    /// Scores `order` by the fraction of pairs of items that it puts in the right relative order,
+    /// so moving a single item to the wrong place only costs the pairs it is part of.
+    pub fn check(&self, order: &[String]) -> Result<AnswerCheck, ValidationError> {
+        let mut positions = Vec::with_capacity(self.items.len());
+        for item in self.items.iter() {
+            match order.iter().position(|given| given.trim() == item.trim()) {
+                Some(position) => positions.push(position),
+                None => {
+                    return Err(ValidationError::new(format!(
+                        "The answer must contain the item '{}'",
+                        item
+                    )))
+                }
+            }
+        }
+        if order.len() != self.items.len() {
+            return Err(ValidationError::new(
+                "The answer must contain each item exactly once".to_string(),
+            ));
+        }
+
+        let mut pairs = 0;
+        let mut ordered_pairs = 0;
+        for i in 0..positions.len() {
+            for j in (i + 1)..positions.len() {
+                pairs += 1;
+                if positions[i] < positions[j] {
+                    ordered_pairs += 1;
+                }
+            }
+        }
+
+        Ok(partial_credit(ordered_pairs, pairs))
+    }
+
+    fn validate(&self) -> Result<(), ValidationError> {
+        if self.items.len() < 2 {
+            return Err(ValidationError::new(
+                "An ordering question needs at least two items".to_string(),
+            ));
+        }
+        validate_unique(self.items.iter(), "item")
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, GraphQLObject)]
+pub struct MatchingPair {
+    pub left: String,
+    pub right: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, GraphQLObject)]
+pub struct MatchingAnswer {
+    pub pairs: Vec<MatchingPair>,
+}
+
+/// The items of a matching question as players see them: both sides shuffled separately, so they are not paired up.
+#[derive(Debug, GraphQLObject)]
+pub struct PublicMatching {
+    pub left: Vec<String>,
+    pub right: Vec<String>,
+}
+
+impl MatchingAnswer {
+    /// Both sides shuffled by `rng`, each starting from alphabetical order so that the shuffle reveals no pairs
+    /// (see `OrderingAnswer::public`).
+    pub fn public(&self, rng: &mut SplitMix64) -> PublicMatching {
+        let mut left: Vec<String> = self.pairs.iter().map(|pair| pair.left.clone()).collect();
+        let mut right: Vec<String> = self.pairs.iter().map(|pair| pair.right.clone()).collect();
+        left.sort();
+        right.sort();
+        rng.shuffle(&mut left);
+        rng.shuffle(&mut right);
+        PublicMatching {
+            left: left,
+            right: right,
+        }
+    }
+
+    /// Scores `pairs` by the fraction of left items matched with their right item.
+    /// Left items that are missing from `pairs` count as wrongly matched.
+    pub fn check(&self, pairs: &[MatchingPair]) -> Result<AnswerCheck, ValidationError> {
+        let mut matched = 0;
+        for (index, given) in pairs.iter().enumerate() {
+            let expected = self
+                .pairs
+                .iter()
+                .find(|pair| pair.left.trim() == given.left.trim())
+                .ok_or_else(|| {
+                    ValidationError::new(format!("'{}' is not a left item", given.left))
+                })?;
+            if !self
+                .pairs
+                .iter()
+                .any(|pair| pair.right.trim() == given.right.trim())
+            {
+                return Err(ValidationError::new(format!(
+                    "'{}' is not a right item",
+                    given.right
+                )));
+            }
+            if pairs[..index]
+                .iter()
+                .any(|earlier| earlier.left.trim() == given.left.trim())
+            {
+                return Err(ValidationError::new(format!(
+                    "'{}' is matched more than once",
+                    given.left
+                )));
+            }
+
+            if expected.right.trim() == given.right.trim() {
+                matched += 1;
+            }
+        }
+
+        Ok(partial_credit(matched, self.pairs.len()))
+    }
+
+    fn validate(&self) -> Result<(), ValidationError> {
+        if self.pairs.len() < 2 {
+            return Err(ValidationError::new(
+                "A matching question needs at least two pairs".to_string(),
+            ));
+        }
+        validate_unique(self.pairs.iter().map(|pair| &pair.left), "left item")?;
+        validate_unique(self.pairs.iter().map(|pair| &pair.right), "right item")
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, GraphQLEnum)]
+pub enum ToleranceKind {
+    /// The answer may differ from the target value by at most the tolerance.
+    Absolute,
+    /// The answer may differ from the target value by at most the tolerance, as a percentage of the target value.
+    Percentage,
+}
+
+impl Default for ToleranceKind {
+    fn default() -> ToleranceKind {
+        ToleranceKind::Absolute
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, GraphQLObject)]
+pub struct NumericAnswer {
+    pub value: f64,
+    pub tolerance: f64,
+    pub tolerance_kind: ToleranceKind,
+    /// The unit the answer is given in, e.g. "metres".
+    pub unit: Option<String>,
+}
+
+/// A numeric question as players see it: only the unit the answer is expected in.
+#[derive(Debug, GraphQLObject)]
+pub struct PublicNumeric {
+    pub unit: Option<String>,
+}
+
+impl NumericAnswer {
+    pub fn public(&self) -> PublicNumeric {
+        PublicNumeric {
+            unit: self.unit.clone(),
+        }
+    }
+
+    /// The largest difference from the target value that is still correct.
+    fn max_difference(&self) -> f64 {
+        match self.tolerance_kind {
+            ToleranceKind::Absolute => self.tolerance,
+            ToleranceKind::Percentage => (self.value * self.tolerance / 100.0).abs(),
+        }
+    }
+
+    /// Checks `value` against the target value. The unit is part of the answer: it must be the question's unit,
+    /// ignoring case, or be left out if the question has none. Any other unit, or none, makes the answer wrong.
+    pub fn check(&self, value: f64, unit: Option<&str>) -> Result<AnswerCheck, ValidationError> {
+        if !value.is_finite() {
+            return Err(ValidationError::new(
+                "The answer must be a finite number".to_string(),
+            ));
+        }
+
+        let normalize = |unit: &str| unit.trim().to_lowercase();
+        let same_unit = self.unit.as_ref().map(|expected| normalize(expected))
+            == unit.map(normalize).filter(|unit| !unit.is_empty());
+
+        let correct = same_unit && (value - self.value).abs() <= self.max_difference();
+        Ok(AnswerCheck {
+            correct: correct,
+            score: if correct { 1.0 } else { 0.0 },
+            matched_answer: None,
+            distance: None,
+        })
+    }
+
+    fn validate(&self) -> Result<(), ValidationError> {
+        let error = if !self.value.is_finite() {
+            Some("The value of a numeric question must be a finite number")
+        } else if !self.tolerance.is_finite() || self.tolerance < 0.0 {
+            Some("The tolerance of a numeric question must be a positive number or zero")
+        } else if self.tolerance_kind == ToleranceKind::Percentage && self.value == 0.0 {
+            Some("A percentage tolerance needs a value other than zero, as any percentage of zero is zero")
+        } else if self
+            .unit
+            .as_ref()
+            .map_or(false, |unit| unit.trim().is_empty())
+        {
+            Some("The unit of a numeric question cannot be blank")
+        } else {
+            None
+        };
+
+        match error {
+            Some(message) => Err(ValidationError::new(message.to_string())),
+            None => Ok(()),
+        }
+    }
+}
+
+fn partial_credit(earned: usize, total: usize) -> AnswerCheck {
+    AnswerCheck {
+        correct: earned == total,
+        score: if total == 0 {
+            1.0
+        } else {
+            earned as f64 / total as f64
+        },
+        matched_answer: None,
+        distance: None,
+    }
+}
+
+/// Ensures that the items are not blank and that no two are the same, ignoring surrounding whitespace.
+fn validate_unique<'a, I: Iterator<Item = &'a String>>(
+    items: I,
+    name: &str,
+) -> Result<(), ValidationError> {
+    let mut seen: Vec<&str> = vec![];
+    for item in items {
+        let item = item.trim();
+        if item.is_empty() {
+            return Err(ValidationError::new(format!(
+                "Every {} needs some text",
+                name
+            )));
+        }
+        if seen.contains(&item) {
+            return Err(ValidationError::new(format!(
+                "The {} '{}' appears more than once",
+                name, item
+            )));
+        }
+        seen.push(item);
+    }
+    Ok(())
+}
+
+#[derive(Debug, GraphQLObject)]
+pub struct DeletedCategory {
+    pub title: String,
+    pub deleted_on: DateTime<Utc>,
+}
+
+pub const MAX_TAGS: usize = 20;
+pub const MAX_TAG_LENGTH: usize = 50;
+
+/// Tags are compared in lowercase with runs of whitespace collapsed, e.g. "World  War II" is "world war ii".
+/// Returns the tags sorted, without blanks and duplicates.
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = tags
+        .iter()
+        .map(|tag| {
+            tag.split_whitespace()
+                .collect::<Vec<&str>>()
+                .join(" ")
+                .to_lowercase()
+        })
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    normalized.sort();
+    normalized.dedup();
+    normalized
+}
+
+/// A tag and the number of questions that have it.
+#[derive(Debug, Clone, GraphQLObject)]
+pub struct TagCount {
+    pub name: String,
+    pub count: i32,
+}
+
+pub const MAX_SEARCH_LENGTH: usize = 200;
+pub const MAX_RANDOM_QUESTIONS: i32 = 50;
+
+/// Why a question was found by a search.
+#[derive(Serialize, Deserialize, Debug, Clone, GraphQLObject)]
+pub struct SearchHighlight {
+    pub question_id: i32,
+    /// How well the question matches the search, higher being better. Only comparable within one search.
+    pub rank: f64,
+    /// An excerpt of the question and its choices with the matched words wrapped in `<mark>` and `</mark>`.
+    /// It is safe HTML: the question text is escaped, so `<mark>` and `</mark>` are the only tags it contains.
+    pub snippet: String,
+}
+
+#[derive(Debug)]
+pub struct DeletedQuestion {
+    pub question: Question,
+    pub deleted_on: DateTime<Utc>,
+}
+
+/// Quizzes ask questions that are answered by picking one choice.
+pub const QUIZ_QUESTION_TYPES: [QuestionType; 2] =
+    [QuestionType::SingleChoice, QuestionType::TrueFalse];
+
+/// A quiz being played: the questions it asks, in order, and the answers given so far.
+#[derive(Debug, Clone)]
+pub struct QuizSession {
+    pub id: String,
+    /// The category the questions were picked from, as named when the quiz was started.
+    pub category: String,
+    /// The player who started the quiz, if they sent a token. Only they can answer and finish it.
+    pub player_id: Option<String>,
+    pub question_ids: Vec<i32>,
+    /// In the order they were given.
+    pub answers: Vec<QuizAnswer>,
+    pub started_on: DateTime<Utc>,
+    pub finished_on: Option<DateTime<Utc>>,
+}
+
+/// The choice picked for one question of a quiz.
+#[derive(Debug, Clone, GraphQLObject)]
+pub struct QuizAnswer {
+    pub question_id: i32,
+    pub choice_id: i32,
+    pub correct: bool,
+    pub answered_on: DateTime<Utc>,
+}
+
+/// A question as players see it during a quiz, without its answer, explanation or sources.
+#[derive(Debug, GraphQLObject)]
+pub struct QuizQuestion {
+    pub id: i32,
+    pub question: String,
+    pub category: String,
+    pub difficulty: Difficulty,
+    pub question_type: QuestionType,
+    pub media: Vec<Attachment>,
+    pub tags: Vec<String>,
+    pub choices: Vec<PublicChoice>,
+}
+
+impl QuizQuestion {
+    pub fn new(question: &Question) -> QuizQuestion {
+        QuizQuestion {
+            id: question.id.unwrap_or_default(),
+            question: question.question.clone(),
+            category: question.category.clone(),
+            difficulty: question.difficulty,
+            question_type: question.question_type,
+            media: question.media.clone(),
+            tags: question.tags.clone(),
+            choices: question.choices.iter().map(PublicChoice::new).collect(),
+        }
+    }
+}
+
+#[derive(Debug, GraphQLObject)]
+pub struct StartedQuiz {
+    pub session_id: String,
+    pub questions: Vec<QuizQuestion>,
+}
+
+/// The grade of an answer submitted during a quiz.
+#[derive(Debug, GraphQLObject)]
+pub struct GradedAnswer {
+    pub question_id: i32,
+    pub choice_id: i32,
+    pub correct: bool,
+    pub correct_choice_ids: Vec<i32>,
+    /// The question's explanation.
+    pub explanation: Option<String>,
+    /// The explanation of the picked choice.
+    pub choice_explanation: Option<String>,
+}
+
+/// The score of a finished quiz.
+#[derive(Debug, GraphQLObject)]
+pub struct QuizResult {
+    pub session_id: String,
+    pub category: String,
+    pub question_count: i32,
+    pub answered_count: i32,
+    pub correct_count: i32,
+    /// The fraction of the questions answered correctly, from 0 to 1. Unanswered questions count as wrong.
+    pub score: f64,
+    pub answers: Vec<QuizAnswer>,
+    pub unanswered_question_ids: Vec<i32>,
+    pub started_on: DateTime<Utc>,
+    pub finished_on: DateTime<Utc>,
+}
+
+impl QuizResult {
+    pub fn new(session: QuizSession, finished_on: DateTime<Utc>) -> QuizResult {
+        let answered: Vec<i32> = session.answers.iter().map(|a| a.question_id).collect();
+        let unanswered_question_ids: Vec<i32> = session
+            .question_ids
+            .iter()
+            .filter(|id| !answered.contains(id))
+            .cloned()
+            .collect();
+        let question_count = session.question_ids.len() as i32;
+        let correct_count = session.answers.iter().filter(|a| a.correct).count() as i32;
+
+        QuizResult {
+            session_id: session.id,
+            category: session.category,
+            question_count: question_count,
+            answered_count: session.answers.len() as i32,
+            correct_count: correct_count,
+            score: if question_count == 0 {
+                0.0
+            } else {
+                correct_count as f64 / question_count as f64
+            },
+            answers: session.answers,
+            unanswered_question_ids: unanswered_question_ids,
+            started_on: session.started_on,
+            finished_on: finished_on,
+        }
+    }
+}
+
+pub const MIN_USERNAME_LENGTH: usize = 3;
+pub const MAX_USERNAME_LENGTH: usize = 30;
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+pub const MAX_PASSWORD_LENGTH: usize = 128;
+pub const MIN_DEVICE_ID_LENGTH: usize = 16;
+pub const MAX_DEVICE_ID_LENGTH: usize = 128;
+
+/// A registered player, or an anonymous player known only by the device they play on.
+#[derive(Debug, Clone, GraphQLObject)]
+pub struct Player {
+    pub id: String,
+    /// Lowercase, see `Player::normalize_username`. `None` for anonymous players.
+    pub username: Option<String>,
+    pub created_on: DateTime<Utc>,
+}
+
+impl Player {
+    /// Usernames are compared in lowercase, so "Alice" and "alice" are the same player.
+    pub fn normalize_username(username: &str) -> String {
+        username.trim().to_lowercase()
+    }
+
+    /// Usernames are made of ASCII letters and digits, `_`, `-` and `.`, so they are easy to type
+    /// and cannot imitate another player's with look-alike letters from other scripts, such as a Cyrillic "а".
+    pub fn validate_username(username: &str) -> Result<(), ValidationError> {
+        let length = username.chars().count();
+        if length < MIN_USERNAME_LENGTH || length > MAX_USERNAME_LENGTH {
+            return Err(ValidationError::new(format!(
+                "Usernames must be between {} and {} characters long",
+                MIN_USERNAME_LENGTH, MAX_USERNAME_LENGTH
+            )));
+        }
+        if !username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+        {
+            return Err(ValidationError::new(
+                "Usernames may only contain the letters a to z, digits, '_', '-' and '.'".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Device ids are generated by the client, e.g. a UUID, and must be long enough that they cannot be guessed.
+    pub fn validate_device_id(device_id: &str) -> Result<(), ValidationError> {
+        let length = device_id.chars().count();
+        if length < MIN_DEVICE_ID_LENGTH || length > MAX_DEVICE_ID_LENGTH {
+            return Err(ValidationError::new(format!(
+                "Device ids must be between {} and {} characters long",
+                MIN_DEVICE_ID_LENGTH, MAX_DEVICE_ID_LENGTH
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn validate_password(password: &str) -> Result<(), ValidationError> {
+        let length = password.chars().count();
+        if length < MIN_PASSWORD_LENGTH || length > MAX_PASSWORD_LENGTH {
+            return Err(ValidationError::new(format!(
+                "Passwords must be between {} and {} characters long",
+                MIN_PASSWORD_LENGTH, MAX_PASSWORD_LENGTH
+            )));
+        }
+        Ok(())
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, GraphQLObject)]
-pub struct Choice {
-    pub id: Option<i32>,
-    pub title: String,
-    pub correct: bool,
+/// The tokens returned when a player registers, logs in or refreshes their tokens.
+/// The access token is sent as `Authorization: Bearer <token>`; the refresh token is only sent to `refreshTokens`.
+#[derive(Debug, GraphQLObject)]
+pub struct AuthTokens {
+    pub player: Player,
+    pub access_token: String,
+    pub access_token_expires_on: DateTime<Utc>,
+    pub refresh_token: String,
+    pub refresh_token_expires_on: DateTime<Utc>,
 }
 
-#[derive(Serialize, Deserialize, Debug, GraphQLObject)]
-pub struct Question {
-    pub id: Option<i32>,
+/// A snapshot of a question, recorded every time the question is saved.
+#[derive(Debug, Clone, GraphQLObject)]
+pub struct QuestionRevision {
+    pub revision: i32,
+    pub author: String,
+    pub created_on: DateTime<Utc>,
     pub question: String,
     pub category: String,
+    /// `None` for revisions recorded before questions had a difficulty.
+    pub difficulty: Option<Difficulty>,
+    /// `None` for revisions recorded before questions had a type.
+    pub question_type: Option<QuestionType>,
     pub choices: Vec<Choice>,
+    /// Also `None` for revisions recorded before questions had explanations, which are those without `sources`.
+    pub explanation: Option<String>,
+    /// `None` for revisions recorded before questions had sources.
+    pub sources: Option<Vec<Source>>,
+    /// `None` for revisions recorded before questions had media.
+    pub media: Option<Vec<Attachment>>,
+    /// `None` for revisions recorded before questions had tags.
+    pub tags: Option<Vec<String>>,
+    pub free_text: Option<FreeTextAnswer>,
+    pub ordering: Option<OrderingAnswer>,
+    pub matching: Option<MatchingAnswer>,
+    pub numeric: Option<NumericAnswer>,
 }
 
 #[derive(GraphQLInputObject)]
 pub struct NewQuestion {
     pub question: String,
     pub category: String,
+    /// Defaults to `MEDIUM`.
+    pub difficulty: Option<Difficulty>,
+    /// Defaults to `SINGLE_CHOICE`.
+    pub question_type: Option<QuestionType>,
     pub choices: Vec<NewChoice>,
+    pub explanation: Option<String>,
+    pub sources: Option<Vec<NewSource>>,
+    pub media: Option<Vec<NewAttachment>>,
+    pub tags: Option<Vec<String>>,
+    /// Required by `FREE_TEXT` questions.
+    pub free_text: Option<NewFreeTextAnswer>,
+    /// Required by `ORDERING` questions.
+    pub ordering: Option<NewOrderingAnswer>,
+    /// Required by `MATCHING` questions.
+    pub matching: Option<NewMatchingAnswer>,
+    /// Required by `NUMERIC` questions.
+    pub numeric: Option<NewNumericAnswer>,
+}
+
+impl NewQuestion {
+    /// Every attachment of the question and its choices, so that uploads can be stored before it is saved.
+    pub fn attachments_mut(&mut self) -> Vec<&mut NewAttachment> {
+        let mut attachments: Vec<&mut NewAttachment> = attachments_mut(&mut self.media).collect();
+        for choice in self.choices.iter_mut() {
+            attachments.extend(attachments_mut(&mut choice.media));
+        }
+        attachments
+    }
+
+    /// The question to save. Optional fields that are not provided take their defaults.
+    pub fn to_question(&self) -> Question {
+        Question {
+            id: None,
+            question: self.question.clone(),
+            category: self.category.clone(),
+            difficulty: self.difficulty.unwrap_or_default(),
+            question_type: self.question_type.unwrap_or_default(),
+            choices: self.choices.iter().map(NewChoice::to_choice).collect(),
+            explanation: self.explanation.as_ref().and_then(non_empty),
+            sources: self
+                .sources
+                .iter()
+                .flat_map(|sources| sources.iter())
+                .map(NewSource::to_source)
+                .collect(),
+            media: to_attachments(&self.media),
+            tags: self
+                .tags
+                .as_ref()
+                .map(|tags| normalize_tags(tags))
+                .unwrap_or_default(),
+            free_text: self
+                .free_text
+                .as_ref()
+                .map(NewFreeTextAnswer::to_free_text_answer),
+            ordering: self
+                .ordering
+                .as_ref()
+                .map(NewOrderingAnswer::to_ordering_answer),
+            matching: self
+                .matching
+                .as_ref()
+                .map(NewMatchingAnswer::to_matching_answer),
+            numeric: self
+                .numeric
+                .as_ref()
+                .map(NewNumericAnswer::to_numeric_answer),
+        }
+    }
+}
+
+#[derive(GraphQLInputObject)]
+pub struct NewFreeTextAnswer {
+    pub accepted: Vec<NewAcceptedAnswer>,
+    pub tolerance: Option<i32>,
+}
+
+#[derive(GraphQLInputObject)]
+pub struct NewAcceptedAnswer {
+    pub answer: String,
+    pub aliases: Option<Vec<String>>,
+}
+
+#[derive(GraphQLInputObject)]
+pub struct NewOrderingAnswer {
+    /// The items in their correct order.
+    pub items: Vec<String>,
+}
+
+#[derive(GraphQLInputObject)]
+pub struct NewMatchingAnswer {
+    pub pairs: Vec<NewMatchingPair>,
+}
+
+#[derive(GraphQLInputObject)]
+pub struct NewMatchingPair {
+    pub left: String,
+    pub right: String,
+}
+
+#[derive(GraphQLInputObject)]
+pub struct NewNumericAnswer {
+    pub value: f64,
+    /// Defaults to 0, i.e. only the exact value is correct.
+    pub tolerance: Option<f64>,
+    /// Defaults to `ABSOLUTE`.
+    pub tolerance_kind: Option<ToleranceKind>,
+    pub unit: Option<String>,
+}
+
+impl NewNumericAnswer {
+    pub fn to_numeric_answer(&self) -> NumericAnswer {
+        NumericAnswer {
+            value: self.value,
+            tolerance: self.tolerance.unwrap_or(0.0),
+            tolerance_kind: self.tolerance_kind.unwrap_or_default(),
+            unit: self.unit.clone(),
+        }
+    }
+}
+
+impl NewOrderingAnswer {
+    pub fn to_ordering_answer(&self) -> OrderingAnswer {
+        OrderingAnswer {
+            items: self.items.clone(),
+        }
+    }
+}
+
+impl NewMatchingAnswer {
+    pub fn to_matching_answer(&self) -> MatchingAnswer {
+        MatchingAnswer {
+            pairs: self
+                .pairs
+                .iter()
+                .map(NewMatchingPair::to_matching_pair)
+                .collect(),
+        }
+    }
+}
+
+impl NewMatchingPair {
+    pub fn to_matching_pair(&self) -> MatchingPair {
+        MatchingPair {
+            left: self.left.clone(),
+            right: self.right.clone(),
+        }
+    }
+}
+
+impl NewFreeTextAnswer {
+    pub fn to_free_text_answer(&self) -> FreeTextAnswer {
+        FreeTextAnswer {
+            accepted: self
+                .accepted
+                .iter()
+                .map(|accepted| AcceptedAnswer {
+                    answer: accepted.answer.clone(),
+                    aliases: accepted.aliases.clone().unwrap_or_default(),
+                })
+                .collect(),
+            tolerance: self.tolerance,
+        }
+    }
 }
 
 #[derive(GraphQLInputObject)]
 pub struct NewChoice {
     pub title: String,
     pub correct: bool,
+    pub explanation: Option<String>,
+    pub media: Option<Vec<NewAttachment>>,
+}
+
+impl NewChoice {
+    pub fn to_choice(&self) -> Choice {
+        Choice {
+            id: None,
+            title: self.title.clone(),
+            correct: self.correct,
+            explanation: self.explanation.as_ref().and_then(non_empty),
+            media: to_attachments(&self.media),
+        }
+    }
+}
+
+fn attachments_mut(
+    media: &mut Option<Vec<NewAttachment>>,
+) -> impl Iterator<Item = &mut NewAttachment> {
+    media.iter_mut().flat_map(|media| media.iter_mut())
+}
+
+fn to_attachments(media: &Option<Vec<NewAttachment>>) -> Vec<Attachment> {
+    media
+        .iter()
+        .flat_map(|media| media.iter())
+        .map(NewAttachment::to_attachment)
+        .collect()
+}
+
+#[derive(GraphQLInputObject)]
+pub struct NewSource {
+    pub url: String,
+    pub title: Option<String>,
+}
+
+impl NewSource {
+    pub fn to_source(&self) -> Source {
+        Source {
+            url: self.url.trim().to_string(),
+            title: self.title.as_ref().and_then(non_empty),
+        }
+    }
+}
+
+/// Changes to an existing question. Fields that are not provided are left unchanged.
+#[derive(Default, GraphQLInputObject)]
+pub struct QuestionPatch {
+    pub question: Option<String>,
+    pub category: Option<String>,
+    pub difficulty: Option<Difficulty>,
+    pub question_type: Option<QuestionType>,
+    /// An empty string clears the explanation.
+    pub explanation: Option<String>,
+    /// Replaces the sources.
+    pub sources: Option<Vec<NewSource>>,
+    /// Replaces the media.
+    pub media: Option<Vec<NewAttachment>>,
+    /// Replaces the tags.
+    pub tags: Option<Vec<String>>,
+    pub add_choices: Option<Vec<NewChoice>>,
+    pub update_choices: Option<Vec<ChoicePatch>>,
+    pub remove_choices: Option<Vec<i32>>,
+    /// Replaces the accepted answers of a free-text question.
+    pub free_text: Option<NewFreeTextAnswer>,
+    /// Replaces the items of an ordering question.
+    pub ordering: Option<NewOrderingAnswer>,
+    /// Replaces the pairs of a matching question.
+    pub matching: Option<NewMatchingAnswer>,
+    /// Replaces the target value of a numeric question.
+    pub numeric: Option<NewNumericAnswer>,
+}
+
+#[derive(GraphQLInputObject)]
+pub struct ChoicePatch {
+    pub id: i32,
+    pub title: Option<String>,
+    pub correct: Option<bool>,
+    /// An empty string clears the explanation.
+    pub explanation: Option<String>,
+    /// Replaces the media.
+    pub media: Option<Vec<NewAttachment>>,
+}
+
+impl QuestionPatch {
+    /// Every attachment in the patch, so that uploads can be stored before it is applied.
+    pub fn attachments_mut(&mut self) -> Vec<&mut NewAttachment> {
+        let mut attachments: Vec<&mut NewAttachment> = attachments_mut(&mut self.media).collect();
+        for choice in self
+            .add_choices
+            .iter_mut()
+            .flat_map(|choices| choices.iter_mut())
+        {
+            attachments.extend(attachments_mut(&mut choice.media));
+        }
+        for choice in self
+            .update_choices
+            .iter_mut()
+            .flat_map(|choices| choices.iter_mut())
+        {
+            attachments.extend(attachments_mut(&mut choice.media));
+        }
+        attachments
+    }
+
+    /// Applies the patch to `question`.
+    /// Choices are removed, then updated, then added; added choices have no id until they are saved.
+    pub fn apply(&self, question: &mut Question) -> Result<(), ValidationError> {
+        if let Some(ref text) = self.question {
+            question.question = text.clone();
+        }
+
+        if let Some(ref category) = self.category {
+            question.category = category.clone();
+        }
+
+        if let Some(difficulty) = self.difficulty {
+            question.difficulty = difficulty;
+        }
+
+        if let Some(question_type) = self.question_type {
+            question.question_type = question_type;
+        }
+
+        if let Some(ref explanation) = self.explanation {
+            question.explanation = non_empty(explanation);
+        }
+
+        if let Some(ref sources) = self.sources {
+            question.sources = sources.iter().map(NewSource::to_source).collect();
+        }
+
+        if self.media.is_some() {
+            question.media = to_attachments(&self.media);
+        }
+
+        if let Some(ref tags) = self.tags {
+            question.tags = normalize_tags(tags);
+        }
+
+        for id in self.remove_choices.iter().flat_map(|ids| ids.iter()) {
+            let index = Self::choice_index(question, *id)?;
+            question.choices.remove(index);
+        }
+
+        for choice_patch in self
+            .update_choices
+            .iter()
+            .flat_map(|patches| patches.iter())
+        {
+            let index = Self::choice_index(question, choice_patch.id)?;
+            let choice = &mut question.choices[index];
+
+            if let Some(ref title) = choice_patch.title {
+                choice.title = title.clone();
+            }
+            if let Some(correct) = choice_patch.correct {
+                choice.correct = correct;
+            }
+            if let Some(ref explanation) = choice_patch.explanation {
+                choice.explanation = non_empty(explanation);
+            }
+            if choice_patch.media.is_some() {
+                choice.media = to_attachments(&choice_patch.media);
+            }
+        }
+
+        for new_choice in self.add_choices.iter().flat_map(|choices| choices.iter()) {
+            question.choices.push(new_choice.to_choice());
+        }
+
+        if let Some(ref free_text) = self.free_text {
+            question.free_text = Some(free_text.to_free_text_answer());
+        }
+        if let Some(ref ordering) = self.ordering {
+            question.ordering = Some(ordering.to_ordering_answer());
+        }
+        if let Some(ref matching) = self.matching {
+            question.matching = Some(matching.to_matching_answer());
+        }
+        if let Some(ref numeric) = self.numeric {
+            question.numeric = Some(numeric.to_numeric_answer());
+        }
+
+        // Answers that do not belong to the question's (possibly changed) type are dropped.
+        if question.question_type != QuestionType::FreeText {
+            question.free_text = None;
+        }
+        if question.question_type != QuestionType::Ordering {
+            question.ordering = None;
+        }
+        if question.question_type != QuestionType::Matching {
+            question.matching = None;
+        }
+        if question.question_type != QuestionType::Numeric {
+            question.numeric = None;
+        }
+
+        Ok(())
+    }
+
+    fn choice_index(question: &Question, id: i32) -> Result<usize, ValidationError> {
+        question
+            .choices
+            .iter()
+            .position(|choice| choice.id == Some(id))
+            .ok_or_else(|| ValidationError {
+                message: format!("Choice {} does not belong to this question", id),
+            })
+    }
 }
 
 impl Question {
+    /// Builds the question as it was at `revision`.
+    /// Choices that have since been removed from `current` lose their id, so that they are added again when saved.
+    /// What a revision recorded before it existed, such as the tags of a revision older than tags, is kept from `current`.
+    pub fn from_revision(current: &Question, revision: QuestionRevision) -> Question {
+        // Explanations and sources were added together, so a revision without sources has no explanation either.
+        let explanation = match revision.sources {
+            Some(_) => revision.explanation,
+            None => current.explanation.clone(),
+        };
+        Question {
+            id: current.id,
+            question: revision.question,
+            category: revision.category,
+            difficulty: revision.difficulty.unwrap_or(current.difficulty),
+            question_type: revision.question_type.unwrap_or(current.question_type),
+            choices: revision
+                .choices
+                .into_iter()
+                .map(|choice| Choice {
+                    id: choice
+                        .id
+                        .filter(|id| current.choices.iter().any(|c| c.id == Some(*id))),
+                    ..choice
+                })
+                .collect(),
+            explanation: explanation,
+            sources: revision.sources.unwrap_or_else(|| current.sources.clone()),
+            media: revision.media.unwrap_or_else(|| current.media.clone()),
+            tags: revision.tags.unwrap_or_else(|| current.tags.clone()),
+            free_text: revision.free_text,
+            ordering: revision.ordering,
+            matching: revision.matching,
+            numeric: revision.numeric,
+        }
+    }
+
     pub fn validate(question: &Question) -> Result<(), ValidationError> {
-        if question
+        let choices = question.choices.len();
+        let correct = question
             .choices
             .iter()
             .filter(|choice| choice.correct)
-            .count()
-            > 1
-        {
+            .count();
+
+        let error = match question.question_type {
+            QuestionType::SingleChoice if choices < 2 => {
+                Some("A single choice question needs at least two choices")
+            }
+            QuestionType::SingleChoice if correct != 1 => {
+                Some("A single choice question must have exactly one correct choice")
+            }
+            QuestionType::MultipleChoice if choices < 2 => {
+                Some("A multiple choice question needs at least two choices")
+            }
+            QuestionType::MultipleChoice if correct < 1 => {
+                Some("A multiple choice question needs at least one correct choice")
+            }
+            QuestionType::TrueFalse if choices != 2 => {
+                Some("A true/false question must have exactly two choices")
+            }
+            QuestionType::TrueFalse if correct != 1 => {
+                Some("A true/false question must have exactly one correct choice")
+            }
+            QuestionType::FreeText if choices > 0 => {
+                Some("A free-text question cannot have choices")
+            }
+            QuestionType::FreeText if question.free_text.is_none() => {
+                Some("A free-text question needs accepted answers")
+            }
+            QuestionType::Ordering if choices > 0 => {
+                Some("An ordering question cannot have choices")
+            }
+            QuestionType::Ordering if question.ordering.is_none() => {
+                Some("An ordering question needs items to order")
+            }
+            QuestionType::Matching if choices > 0 => {
+                Some("A matching question cannot have choices")
+            }
+            QuestionType::Matching if question.matching.is_none() => {
+                Some("A matching question needs pairs to match")
+            }
+            QuestionType::Numeric if choices > 0 => Some("A numeric question cannot have choices"),
+            QuestionType::Numeric if question.numeric.is_none() => {
+                Some("A numeric question needs a target value")
+            }
+            _ if question.question_type != QuestionType::FreeText
+                && question.free_text.is_some() =>
+            {
+                Some("Only free-text questions have accepted answers")
+            }
+            _ if question.question_type != QuestionType::Ordering
+                && question.ordering.is_some() =>
+            {
+                Some("Only ordering questions have items to order")
+            }
+            _ if question.question_type != QuestionType::Matching
+                && question.matching.is_some() =>
+            {
+                Some("Only matching questions have pairs to match")
+            }
+            _ if question.question_type != QuestionType::Numeric && question.numeric.is_some() => {
+                Some("Only numeric questions have a target value")
+            }
+            _ => None,
+        };
+
+        if let Some(message) = error {
             return Err(ValidationError {
-                message: "Only one correct choice allowed".to_string(),
+                message: message.to_string(),
             });
         }
-        Ok(())
+
+        let attachments = question
+            .choices
+            .iter()
+            .flat_map(|choice| choice.media.iter())
+            .chain(question.media.iter());
+        for attachment in attachments {
+            attachment.validate()?;
+        }
+
+        if question.tags.len() > MAX_TAGS {
+            return Err(ValidationError::new(format!(
+                "A question can have at most {} tags",
+                MAX_TAGS
+            )));
+        }
+        for tag in question.tags.iter() {
+            if tag.chars().count() > MAX_TAG_LENGTH {
+                return Err(ValidationError::new(format!(
+                    "The tag '{}' is longer than {} characters",
+                    tag, MAX_TAG_LENGTH
+                )));
+            }
+        }
+
+        for source in question.sources.iter() {
+            match Url::parse(&source.url) {
+                Ok(ref url) if url.scheme() == "http" || url.scheme() == "https" => {}
+                _ => {
+                    return Err(ValidationError::new(format!(
+                        "'{}' is not a valid source: it must be an http or https URL",
+                        source.url
+                    )))
+                }
+            }
+        }
+
+        if let Some(ref free_text) = question.free_text {
+            free_text.validate()?;
+        }
+        if let Some(ref ordering) = question.ordering {
+            ordering.validate()?;
+        }
+        if let Some(ref matching) = question.matching {
+            matching.validate()?;
+        }
+        match question.numeric {
+            Some(ref numeric) => numeric.validate(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn free_text(accepted: &[(&str, &[&str])], tolerance: Option<i32>) -> FreeTextAnswer {
+        FreeTextAnswer {
+            accepted: accepted
+                .iter()
+                .map(|&(answer, aliases)| AcceptedAnswer {
+                    answer: answer.to_string(),
+                    aliases: aliases.iter().map(|alias| alias.to_string()).collect(),
+                })
+                .collect(),
+            tolerance: tolerance,
+        }
+    }
+
+    #[test]
+    fn normalize_answer_folds_case_and_accents() {
+        assert_eq!(normalize_answer("PELÉ"), "pele");
+        assert_eq!(normalize_answer("Ærøskøbing"), "aeroskobing");
+        assert_eq!(normalize_answer("Straße"), "strasse");
+        assert_eq!(normalize_answer("Łódź"), "lodz");
+        assert_eq!(normalize_answer("ﬁnale"), "finale");
+    }
+
+    #[test]
+    fn normalize_answer_treats_punctuation_as_spaces() {
+        assert_eq!(normalize_answer("  Hello,   World! "), "hello world");
+        assert_eq!(normalize_answer("rock-n-roll"), "rock n roll");
+        assert_eq!(normalize_answer("O'Neill"), "oneill");
+        assert_eq!(normalize_answer("O\u{2019}Neill"), "oneill");
+        assert_eq!(normalize_answer("?!"), "");
+    }
+
+    #[test]
+    fn normalize_answer_strips_one_leading_article() {
+        assert_eq!(normalize_answer("The Beatles"), "beatles");
+        assert_eq!(normalize_answer("the da vinci"), "da vinci");
+        assert_eq!(normalize_answer("An Apple"), "apple");
+        assert_eq!(normalize_answer("the the"), "the");
+        // An answer that is only an article keeps it.
+        assert_eq!(normalize_answer("The"), "the");
+        assert_eq!(normalize_answer("theatre"), "theatre");
+    }
+
+    #[test]
+    fn edit_distance_counts_characters() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("abc", ""), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("colour", "color"), 1);
+        assert_eq!(edit_distance("flaw", "lawn"), 2);
+        assert_eq!(edit_distance("café", "cafe"), 1);
+    }
+
+    #[test]
+    fn check_accepts_answers_that_differ_in_case_accents_and_punctuation() {
+        let answer = free_text(&[("Pelé", &[])], Some(0));
+        for given in &["pele", "PELE", "  Pelé! ", "the pele"] {
+            let check = answer.check(given);
+            assert!(check.correct, "{} should be accepted", given);
+            assert_eq!(check.score, 1.0);
+            assert_eq!(check.matched_answer, Some("Pelé".to_string()));
+            assert_eq!(check.distance, Some(0));
+        }
+    }
+
+    #[test]
+    fn check_accepts_aliases_but_reports_the_accepted_answer() {
+        let answer = free_text(&[("Leonardo da Vinci", &["da Vinci", "Leonardo"])], None);
+
+        let check = answer.check("the da vinci");
+        assert!(check.correct);
+        assert_eq!(check.matched_answer, Some("Leonardo da Vinci".to_string()));
+        assert_eq!(check.distance, Some(0));
+
+        let check = answer.check("Leonardo");
+        assert_eq!(check.matched_answer, Some("Leonardo da Vinci".to_string()));
+
+        assert!(!answer.check("Michelangelo").correct);
+    }
+
+    #[test]
+    fn check_picks_the_closest_accepted_answer() {
+        let answer = free_text(&[("Austria", &[]), ("Australia", &[])], Some(2));
+        let check = answer.check("Australi");
+        assert_eq!(check.matched_answer, Some("Australia".to_string()));
+        assert_eq!(check.distance, Some(1));
+    }
+
+    #[test]
+    fn check_forgives_typos_up_to_the_tolerance() {
+        let answer = free_text(&[("colour", &[])], Some(1));
+        assert_eq!(answer.check("color").distance, Some(1));
+        assert!(!answer.check("colr").correct);
+
+        let answer = free_text(&[("colour", &[])], Some(0));
+        assert!(answer.check("Colour").correct);
+        assert!(!answer.check("color").correct);
+    }
+
+    #[test]
+    fn check_grows_the_default_tolerance_with_the_answer() {
+        // Four characters forgive no typo, five forgive one.
+        let answer = free_text(&[("Rome", &[])], None);
+        assert!(answer.check("rome").correct);
+        assert!(!answer.check("roma").correct);
+
+        let answer = free_text(&[("Paris", &[])], None);
+        assert_eq!(answer.check("pari").distance, Some(1));
+        assert!(!answer.check("par").correct);
+
+        let answer = free_text(&[("Constantinople", &[])], None);
+        assert_eq!(answer.check("Konstantinopl").distance, Some(2));
+        assert!(!answer.check("Konstantinpl").correct);
+
+        // Capped at three typos, however long the answer.
+        let answer = free_text(&[("Saint Vincent and the Grenadines", &[])], None);
+        assert_eq!(
+            answer.check("Sant Vincnt and the Grenadins").distance,
+            Some(3)
+        );
+        assert!(!answer.check("Sant Vincnt and the Grenadns").correct);
+    }
+
+    #[test]
+    fn check_rejects_empty_answers() {
+        let answer = free_text(&[("Paris", &[])], None);
+        let check = answer.check("!!");
+        assert!(!check.correct);
+        assert_eq!(check.score, 0.0);
+        assert_eq!(check.matched_answer, None);
+        assert_eq!(check.distance, None);
+    }
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|item| item.to_string()).collect()
+    }
+
+    fn ordering() -> OrderingAnswer {
+        OrderingAnswer {
+            items: strings(&["Mercury", "Venus", "Earth", "Mars"]),
+        }
+    }
+
+    #[test]
+    fn ordering_check_gives_full_credit_for_the_correct_order() {
+        let check = ordering()
+            .check(&strings(&[" Mercury", "Venus", "Earth ", "Mars"]))
+            .unwrap();
+        assert!(check.correct);
+        assert_eq!(check.score, 1.0);
+    }
+
+    #[test]
+    fn ordering_check_gives_partial_credit_for_pairs_in_order() {
+        // One adjacent swap puts one of the six pairs out of order.
+        let check = ordering()
+            .check(&strings(&["Mercury", "Earth", "Venus", "Mars"]))
+            .unwrap();
+        assert!(!check.correct);
+        assert!((check.score - 5.0 / 6.0).abs() < 1e-9);
+        assert_eq!(format!("{:.4}", check.score), "0.8333");
+
+        // Moving the first item to the end puts three of the six pairs out of order.
+        let check = ordering()
+            .check(&strings(&["Venus", "Earth", "Mars", "Mercury"]))
+            .unwrap();
+        assert_eq!(check.score, 0.5);
+
+        let check = ordering()
+            .check(&strings(&["Mars", "Earth", "Venus", "Mercury"]))
+            .unwrap();
+        assert!(!check.correct);
+        assert_eq!(check.score, 0.0);
+    }
+
+    #[test]
+    fn ordering_check_rejects_missing_duplicate_and_unknown_items() {
+        let error = ordering()
+            .check(&strings(&["Mercury", "Venus", "Earth"]))
+            .unwrap_err();
+        assert_eq!(error.message, "The answer must contain the item 'Mars'");
+
+        let error = ordering()
+            .check(&strings(&["Mercury", "Venus", "Venus", "Mars"]))
+            .unwrap_err();
+        assert_eq!(error.message, "The answer must contain the item 'Earth'");
+
+        let error = ordering()
+            .check(&strings(&["Mercury", "Venus", "Earth", "Mars", "Mars"]))
+            .unwrap_err();
+        assert_eq!(
+            error.message,
+            "The answer must contain each item exactly once"
+        );
+
+        let error = ordering()
+            .check(&strings(&["Mercury", "Venus", "Earth", "Mars", "Pluto"]))
+            .unwrap_err();
+        assert_eq!(
+            error.message,
+            "The answer must contain each item exactly once"
+        );
+    }
+
+    #[test]
+    fn ordering_public_shuffles_the_items() {
+        let answer = OrderingAnswer {
+            items: strings(&["a", "b", "c", "d", "e", "f", "g", "h"]),
+        };
+        let mut rng = SplitMix64::new(7);
+        let mut orders = vec![];
+        for _ in 0..10 {
+            let mut items = answer.public(&mut rng).items;
+            orders.push(items.clone());
+            items.sort();
+            assert_eq!(items, answer.items);
+        }
+        // The items are not in a fixed order, alphabetical or otherwise.
+        assert!(orders.iter().any(|order| *order != answer.items));
+        assert!(orders.iter().any(|order| *order != orders[0]));
+    }
+
+    #[test]
+    fn ordering_public_does_not_depend_on_the_correct_order() {
+        let answer = OrderingAnswer {
+            items: strings(&["Mercury", "Venus", "Earth", "Mars"]),
+        };
+        let reversed = OrderingAnswer {
+            items: strings(&["Mars", "Earth", "Venus", "Mercury"]),
+        };
+        for seed in 0..10 {
+            assert_eq!(
+                answer.public(&mut SplitMix64::new(seed)).items,
+                reversed.public(&mut SplitMix64::new(seed)).items
+            );
+        }
+    }
+
+    fn pair(left: &str, right: &str) -> MatchingPair {
+        MatchingPair {
+            left: left.to_string(),
+            right: right.to_string(),
+        }
+    }
+
+    fn matching() -> MatchingAnswer {
+        MatchingAnswer {
+            pairs: vec![
+                pair("France", "Paris"),
+                pair("Italy", "Rome"),
+                pair("Spain", "Madrid"),
+                pair("Portugal", "Lisbon"),
+            ],
+        }
+    }
+
+    #[test]
+    fn matching_check_gives_credit_per_matched_left_item() {
+        let check = matching()
+            .check(&[
+                pair("Spain", "Madrid"),
+                pair(" France", "Paris "),
+                pair("Portugal", "Lisbon"),
+                pair("Italy", "Rome"),
+            ])
+            .unwrap();
+        assert!(check.correct);
+        assert_eq!(check.score, 1.0);
+
+        let check = matching()
+            .check(&[
+                pair("France", "Rome"),
+                pair("Italy", "Paris"),
+                pair("Spain", "Madrid"),
+                pair("Portugal", "Lisbon"),
+            ])
+            .unwrap();
+        assert!(!check.correct);
+        assert_eq!(check.score, 0.5);
+
+        // Left items that are not matched count as wrong.
+        let check = matching().check(&[pair("France", "Paris")]).unwrap();
+        assert_eq!(check.score, 0.25);
+        assert_eq!(matching().check(&[]).unwrap().score, 0.0);
+    }
+
+    #[test]
+    fn matching_check_rejects_unknown_and_repeated_items() {
+        let error = matching().check(&[pair("Germany", "Paris")]).unwrap_err();
+        assert_eq!(error.message, "'Germany' is not a left item");
+
+        let error = matching().check(&[pair("France", "Berlin")]).unwrap_err();
+        assert_eq!(error.message, "'Berlin' is not a right item");
+
+        let error = matching()
+            .check(&[pair("France", "Paris"), pair("France", "Rome")])
+            .unwrap_err();
+        assert_eq!(error.message, "'France' is matched more than once");
+    }
+
+    #[test]
+    fn matching_public_keeps_both_sides() {
+        let answer = matching();
+        let public = answer.public(&mut SplitMix64::new(7));
+        let mut left = public.left.clone();
+        let mut right = public.right.clone();
+        left.sort();
+        right.sort();
+        assert_eq!(left, strings(&["France", "Italy", "Portugal", "Spain"]));
+        assert_eq!(right, strings(&["Lisbon", "Madrid", "Paris", "Rome"]));
+    }
+
+    #[test]
+    fn matching_public_does_not_depend_on_the_pairs() {
+        let answer = matching();
+        let mut swapped = matching();
+        let right = swapped.pairs[0].right.clone();
+        swapped.pairs[0].right = swapped.pairs[1].right.clone();
+        swapped.pairs[1].right = right;
+        for seed in 0..10 {
+            let public = answer.public(&mut SplitMix64::new(seed));
+            let swapped_public = swapped.public(&mut SplitMix64::new(seed));
+            assert_eq!(public.left, swapped_public.left);
+            assert_eq!(public.right, swapped_public.right);
+        }
+    }
+
+    fn numeric(
+        value: f64,
+        tolerance: f64,
+        kind: ToleranceKind,
+        unit: Option<&str>,
+    ) -> NumericAnswer {
+        NumericAnswer {
+            value: value,
+            tolerance: tolerance,
+            tolerance_kind: kind,
+            unit: unit.map(|unit| unit.to_string()),
+        }
+    }
+
+    fn numeric_correct(answer: &NumericAnswer, value: f64, unit: Option<&str>) -> bool {
+        answer.check(value, unit).unwrap().correct
+    }
+
+    #[test]
+    fn numeric_check_includes_the_absolute_tolerance() {
+        let answer = numeric(100.0, 0.5, ToleranceKind::Absolute, None);
+        assert!(numeric_correct(&answer, 100.0, None));
+        assert!(numeric_correct(&answer, 100.5, None));
+        assert!(numeric_correct(&answer, 99.5, None));
+        assert!(!numeric_correct(&answer, 100.5001, None));
+        assert!(!numeric_correct(&answer, 99.4999, None));
+
+        let exact = numeric(3.0, 0.0, ToleranceKind::Absolute, None);
+        assert!(numeric_correct(&exact, 3.0, None));
+        assert!(!numeric_correct(&exact, 3.0001, None));
+    }
+
+    #[test]
+    fn numeric_check_includes_the_percentage_tolerance() {
+        let answer = numeric(200.0, 5.0, ToleranceKind::Percentage, None);
+        assert!(numeric_correct(&answer, 210.0, None));
+        assert!(numeric_correct(&answer, 190.0, None));
+        assert!(!numeric_correct(&answer, 210.001, None));
+        assert!(!numeric_correct(&answer, 189.999, None));
+
+        // The tolerance is a percentage of the magnitude, so negative values work the same way.
+        let answer = numeric(-200.0, 5.0, ToleranceKind::Percentage, None);
+        assert!(numeric_correct(&answer, -190.0, None));
+        assert!(numeric_correct(&answer, -210.0, None));
+        assert!(!numeric_correct(&answer, -211.0, None));
+    }
+
+    #[test]
+    fn numeric_check_requires_the_question_unit() {
+        let answer = numeric(8848.0, 10.0, ToleranceKind::Absolute, Some("metres"));
+        assert!(numeric_correct(&answer, 8848.0, Some("metres")));
+        assert!(numeric_correct(&answer, 8850.0, Some(" Metres ")));
+        assert!(!numeric_correct(&answer, 8848.0, Some("feet")));
+        assert!(!numeric_correct(&answer, 8848.0, None));
+        assert!(!numeric_correct(&answer, 8848.0, Some(" ")));
+
+        let unitless = numeric(7.0, 0.0, ToleranceKind::Absolute, None);
+        assert!(numeric_correct(&unitless, 7.0, None));
+        assert!(numeric_correct(&unitless, 7.0, Some("")));
+        assert!(!numeric_correct(&unitless, 7.0, Some("continents")));
+    }
+
+    #[test]
+    fn numeric_check_rejects_non_finite_answers() {
+        let answer = numeric(1.0, 1.0, ToleranceKind::Absolute, None);
+        for value in &[
+            ::std::f64::NAN,
+            ::std::f64::INFINITY,
+            ::std::f64::NEG_INFINITY,
+        ] {
+            let error = answer.check(*value, None).unwrap_err();
+            assert_eq!(error.message, "The answer must be a finite number");
+        }
+    }
+
+    #[test]
+    fn numeric_validate_rejects_unusable_targets() {
+        assert!(numeric(0.0, 1.0, ToleranceKind::Absolute, None)
+            .validate()
+            .is_ok());
+        assert!(numeric(0.0, 5.0, ToleranceKind::Percentage, None)
+            .validate()
+            .is_err());
+        assert!(numeric(::std::f64::NAN, 0.0, ToleranceKind::Absolute, None)
+            .validate()
+            .is_err());
+        assert!(
+            numeric(1.0, ::std::f64::INFINITY, ToleranceKind::Absolute, None)
+                .validate()
+                .is_err()
+        );
+        assert!(numeric(1.0, -1.0, ToleranceKind::Absolute, None)
+            .validate()
+            .is_err());
+        assert!(numeric(1.0, 0.0, ToleranceKind::Absolute, Some(" "))
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn usernames_are_ascii() {
+        for username in &["alice", "Alice_1", "a.b-c"] {
+            assert!(Player::validate_username(username).is_ok(), "{}", username);
+        }
+        // "аlice" starts with a Cyrillic "а", which looks like "a" but would be a different player.
+        for username in &["\u{430}lice", "élodie", "bob smith", "ab"] {
+            assert!(Player::validate_username(username).is_err(), "{}", username);
+        }
+        assert_ne!(
+            Player::normalize_username("\u{430}lice"),
+            Player::normalize_username("alice")
+        );
     }
 }
@@ -0,0 +1,794 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use futures::future;
+use futures::{Future, Stream};
+use futures_cpupool::CpuPool;
+use hyper::header::CONTENT_TYPE;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use juniper::RootNode;
+use serde_derive::Serialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+use controllers::{Context, Mutation, Query};
+use models::Upload;
+
+/// The most files a single request may upload. Each is limited to `MEDIA_MAX_BYTES`.
+const MAX_FILES: usize = 10;
+/// The `operations` and `map` fields are held in memory.
+const MAX_FIELD_BYTES: usize = 1024 * 1024;
+const MAX_HEADER_BYTES: usize = 8 * 1024;
+
+pub enum MultipartError {
+    Invalid(String),
+    TooLarge(String),
+    IOError(String),
+    Hyper(::hyper::Error),
+}
+
+impl MultipartError {
+    fn invalid(message: &str) -> MultipartError {
+        MultipartError::Invalid(message.to_owned())
+    }
+}
+
+pub fn is_multipart(req: &Request<Body>) -> bool {
+    req.headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| {
+            value
+                .trim()
+                .to_lowercase()
+                .starts_with("multipart/form-data")
+        })
+        .unwrap_or(false)
+}
+
+/// Uploads wait here until the request that sent them completes.
+pub fn upload_dir() -> PathBuf {
+    env::temp_dir().join("quizzical-uploads")
+}
+
+/// Removes the files left behind by a server that did not shut down cleanly.
+pub fn clear_upload_dir() {
+    let _ = fs::remove_dir_all(upload_dir());
+}
+
+/// Handles `POST /graphql` requests that follow the
+/// [GraphQL multipart request spec](https://github.com/jaydenseric/graphql-multipart-request-spec).
+/// The `operations` are executed like a JSON request, after each file's placeholder in the variables
+/// has been replaced with an `Upload` token.
+pub fn graphql(
+    cpu_pool: CpuPool,
+    root_node: Arc<RootNode<'static, Query, Mutation>>,
+    ctx: Arc<Context>,
+    req: Request<Body>,
+) -> Box<Future<Item = Response<Body>, Error = ::hyper::Error> + Send> {
+    let content_type = req
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("")
+        .to_owned();
+    let boundary = match header_params(&content_type).remove("boundary") {
+        Some(boundary) => boundary,
+        None => {
+            return Box::new(future::ok(error(&MultipartError::invalid(
+                "The boundary is missing",
+            ))))
+        }
+    };
+
+    let form = match MultipartForm::new(&boundary, ctx.media_service.max_bytes) {
+        Ok(form) => form,
+        Err(e) => return Box::new(future::ok(error(&e))),
+    };
+
+    let parsed = req
+        .into_body()
+        .map_err(MultipartError::Hyper)
+        .fold(form, |mut form, chunk| form.feed(&chunk).map(|_| form))
+        .and_then(|form| form.finish());
+
+    Box::new(parsed.then(move |result| {
+        let (operations, pending) = match result.and_then(|form| form.into_operations(ctx.clone()))
+        {
+            Ok(parsed) => parsed,
+            Err(MultipartError::Hyper(e)) => return future::Either::A(future::err(e)),
+            Err(e) => return future::Either::A(future::ok(error(&e))),
+        };
+
+        let mut request = Request::new(Body::from(operations.to_string()));
+        *request.method_mut() = Method::POST;
+        request
+            .headers_mut()
+            .insert(CONTENT_TYPE, "application/json".parse().unwrap());
+
+        // The pending uploads are dropped, and their files deleted, once the operations have run.
+        future::Either::B(
+            juniper_hyper::graphql(cpu_pool, root_node, ctx, request).then(move |response| {
+                drop(pending);
+                response
+            }),
+        )
+    }))
+}
+
+/// Keeps a file written while the request is read. The file is deleted if the request fails.
+struct TempFile {
+    /// Taken once the file has been handed over.
+    path: Option<PathBuf>,
+    file: Option<fs::File>,
+    size: usize,
+    mime_type: String,
+}
+
+impl TempFile {
+    fn create(mime_type: &str) -> Result<TempFile, MultipartError> {
+        let path = upload_dir().join(format!("{}.upload", Uuid::new_v4().to_simple()));
+        let file =
+            fs::File::create(&path).map_err(|e| MultipartError::IOError(format!("{}", e)))?;
+        Ok(TempFile {
+            path: Some(path),
+            file: Some(file),
+            size: 0,
+            mime_type: mime_type.to_owned(),
+        })
+    }
+
+    /// Hands the file over to the media service, which deletes it when the upload is removed.
+    fn into_pending(mut self, ctx: &Context) -> Upload {
+        self.file = None;
+        let path = self.path.take().unwrap();
+        ctx.media_service.add_pending_upload(path, &self.mime_type)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if let Some(ref path) = self.path {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// The uploads registered for a request, removed from the media service when the request completes.
+struct PendingUploads {
+    ctx: Arc<Context>,
+    uploads: Vec<Upload>,
+}
+
+impl Drop for PendingUploads {
+    fn drop(&mut self) {
+        for upload in self.uploads.iter() {
+            self.ctx.media_service.remove_pending_upload(upload);
+        }
+    }
+}
+
+enum Target {
+    Field(Vec<u8>),
+    File(TempFile),
+}
+
+struct Part {
+    name: String,
+    target: Target,
+}
+
+enum State {
+    Preamble,
+    Delimiter,
+    Headers,
+    Body(Part),
+    Done,
+}
+
+/// Parses a `multipart/form-data` body as it arrives, writing files to disk instead of keeping them in memory.
+struct MultipartForm {
+    /// `\r\n--<boundary>`. The body is read as if it started with `\r\n`, so the first boundary matches too.
+    delimiter: Vec<u8>,
+    buffer: Vec<u8>,
+    state: State,
+    max_file_bytes: usize,
+    fields: HashMap<String, Vec<u8>>,
+    files: HashMap<String, TempFile>,
+}
+
+impl MultipartForm {
+    fn new(boundary: &str, max_file_bytes: usize) -> Result<MultipartForm, MultipartError> {
+        fs::create_dir_all(upload_dir()).map_err(|e| MultipartError::IOError(format!("{}", e)))?;
+        Ok(MultipartForm {
+            delimiter: format!("\r\n--{}", boundary).into_bytes(),
+            buffer: b"\r\n".to_vec(),
+            state: State::Preamble,
+            max_file_bytes: max_file_bytes,
+            fields: HashMap::new(),
+            files: HashMap::new(),
+        })
+    }
+
+    fn feed(&mut self, data: &[u8]) -> Result<(), MultipartError> {
+        self.buffer.extend_from_slice(data);
+
+        loop {
+            match self.state {
+                State::Preamble => match find(&self.buffer, &self.delimiter) {
+                    Some(index) => {
+                        self.buffer.drain(..index + self.delimiter.len());
+                        self.state = State::Delimiter;
+                    }
+                    None => {
+                        let keep = self.delimiter.len() - 1;
+                        let discard = self.buffer.len().saturating_sub(keep);
+                        self.buffer.drain(..discard);
+                        return Ok(());
+                    }
+                },
+                State::Delimiter => {
+                    if self.buffer.len() < 2 {
+                        return Ok(());
+                    }
+                    if self.buffer.starts_with(b"--") {
+                        self.state = State::Done;
+                    } else if self.buffer.starts_with(b"\r\n") {
+                        self.buffer.drain(..2);
+                        self.state = State::Headers;
+                    } else {
+                        return Err(MultipartError::invalid("Malformed multipart boundary"));
+                    }
+                }
+                State::Headers => match find(&self.buffer, b"\r\n\r\n") {
+                    Some(index) => {
+                        let part = self.start_part(&self.buffer[..index].to_vec())?;
+                        self.buffer.drain(..index + 4);
+                        self.state = State::Body(part);
+                    }
+                    None if self.buffer.len() > MAX_HEADER_BYTES => {
+                        return Err(MultipartError::invalid("The part headers are too long"))
+                    }
+                    None => return Ok(()),
+                },
+                State::Body(_) => match find(&self.buffer, &self.delimiter) {
+                    Some(index) => {
+                        let data = self
+                            .buffer
+                            .drain(..index + self.delimiter.len())
+                            .collect::<Vec<u8>>();
+                        self.write(&data[..index])?;
+                        self.end_part()?;
+                        self.state = State::Delimiter;
+                    }
+                    None => {
+                        // The end of the buffer could be the start of a delimiter split across chunks.
+                        let keep = self.delimiter.len() - 1;
+                        let ready = self.buffer.len().saturating_sub(keep);
+                        let data = self.buffer.drain(..ready).collect::<Vec<u8>>();
+                        self.write(&data)?;
+                        return Ok(());
+                    }
+                },
+                State::Done => {
+                    // Anything after the closing boundary is an epilogue and is ignored.
+                    self.buffer.clear();
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    fn finish(self) -> Result<MultipartForm, MultipartError> {
+        match self.state {
+            State::Done => Ok(self),
+            _ => Err(MultipartError::invalid(
+                "The multipart body ended unexpectedly",
+            )),
+        }
+    }
+
+    fn start_part(&self, headers: &[u8]) -> Result<Part, MultipartError> {
+        let headers = String::from_utf8_lossy(headers);
+        let mut disposition = None;
+        let mut content_type = None;
+
+        for line in headers.split("\r\n") {
+            let mut header = line.splitn(2, ':');
+            let name = header.next().unwrap_or("").trim().to_lowercase();
+            let value = header.next().unwrap_or("").trim();
+            match name.as_str() {
+                "content-disposition" => disposition = Some(header_params(value)),
+                "content-type" => content_type = Some(value.to_owned()),
+                _ => {}
+            }
+        }
+
+        let name = disposition
+            .and_then(|mut params| params.remove("name"))
+            .ok_or_else(|| MultipartError::invalid("A part has no name"))?;
+
+        let target = match name.as_str() {
+            "operations" | "map" => Target::Field(vec![]),
+            _ => {
+                if self.files.len() >= MAX_FILES {
+                    return Err(MultipartError::TooLarge(format!(
+                        "No more than {} files can be uploaded at once",
+                        MAX_FILES
+                    )));
+                }
+                let mime_type = content_type.unwrap_or("application/octet-stream".to_owned());
+                Target::File(TempFile::create(&mime_type)?)
+            }
+        };
+        Ok(Part {
+            name: name,
+            target: target,
+        })
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), MultipartError> {
+        let max_file_bytes = self.max_file_bytes;
+        let part = match self.state {
+            State::Body(ref mut part) => part,
+            _ => return Ok(()),
+        };
+
+        match part.target {
+            Target::Field(ref mut value) => {
+                if value.len() + data.len() > MAX_FIELD_BYTES {
+                    return Err(MultipartError::TooLarge(format!(
+                        "The '{}' field may not be larger than {} bytes",
+                        part.name, MAX_FIELD_BYTES
+                    )));
+                }
+                value.extend_from_slice(data);
+            }
+            Target::File(ref mut temp_file) => {
+                if temp_file.size + data.len() > max_file_bytes {
+                    return Err(MultipartError::TooLarge(format!(
+                        "Files may not be larger than {} bytes",
+                        max_file_bytes
+                    )));
+                }
+                temp_file.size += data.len();
+                if let Some(ref mut file) = temp_file.file {
+                    file.write_all(data)
+                        .map_err(|e| MultipartError::IOError(format!("{}", e)))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn end_part(&mut self) -> Result<(), MultipartError> {
+        let part = match ::std::mem::replace(&mut self.state, State::Delimiter) {
+            State::Body(part) => part,
+            _ => return Ok(()),
+        };
+
+        match part.target {
+            Target::Field(value) => {
+                self.fields.insert(part.name, value);
+            }
+            Target::File(mut temp_file) => {
+                if let Some(file) = temp_file.file.take() {
+                    file.sync_all()
+                        .map_err(|e| MultipartError::IOError(format!("{}", e)))?;
+                }
+                self.files.insert(part.name, temp_file);
+            }
+        }
+        Ok(())
+    }
+
+    /// Registers the files with the media service and replaces their placeholders in `operations`.
+    fn into_operations(
+        mut self,
+        ctx: Arc<Context>,
+    ) -> Result<(Value, PendingUploads), MultipartError> {
+        let (mut operations, mapped) = self.mapped_files()?;
+
+        let mut pending = PendingUploads {
+            ctx: ctx.clone(),
+            uploads: vec![],
+        };
+
+        for (temp_file, paths) in mapped {
+            let upload = temp_file.into_pending(&ctx);
+            pending.uploads.push(upload.clone());
+
+            for path in paths {
+                if let Some(placeholder) = placeholder(&mut operations, &path) {
+                    *placeholder = Value::String(upload.0.clone());
+                }
+            }
+        }
+        Ok((operations, pending))
+    }
+
+    /// Parses `operations` and pairs each file in `map` with its paths, after checking that every file was sent
+    /// and every path exists, so that nothing is registered for a request that is rejected.
+    fn mapped_files(&mut self) -> Result<(Value, Vec<(TempFile, Vec<String>)>), MultipartError> {
+        let mut operations: Value = self.json_field("operations")?;
+        let map: HashMap<String, Vec<String>> = self.json_field("map")?;
+
+        let mut mapped = Vec::with_capacity(map.len());
+        for (name, paths) in map {
+            let temp_file = self.files.remove(&name).ok_or_else(|| {
+                MultipartError::Invalid(format!("The file '{}' is missing", name))
+            })?;
+            for path in paths.iter() {
+                if placeholder(&mut operations, path).is_none() {
+                    return Err(MultipartError::Invalid(format!(
+                        "'{}' is not a path in the operations",
+                        path
+                    )));
+                }
+            }
+            mapped.push((temp_file, paths));
+        }
+        Ok((operations, mapped))
+    }
+
+    fn json_field<T: ::serde::de::DeserializeOwned>(
+        &self,
+        name: &str,
+    ) -> Result<T, MultipartError> {
+        let value = self
+            .fields
+            .get(name)
+            .ok_or_else(|| MultipartError::Invalid(format!("The '{}' field is missing", name)))?;
+        serde_json::from_slice(value).map_err(|e| {
+            MultipartError::Invalid(format!("The '{}' field is not valid JSON: {}", name, e))
+        })
+    }
+}
+
+/// Finds the value at a path such as `variables.files.0`, or `0.variables.file` in a batch.
+fn placeholder<'a>(operations: &'a mut Value, path: &str) -> Option<&'a mut Value> {
+    path.split('.').fold(Some(operations), |value, key| {
+        value.and_then(|value| match *value {
+            Value::Object(ref mut object) => object.get_mut(key),
+            Value::Array(ref mut array) => key
+                .parse::<usize>()
+                .ok()
+                .and_then(move |index| array.get_mut(index)),
+            _ => None,
+        })
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Parses the parameters of a header such as `form-data; name="map"` or `multipart/form-data; boundary=x`.
+fn header_params(value: &str) -> HashMap<String, String> {
+    let mut params = HashMap::new();
+    let mut chars = value.chars().peekable();
+
+    // Skips the value before the first parameter.
+    while let Some(c) = chars.next() {
+        if c == ';' {
+            break;
+        }
+    }
+
+    loop {
+        let name: String = chars.by_ref().take_while(|&c| c != '=').collect();
+        let name = name.trim().to_lowercase();
+        if name.is_empty() {
+            break;
+        }
+
+        while chars.peek() == Some(&' ') {
+            chars.next();
+        }
+        let mut param = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => break,
+                    '\\' => param.extend(chars.next()),
+                    _ => param.push(c),
+                }
+            }
+            while let Some(c) = chars.next() {
+                if c == ';' {
+                    break;
+                }
+            }
+        } else {
+            param = chars.by_ref().take_while(|&c| c != ';').collect();
+        }
+        params.insert(name, param.trim().to_owned());
+    }
+    params
+}
+
+#[derive(Serialize)]
+struct GraphQLError {
+    message: String,
+}
+
+/// Errors are reported in the shape of a GraphQL response, as for requests that cannot be executed.
+#[derive(Serialize)]
+struct ErrorResponse {
+    errors: Vec<GraphQLError>,
+}
+
+fn error(e: &MultipartError) -> Response<Body> {
+    let (status, message) = match *e {
+        MultipartError::Invalid(ref message) => (StatusCode::BAD_REQUEST, message.clone()),
+        MultipartError::TooLarge(ref message) => (StatusCode::PAYLOAD_TOO_LARGE, message.clone()),
+        MultipartError::IOError(ref message) => {
+            (StatusCode::INTERNAL_SERVER_ERROR, message.clone())
+        }
+        MultipartError::Hyper(ref e) => (StatusCode::BAD_REQUEST, format!("{}", e)),
+    };
+    let body = ErrorResponse {
+        errors: vec![GraphQLError { message: message }],
+    };
+
+    let mut response = Response::new(Body::from(serde_json::to_string(&body).unwrap()));
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, "application/json".parse().unwrap());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOUNDARY: &str = "----quizzical-boundary";
+
+    /// A body with the parts in order. Parts with a content type are files.
+    fn body(parts: &[(&str, Option<&str>, &[u8])]) -> Vec<u8> {
+        let mut body = b"preamble".to_vec();
+        for &(name, content_type, data) in parts {
+            body.extend_from_slice(format!("\r\n--{}\r\n", BOUNDARY).as_bytes());
+            match content_type {
+                Some(content_type) => body.extend_from_slice(
+                    format!(
+                        "Content-Disposition: form-data; name=\"{}\"; filename=\"{}.png\"\r\nContent-Type: {}\r\n\r\n",
+                        name, name, content_type
+                    )
+                    .as_bytes(),
+                ),
+                None => body.extend_from_slice(
+                    format!("Content-Disposition: form-data; name=\"{}\"\r\n\r\n", name).as_bytes(),
+                ),
+            }
+            body.extend_from_slice(data);
+        }
+        body.extend_from_slice(format!("\r\n--{}--\r\nepilogue", BOUNDARY).as_bytes());
+        body
+    }
+
+    fn upload_body(map: &str, file: &[u8]) -> Vec<u8> {
+        body(&[
+            (
+                "operations",
+                None,
+                br#"{"query":"mutation($file: Upload!) { uploadMedia(file: $file) { mediaId } }","variables":{"file":null}}"#,
+            ),
+            ("map", None, map.as_bytes()),
+            ("0", Some("image/png"), file),
+        ])
+    }
+
+    fn parse(
+        body: &[u8],
+        chunk_size: usize,
+        max_file_bytes: usize,
+    ) -> Result<MultipartForm, MultipartError> {
+        let mut form = MultipartForm::new(BOUNDARY, max_file_bytes)?;
+        for chunk in body.chunks(chunk_size) {
+            form.feed(chunk)?;
+        }
+        form.finish()
+    }
+
+    fn file_contents(form: &MultipartForm, name: &str) -> Vec<u8> {
+        fs::read(form.files[name].path.as_ref().unwrap()).unwrap()
+    }
+
+    fn message(result: Result<MultipartForm, MultipartError>) -> String {
+        match result {
+            Err(MultipartError::Invalid(message)) => format!("invalid: {}", message),
+            Err(MultipartError::TooLarge(message)) => format!("too large: {}", message),
+            Err(MultipartError::IOError(message)) => format!("io: {}", message),
+            Err(MultipartError::Hyper(e)) => format!("hyper: {}", e),
+            Ok(_) => "ok".to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_fields_and_files() {
+        let form = parse(
+            &upload_body(r#"{"0":["variables.file"]}"#, b"\x89PNG data"),
+            4096,
+            1024,
+        )
+        .ok()
+        .unwrap();
+        assert_eq!(form.fields["map"], br#"{"0":["variables.file"]}"#.to_vec());
+        assert!(form.fields.contains_key("operations"));
+        assert_eq!(form.files["0"].mime_type, "image/png");
+        assert_eq!(form.files["0"].size, 9);
+        assert_eq!(file_contents(&form, "0"), b"\x89PNG data".to_vec());
+    }
+
+    #[test]
+    fn parses_boundaries_split_across_chunks() {
+        // The file contains most of a delimiter, which must not be mistaken for the end of the part.
+        let data = format!("line\r\n--{}x\r\n--", &BOUNDARY[..BOUNDARY.len() - 1]);
+        let body = upload_body(r#"{"0":["variables.file"]}"#, data.as_bytes());
+
+        for chunk_size in 1..(BOUNDARY.len() + 8) {
+            let form = parse(&body, chunk_size, 1024).ok().unwrap();
+            assert_eq!(file_contents(&form, "0"), data.as_bytes().to_vec());
+            assert_eq!(form.fields["map"], br#"{"0":["variables.file"]}"#.to_vec());
+        }
+    }
+
+    #[test]
+    fn rejects_bodies_that_end_early() {
+        let body = upload_body("{}", b"data");
+        assert_eq!(
+            message(parse(&body[..body.len() - 20], 4096, 1024)),
+            "invalid: The multipart body ended unexpectedly"
+        );
+    }
+
+    #[test]
+    fn rejects_map_entries_without_a_file() {
+        for map in &[
+            r#"{"1":["variables.file"]}"#,
+            r#"{"0":["variables.file"],"1":["variables.file"]}"#,
+        ] {
+            let mut form = parse(&upload_body(map, b"data"), 4096, 1024).ok().unwrap();
+            match form.mapped_files() {
+                Err(MultipartError::Invalid(ref message)) => {
+                    assert_eq!(message, "The file '1' is missing")
+                }
+                _ => panic!("the map should be rejected"),
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_map_paths_missing_from_the_operations() {
+        let mut form = parse(
+            &upload_body(r#"{"0":["variables.files.0"]}"#, b"data"),
+            4096,
+            1024,
+        )
+        .ok()
+        .unwrap();
+        match form.mapped_files() {
+            Err(MultipartError::Invalid(ref message)) => {
+                assert_eq!(
+                    message,
+                    "'variables.files.0' is not a path in the operations"
+                )
+            }
+            _ => panic!("the map should be rejected"),
+        }
+    }
+
+    #[test]
+    fn pairs_files_with_their_paths() {
+        let mut form = parse(
+            &upload_body(r#"{"0":["variables.file"]}"#, b"data"),
+            4096,
+            1024,
+        )
+        .ok()
+        .unwrap();
+        let (operations, mapped) = form.mapped_files().ok().unwrap();
+        assert_eq!(operations["variables"]["file"], Value::Null);
+        assert_eq!(mapped.len(), 1);
+        assert_eq!(mapped[0].0.size, 4);
+        assert_eq!(mapped[0].1, vec!["variables.file".to_string()]);
+    }
+
+    #[test]
+    fn rejects_oversize_files_and_removes_them() {
+        let body = upload_body(r#"{"0":["variables.file"]}"#, &[7u8; 100]);
+        let mut form = MultipartForm::new(BOUNDARY, 50).ok().unwrap();
+        let mut result = Ok(());
+        for chunk in body.chunks(16) {
+            result = form.feed(chunk);
+            if result.is_err() {
+                break;
+            }
+        }
+        match result {
+            Err(MultipartError::TooLarge(ref message)) => {
+                assert_eq!(message, "Files may not be larger than 50 bytes")
+            }
+            _ => panic!("the file should be rejected"),
+        }
+
+        let path = match form.state {
+            State::Body(Part {
+                target: Target::File(ref temp_file),
+                ..
+            }) => temp_file.path.clone().unwrap(),
+            _ => panic!("the file should still be open"),
+        };
+        assert!(path.exists());
+        drop(form);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn removes_files_of_rejected_requests() {
+        let form = parse(
+            &upload_body(r#"{"1":["variables.file"]}"#, b"data"),
+            4096,
+            1024,
+        )
+        .ok()
+        .unwrap();
+        let path = form.files["0"].path.clone().unwrap();
+        assert!(path.exists());
+
+        let mut form = form;
+        assert!(form.mapped_files().is_err());
+        drop(form);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn rejects_oversize_fields() {
+        let operations = vec![b' '; MAX_FIELD_BYTES + 1];
+        let body = body(&[("operations", None, &operations)]);
+        assert_eq!(
+            message(parse(&body, 64 * 1024, 1024)),
+            format!(
+                "too large: The 'operations' field may not be larger than {} bytes",
+                MAX_FIELD_BYTES
+            )
+        );
+    }
+
+    #[test]
+    fn limits_the_number_of_files() {
+        let names: Vec<String> = (0..MAX_FILES + 1).map(|i| i.to_string()).collect();
+        let parts: Vec<(&str, Option<&str>, &[u8])> = names
+            .iter()
+            .map(|name| (name.as_str(), Some("image/png"), &b"data"[..]))
+            .collect();
+        assert_eq!(
+            message(parse(&body(&parts), 4096, 1024)),
+            format!(
+                "too large: No more than {} files can be uploaded at once",
+                MAX_FILES
+            )
+        );
+    }
+
+    #[test]
+    fn parses_header_params() {
+        let params = header_params(r#"form-data; name="map"; filename="a \"b\".png""#);
+        assert_eq!(params["name"], "map");
+        assert_eq!(params["filename"], r#"a "b".png"#);
+
+        let params = header_params("multipart/form-data; boundary=----x; charset=utf-8");
+        assert_eq!(params["boundary"], "----x");
+        assert_eq!(params["charset"], "utf-8");
+        assert!(header_params("multipart/form-data").is_empty());
+    }
+}
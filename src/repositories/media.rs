@@ -0,0 +1,88 @@
+use super::RepositoryError;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// Stores uploaded media under its media id.
+pub trait MediaStorage: Send + Sync {
+    fn store(&self, media_id: &str, data: &[u8]) -> Result<(), RepositoryError>;
+
+    fn load(&self, media_id: &str) -> Result<Option<Vec<u8>>, RepositoryError>;
+
+    fn exists(&self, media_id: &str) -> Result<bool, RepositoryError>;
+}
+
+/// Keeps every media file in a single directory on the local filesystem.
+pub struct LocalMediaStorage {
+    directory: PathBuf,
+}
+
+impl LocalMediaStorage {
+    /// Creates the directory if it does not exist yet.
+    pub fn new(directory: &str) -> Result<LocalMediaStorage, RepositoryError> {
+        fs::create_dir_all(directory).map_err(|e| io_error(directory, e))?;
+        Ok(LocalMediaStorage {
+            directory: PathBuf::from(directory),
+        })
+    }
+
+    /// Media ids are generated by the server, so anything that could escape the directory is rejected.
+    fn path(&self, media_id: &str) -> Option<PathBuf> {
+        let valid = !media_id.is_empty()
+            && !media_id.starts_with('.')
+            && media_id
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '.' || c == '-');
+
+        if valid {
+            Some(self.directory.join(media_id))
+        } else {
+            None
+        }
+    }
+}
+
+impl MediaStorage for LocalMediaStorage {
+    fn store(&self, media_id: &str, data: &[u8]) -> Result<(), RepositoryError> {
+        let path = self.path(media_id).ok_or_else(|| invalid_id(media_id))?;
+
+        // Written to a temporary file first so a failed upload never leaves a partial file behind.
+        let temporary = self.directory.join(format!(".{}.part", media_id));
+        let result = fs::File::create(&temporary)
+            .and_then(|mut file| file.write_all(data).and_then(|_| file.sync_all()))
+            .and_then(|_| fs::rename(&temporary, &path));
+
+        result.map_err(|e| {
+            let _ = fs::remove_file(&temporary);
+            io_error(media_id, e)
+        })
+    }
+
+    fn load(&self, media_id: &str) -> Result<Option<Vec<u8>>, RepositoryError> {
+        let path = match self.path(media_id) {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+
+        match fs::read(&path) {
+            Ok(data) => Ok(Some(data)),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(io_error(media_id, e)),
+        }
+    }
+
+    fn exists(&self, media_id: &str) -> Result<bool, RepositoryError> {
+        Ok(self
+            .path(media_id)
+            .map(|path| path.is_file())
+            .unwrap_or(false))
+    }
+}
+
+fn invalid_id(media_id: &str) -> RepositoryError {
+    RepositoryError::IOError(format!("'{}' is not a valid media id", media_id))
+}
+
+fn io_error(name: &str, error: io::Error) -> RepositoryError {
+    RepositoryError::IOError(format!("{}: {}", name, error))
+}
@@ -0,0 +1,1418 @@
+use chrono::{DateTime, Utc};
+use log::info;
+use models::{
+    Category, Choice, DeletedCategory, DeletedQuestion, Player, Question, QuestionRevision,
+    QuizAnswer, QuizSession, SearchHighlight, TagCount,
+};
+use sampling::SampleKey;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use super::{
+    snippet_html, CategoriesRepository, PlayersRepository, QuestionFilter, QuestionsRepository,
+    QuizRepository, RepositoryError, SaveCategoryStatus, SearchFilter, HIGHLIGHT_END,
+    HIGHLIGHT_START,
+};
+
+struct CategoryRecord {
+    name: String,
+    parent: Option<String>,
+    description: Option<String>,
+    icon: Option<String>,
+    colour: Option<String>,
+    display_order: i32,
+    featured: bool,
+    active: bool,
+    deleted_on: Option<DateTime<Utc>>,
+}
+
+impl CategoryRecord {
+    fn to_category(&self) -> Category {
+        Category {
+            title: self.name.clone(),
+            parent: self.parent.clone(),
+            description: self.description.clone(),
+            icon: self.icon.clone(),
+            colour: self.colour.clone(),
+            display_order: self.display_order,
+            featured: self.featured,
+        }
+    }
+}
+
+struct QuestionRecord {
+    question: Question,
+    deleted_on: Option<DateTime<Utc>>,
+    revisions: Vec<QuestionRevision>,
+}
+
+impl QuestionRecord {
+    /// Records the current state of the question as its next revision.
+    fn record_revision(&mut self, author: &str) {
+        let revision = QuestionRevision {
+            revision: self.revisions.len() as i32 + 1,
+            author: author.to_string(),
+            created_on: Utc::now(),
+            question: self.question.question.clone(),
+            category: self.question.category.clone(),
+            difficulty: Some(self.question.difficulty),
+            question_type: Some(self.question.question_type),
+            free_text: self.question.free_text.clone(),
+            ordering: self.question.ordering.clone(),
+            matching: self.question.matching.clone(),
+            numeric: self.question.numeric.clone(),
+            choices: self.question.choices.clone(),
+            explanation: self.question.explanation.clone(),
+            sources: Some(self.question.sources.clone()),
+            media: Some(self.question.media.clone()),
+            tags: Some(self.question.tags.clone()),
+        };
+        self.revisions.push(revision);
+    }
+}
+
+struct PlayerRecord {
+    player: Player,
+    /// `None` for anonymous players.
+    password_hash: Option<String>,
+    /// `None` for registered players.
+    device_id: Option<String>,
+}
+
+/// Data shared by the in-memory repositories.
+/// The categories and questions repositories must share a store so that questions are only visible in active categories.
+#[derive(Default)]
+pub struct MemoryStore {
+    categories: Vec<CategoryRecord>,
+    /// Former category names, mapped to the category they now refer to.
+    aliases: HashMap<String, String>,
+    questions: Vec<QuestionRecord>,
+    last_question_id: i32,
+    last_choice_id: i32,
+    quiz_sessions: HashMap<String, QuizSession>,
+    /// Players by id.
+    players: HashMap<String, PlayerRecord>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Arc<RwLock<MemoryStore>> {
+        Arc::new(RwLock::new(MemoryStore::default()))
+    }
+
+    fn category_exists(&self, name: &str) -> bool {
+        self.categories.iter().any(|category| category.name == name)
+    }
+
+    /// Returns the category that `name` is an alias of, or `name` itself if it is not an alias.
+    fn resolve_category<'a>(&'a self, name: &'a str) -> &'a str {
+        self.aliases.get(name).map(|c| c.as_str()).unwrap_or(name)
+    }
+
+    fn is_category_visible(&self, name: &str) -> bool {
+        self.categories.iter().any(|category| {
+            category.name == name && category.active && category.deleted_on.is_none()
+        })
+    }
+
+    /// The visible category selected by the filter and, if requested, its visible descendants.
+    fn selected_categories(&self, filter: &QuestionFilter) -> Vec<String> {
+        let category = self.resolve_category(&filter.category);
+        let mut selected: Vec<String> = vec![];
+        if !self.is_category_visible(category) {
+            return selected;
+        }
+        selected.push(category.to_string());
+
+        // Breadth-first, so that every selected category's children are visited once.
+        let mut next = 0;
+        while filter.include_descendants && next < selected.len() {
+            let parent = selected[next].clone();
+            for child in self.categories.iter().filter(|c| {
+                c.parent.as_ref() == Some(&parent) && c.active && c.deleted_on.is_none()
+            }) {
+                if !selected.contains(&child.name) {
+                    selected.push(child.name.clone());
+                }
+            }
+            next += 1;
+        }
+
+        selected
+    }
+
+    /// Questions selected by the filter that are neither deleted nor in a deleted or inactive category.
+    fn visible_questions<'a>(
+        &'a self,
+        filter: &QuestionFilter,
+    ) -> impl Iterator<Item = &'a Question> {
+        let selected = self.selected_categories(filter);
+        let difficulties = filter.difficulties.clone();
+        let tags = filter.tags.clone();
+        let question_types = filter.question_types.clone();
+
+        self.questions
+            .iter()
+            .filter(move |record| {
+                record.deleted_on.is_none()
+                    && selected.contains(&record.question.category)
+                    && (difficulties.is_empty()
+                        || difficulties.contains(&record.question.difficulty))
+                    && tags.iter().all(|tag| record.question.tags.contains(tag))
+                    && (question_types.is_empty()
+                        || question_types.contains(&record.question.question_type))
+            })
+            .map(|record| &record.question)
+    }
+
+    /// Points the questions, aliases and child categories of category `from` at `to`, like `ON UPDATE CASCADE` does.
+    /// Returns the number of questions moved.
+    fn move_category(&mut self, from: &str, to: &str) -> u64 {
+        let mut moved = 0;
+        for record in self
+            .questions
+            .iter_mut()
+            .filter(|record| record.question.category == from)
+        {
+            record.question.category = to.to_string();
+            moved += 1;
+        }
+
+        for category in self.aliases.values_mut().filter(|c| c.as_str() == from) {
+            *category = to.to_string();
+        }
+
+        for category in self
+            .categories
+            .iter_mut()
+            .filter(|c| c.parent.as_ref().map(|p| p.as_str()) == Some(from))
+        {
+            category.parent = Some(to.to_string());
+        }
+
+        moved
+    }
+
+    fn question_index(&self, id: i32) -> Option<usize> {
+        self.questions
+            .iter()
+            .position(|record| record.question.id == Some(id))
+    }
+
+    /// Every visible question that matches the search, best match first.
+    /// Each search word must match a word of the question text or of a choice title; matches in the text count twice.
+    fn search_results(&self, search: &SearchFilter) -> Vec<(Question, SearchHighlight)> {
+        let search_words: Vec<String> = words(&search.text)
+            .into_iter()
+            .map(|(_, word)| word)
+            .collect();
+        if search_words.is_empty() {
+            return vec![];
+        }
+
+        let selected = search.category.as_ref().map(|category| {
+            self.selected_categories(&QuestionFilter {
+                category: category.clone(),
+                include_descendants: true,
+                difficulties: vec![],
+                tags: vec![],
+                question_types: vec![],
+            })
+        });
+
+        let mut results: Vec<(Question, SearchHighlight)> = vec![];
+        for record in self.questions.iter() {
+            let question = &record.question;
+            let visible = match selected {
+                Some(ref selected) => selected.contains(&question.category),
+                None => self.is_category_visible(&question.category),
+            };
+            if record.deleted_on.is_some() || !visible {
+                continue;
+            }
+
+            let choices = question
+                .choices
+                .iter()
+                .map(|choice| choice.title.as_str())
+                .collect::<Vec<&str>>()
+                .join(" / ");
+            let document: Vec<String> = words(&question.question)
+                .into_iter()
+                .chain(words(&choices))
+                .map(|(_, word)| word)
+                .collect();
+            if !search_words.iter().all(|search_word| {
+                document
+                    .iter()
+                    .any(|word| word_matches(word, std::slice::from_ref(search_word)))
+            }) {
+                continue;
+            }
+
+            let (text, text_matches) = highlight(&question.question, &search_words);
+            let (choices, choice_matches) = highlight(&choices, &search_words);
+            let snippet = if choice_matches == 0 {
+                text
+            } else {
+                format!("{} / {}", text, choices)
+            };
+
+            results.push((
+                question.clone(),
+                SearchHighlight {
+                    question_id: question.id.unwrap_or_default(),
+                    rank: (2 * text_matches + choice_matches) as f64,
+                    snippet: snippet_html(&snippet),
+                },
+            ));
+        }
+
+        results.sort_by(|(a, a_highlight), (b, b_highlight)| {
+            b_highlight
+                .rank
+                .partial_cmp(&a_highlight.rank)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        results
+    }
+}
+
+/// The lowercased words of `text`, each with the byte range it was found at.
+fn words(text: &str) -> Vec<(Range<usize>, String)> {
+    let mut words = vec![];
+    let mut start: Option<usize> = None;
+    for (index, c) in text.char_indices().chain(Some((text.len(), ' '))) {
+        match (start, c.is_alphanumeric()) {
+            (None, true) => start = Some(index),
+            (Some(word_start), false) => {
+                words.push((word_start..index, text[word_start..index].to_lowercase()));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    words
+}
+
+/// Whether the word starts with one of the search words, ignoring a plural "s" on either.
+fn word_matches(word: &str, search_words: &[String]) -> bool {
+    let singular = |word: &str| -> String {
+        if word.len() > 3 && word.ends_with('s') && !word.ends_with("ss") {
+            word[..word.len() - 1].to_string()
+        } else {
+            word.to_string()
+        }
+    };
+
+    let word = singular(word);
+    search_words
+        .iter()
+        .any(|search_word| word.starts_with(&singular(search_word)))
+}
+
+/// Wraps the words of `text` that match one of the search words in the highlight markers.
+/// Returns the highlighted text and the number of words that matched.
+fn highlight(text: &str, search_words: &[String]) -> (String, usize) {
+    let mut highlighted = String::with_capacity(text.len());
+    let mut matches = 0;
+    let mut end = 0;
+    for (range, word) in words(text) {
+        if word_matches(&word, search_words) {
+            highlighted.push_str(&text[end..range.start]);
+            highlighted.push_str(HIGHLIGHT_START);
+            highlighted.push_str(&text[range.clone()]);
+            highlighted.push_str(HIGHLIGHT_END);
+            end = range.end;
+            matches += 1;
+        }
+    }
+    highlighted.push_str(&text[end..]);
+    (highlighted, matches)
+}
+
+fn read(store: &RwLock<MemoryStore>) -> Result<RwLockReadGuard<'_, MemoryStore>, RepositoryError> {
+    store
+        .read()
+        .map_err(|e| RepositoryError::UnknownError(Some(format!("{}", e))))
+}
+
+fn write(
+    store: &RwLock<MemoryStore>,
+) -> Result<RwLockWriteGuard<'_, MemoryStore>, RepositoryError> {
+    store
+        .write()
+        .map_err(|e| RepositoryError::UnknownError(Some(format!("{}", e))))
+}
+
+pub struct InMemoryCategoriesRepository {
+    store: Arc<RwLock<MemoryStore>>,
+}
+
+impl InMemoryCategoriesRepository {
+    pub fn new(store: Arc<RwLock<MemoryStore>>) -> InMemoryCategoriesRepository {
+        InMemoryCategoriesRepository { store: store }
+    }
+}
+
+impl CategoriesRepository for InMemoryCategoriesRepository {
+    fn save_category_and_set_active(
+        &self,
+        category: &str,
+        active: Option<bool>,
+    ) -> Result<SaveCategoryStatus, RepositoryError> {
+        info!("save_category(category: '{:?}').", category);
+
+        let mut store = write(&self.store)?;
+
+        if let Some(existing) = store.categories.iter_mut().find(|c| c.name == category) {
+            // Mirrors `ON CONFLICT DO UPDATE`, which reports the updated row as affected.
+            return Ok(match active {
+                Some(active) => {
+                    existing.active = active;
+                    SaveCategoryStatus::Created
+                }
+                None => SaveCategoryStatus::Exists,
+            });
+        }
+
+        // A new category takes precedence over an alias with the same name.
+        store.aliases.remove(category);
+        store.categories.push(CategoryRecord {
+            name: category.to_string(),
+            parent: None,
+            description: None,
+            icon: None,
+            colour: None,
+            display_order: 0,
+            featured: false,
+            active: active.unwrap_or(true),
+            deleted_on: None,
+        });
+
+        Ok(SaveCategoryStatus::Created)
+    }
+
+    fn list_categories(&self) -> Result<Vec<Category>, RepositoryError> {
+        let store = read(&self.store)?;
+
+        let mut categories: Vec<Category> = store
+            .categories
+            .iter()
+            .filter(|category| category.active && category.deleted_on.is_none())
+            .map(|category| category.to_category())
+            .collect();
+        categories.sort_by(|a, b| {
+            a.display_order
+                .cmp(&b.display_order)
+                .then_with(|| a.title.cmp(&b.title))
+        });
+
+        Ok(categories)
+    }
+
+    fn get_category(&self, name: &str) -> Result<Option<Category>, RepositoryError> {
+        let store = read(&self.store)?;
+
+        Ok(store
+            .categories
+            .iter()
+            .find(|category| category.name == name)
+            .map(|category| category.to_category()))
+    }
+
+    fn update_category(&self, category: &Category) -> Result<bool, RepositoryError> {
+        info!("update_category(category: '{:?}').", category);
+
+        let mut store = write(&self.store)?;
+
+        match store
+            .categories
+            .iter_mut()
+            .find(|c| c.name == category.title)
+        {
+            Some(record) => {
+                record.description = category.description.clone();
+                record.icon = category.icon.clone();
+                record.colour = category.colour.clone();
+                record.display_order = category.display_order;
+                record.featured = category.featured;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn set_category_parent(
+        &self,
+        name: &str,
+        parent: Option<&str>,
+    ) -> Result<bool, RepositoryError> {
+        let mut store = write(&self.store)?;
+
+        if let Some(parent) = parent {
+            if !store.category_exists(parent) {
+                return Err(RepositoryError::DatabaseError(
+                    "23503".into(),
+                    format!("Category '{}' does not exist", parent),
+                ));
+            }
+        }
+
+        match store.categories.iter_mut().find(|c| c.name == name) {
+            Some(category) => {
+                category.parent = parent.map(|p| p.to_string());
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn set_category_active(&self, name: &str, active: bool) -> Result<bool, RepositoryError> {
+        let mut store = write(&self.store)?;
+
+        match store
+            .categories
+            .iter_mut()
+            .find(|category| category.name == name && category.active != active)
+        {
+            Some(category) => {
+                category.active = active;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn delete_category(&self, name: &str) -> Result<bool, RepositoryError> {
+        let mut store = write(&self.store)?;
+
+        match store
+            .categories
+            .iter_mut()
+            .find(|category| category.name == name && category.deleted_on.is_none())
+        {
+            Some(category) => {
+                category.deleted_on = Some(Utc::now());
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn restore_category(&self, name: &str) -> Result<bool, RepositoryError> {
+        let mut store = write(&self.store)?;
+
+        match store
+            .categories
+            .iter_mut()
+            .find(|category| category.name == name && category.deleted_on.is_some())
+        {
+            Some(category) => {
+                category.deleted_on = None;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn list_deleted_categories(&self) -> Result<Vec<DeletedCategory>, RepositoryError> {
+        let store = read(&self.store)?;
+
+        let mut deleted: Vec<DeletedCategory> = store
+            .categories
+            .iter()
+            .filter_map(|category| {
+                category.deleted_on.map(|deleted_on| DeletedCategory {
+                    title: category.name.clone(),
+                    deleted_on: deleted_on,
+                })
+            })
+            .collect();
+        deleted.sort_by(|a, b| b.deleted_on.cmp(&a.deleted_on));
+
+        Ok(deleted)
+    }
+
+    fn category_exists(&self, name: &str) -> Result<bool, RepositoryError> {
+        Ok(read(&self.store)?.category_exists(name))
+    }
+
+    fn rename_category(&self, from: &str, to: &str) -> Result<bool, RepositoryError> {
+        info!("rename_category(from: '{}', to: '{}').", from, to);
+
+        let mut store = write(&self.store)?;
+
+        if store.category_exists(to) {
+            return Err(RepositoryError::DatabaseError(
+                "23505".into(),
+                format!("Category '{}' already exists", to),
+            ));
+        }
+
+        match store.categories.iter_mut().find(|c| c.name == from) {
+            Some(category) => category.name = to.to_string(),
+            None => return Ok(false),
+        }
+
+        store.move_category(from, to);
+        store.aliases.remove(to);
+        store.aliases.insert(from.to_string(), to.to_string());
+
+        Ok(true)
+    }
+
+    fn merge_categories(&self, sources: &[String], target: &str) -> Result<u64, RepositoryError> {
+        info!(
+            "merge_categories(sources: '{:?}', target: '{}').",
+            sources, target
+        );
+
+        let mut store = write(&self.store)?;
+
+        // Everything is checked up front so that a failed merge leaves the store untouched.
+        if !store.category_exists(target) {
+            return Err(RepositoryError::DatabaseError(
+                "23503".into(),
+                format!("Category '{}' does not exist", target),
+            ));
+        }
+        for source in sources {
+            if source == target || !store.category_exists(source) {
+                return Err(RepositoryError::NotFound(format!(
+                    "Category '{}' does not exist",
+                    source
+                )));
+            }
+        }
+
+        let mut moved = 0;
+        for source in sources {
+            moved += store.move_category(source, target);
+            store.categories.retain(|category| &category.name != source);
+            store.aliases.insert(source.clone(), target.to_string());
+        }
+
+        Ok(moved)
+    }
+
+    fn purge_categories(&self, before: DateTime<Utc>) -> Result<u64, RepositoryError> {
+        info!("purge_categories(before: '{}').", before);
+
+        let mut store = write(&self.store)?;
+
+        let purged: Vec<String> = store
+            .categories
+            .iter()
+            .filter(|category| category.deleted_on.map_or(false, |d| d < before))
+            .map(|category| category.name.clone())
+            .collect();
+
+        store
+            .questions
+            .retain(|record| !purged.contains(&record.question.category));
+        store
+            .categories
+            .retain(|category| !purged.contains(&category.name));
+        store
+            .aliases
+            .retain(|_, category| !purged.contains(category));
+        for category in store.categories.iter_mut() {
+            if category
+                .parent
+                .as_ref()
+                .map_or(false, |p| purged.contains(p))
+            {
+                category.parent = None;
+            }
+        }
+
+        Ok(purged.len() as u64)
+    }
+}
+
+pub struct InMemoryQuestionsRepository {
+    store: Arc<RwLock<MemoryStore>>,
+}
+
+impl InMemoryQuestionsRepository {
+    pub fn new(store: Arc<RwLock<MemoryStore>>) -> InMemoryQuestionsRepository {
+        InMemoryQuestionsRepository { store: store }
+    }
+}
+
+impl QuestionsRepository for InMemoryQuestionsRepository {
+    fn save_question(
+        &self,
+        question: &Question,
+        author: &str,
+    ) -> Result<Question, RepositoryError> {
+        info!("save_question(question: '{:?}').", question);
+
+        let mut store = write(&self.store)?;
+
+        let category = store.resolve_category(&question.category).to_string();
+        if !store.category_exists(&category) {
+            return Err(RepositoryError::DatabaseError(
+                "23503".into(),
+                format!("Category '{}' does not exist", question.category),
+            ));
+        }
+
+        store.last_question_id += 1;
+        let question_id = store.last_question_id;
+
+        let mut choices = Vec::with_capacity(question.choices.len());
+        for choice in question.choices.iter() {
+            store.last_choice_id += 1;
+            choices.push(Choice {
+                id: Some(store.last_choice_id),
+                ..choice.clone()
+            });
+        }
+
+        let saved = Question {
+            id: Some(question_id),
+            question: question.question.clone(),
+            category: category,
+            difficulty: question.difficulty,
+            question_type: question.question_type,
+            choices: choices,
+            explanation: question.explanation.clone(),
+            sources: question.sources.clone(),
+            media: question.media.clone(),
+            tags: question.tags.clone(),
+            free_text: question.free_text.clone(),
+            ordering: question.ordering.clone(),
+            matching: question.matching.clone(),
+            numeric: question.numeric.clone(),
+        };
+
+        let mut record = QuestionRecord {
+            question: saved.clone(),
+            deleted_on: None,
+            revisions: vec![],
+        };
+        record.record_revision(author);
+        store.questions.push(record);
+
+        Ok(saved)
+    }
+
+    fn get_question(&self, id: i32) -> Result<Option<Question>, RepositoryError> {
+        let store = read(&self.store)?;
+
+        Ok(store
+            .questions
+            .iter()
+            .find(|record| record.question.id == Some(id) && record.deleted_on.is_none())
+            .map(|record| record.question.clone()))
+    }
+
+    fn get_visible_question(&self, id: i32) -> Result<Option<Question>, RepositoryError> {
+        let store = read(&self.store)?;
+
+        Ok(store
+            .questions
+            .iter()
+            .find(|record| {
+                record.question.id == Some(id)
+                    && record.deleted_on.is_none()
+                    && store.is_category_visible(&record.question.category)
+            })
+            .map(|record| record.question.clone()))
+    }
+
+    fn update_question(
+        &self,
+        question: &Question,
+        author: &str,
+    ) -> Result<Question, RepositoryError> {
+        info!("update_question(question: '{:?}').", question);
+
+        let mut store = write(&self.store)?;
+
+        let category = store.resolve_category(&question.category).to_string();
+        if !store.category_exists(&category) {
+            return Err(RepositoryError::DatabaseError(
+                "23503".into(),
+                format!("Category '{}' does not exist", question.category),
+            ));
+        }
+
+        let index = question
+            .id
+            .and_then(|id| store.question_index(id))
+            .filter(|index| store.questions[*index].deleted_on.is_none())
+            .ok_or(RepositoryError::NotFound(format!(
+                "Question {:?} does not exist",
+                question.id
+            )))?;
+
+        for choice in question.choices.iter() {
+            if let Some(id) = choice.id {
+                if !store.questions[index]
+                    .question
+                    .choices
+                    .iter()
+                    .any(|stored| stored.id == Some(id))
+                {
+                    return Err(RepositoryError::NotFound(format!(
+                        "Choice {} does not belong to question {:?}",
+                        id, question.id
+                    )));
+                }
+            }
+        }
+
+        let mut updated = question.clone();
+        updated.category = category;
+        for choice in updated.choices.iter_mut().filter(|c| c.id.is_none()) {
+            store.last_choice_id += 1;
+            choice.id = Some(store.last_choice_id);
+        }
+
+        store.questions[index].question = updated.clone();
+        store.questions[index].record_revision(author);
+
+        Ok(updated)
+    }
+
+    fn list_revisions(&self, question_id: i32) -> Result<Vec<QuestionRevision>, RepositoryError> {
+        let store = read(&self.store)?;
+
+        Ok(store
+            .question_index(question_id)
+            .map(|index| store.questions[index].revisions.clone())
+            .unwrap_or(vec![]))
+    }
+
+    fn get_revision(
+        &self,
+        question_id: i32,
+        revision: i32,
+    ) -> Result<Option<QuestionRevision>, RepositoryError> {
+        let store = read(&self.store)?;
+
+        Ok(store.question_index(question_id).and_then(|index| {
+            store.questions[index]
+                .revisions
+                .iter()
+                .find(|r| r.revision == revision)
+                .cloned()
+        }))
+    }
+
+    fn count_questions(&self, filter: &QuestionFilter) -> Result<i64, RepositoryError> {
+        let store = read(&self.store)?;
+
+        Ok(store.visible_questions(filter).count() as i64)
+    }
+
+    fn get_questions(
+        &self,
+        filter: &QuestionFilter,
+        page: i64,
+        size: i64,
+    ) -> Result<Vec<Question>, RepositoryError> {
+        let store = read(&self.store)?;
+
+        let offset = match page {
+            0 => 0i64,
+            _ => (page - 1i64) * size,
+        };
+
+        Ok(store
+            .visible_questions(filter)
+            .skip(offset.max(0) as usize)
+            .take(size.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+
+    fn sample_question_ids(
+        &self,
+        filter: &QuestionFilter,
+        key: &SampleKey,
+        exclude_ids: &[i32],
+        count: i64,
+    ) -> Result<Vec<i32>, RepositoryError> {
+        let store = read(&self.store)?;
+
+        let mut keyed: Vec<(i64, i32)> = store
+            .visible_questions(filter)
+            .filter_map(|question| question.id)
+            .filter(|id| !exclude_ids.contains(id))
+            .map(|id| (key.key(i64::from(id)), id))
+            .collect();
+        keyed.sort();
+
+        Ok(keyed
+            .into_iter()
+            .take(count.max(0) as usize)
+            .map(|(_, id)| id)
+            .collect())
+    }
+
+    fn get_questions_by_ids(&self, ids: &[i32]) -> Result<Vec<Question>, RepositoryError> {
+        let store = read(&self.store)?;
+
+        Ok(store
+            .questions
+            .iter()
+            .filter(|record| {
+                record.deleted_on.is_none() && ids.contains(&record.question.id.unwrap_or_default())
+            })
+            .map(|record| record.question.clone())
+            .collect())
+    }
+
+    fn list_tags(&self) -> Result<Vec<TagCount>, RepositoryError> {
+        let store = read(&self.store)?;
+
+        let mut counts: HashMap<String, i32> = HashMap::new();
+        for record in store.questions.iter() {
+            if record.deleted_on.is_some() || !store.is_category_visible(&record.question.category)
+            {
+                continue;
+            }
+            for tag in record.question.tags.iter() {
+                *counts.entry(tag.clone()).or_insert(0) += 1;
+            }
+        }
+
+        let mut tags: Vec<TagCount> = counts
+            .into_iter()
+            .map(|(name, count)| TagCount {
+                name: name,
+                count: count,
+            })
+            .collect();
+        tags.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+        Ok(tags)
+    }
+
+    fn count_search_results(&self, search: &SearchFilter) -> Result<i64, RepositoryError> {
+        let store = read(&self.store)?;
+
+        Ok(store.search_results(search).len() as i64)
+    }
+
+    fn search_questions(
+        &self,
+        search: &SearchFilter,
+        page: i64,
+        size: i64,
+    ) -> Result<Vec<(Question, SearchHighlight)>, RepositoryError> {
+        let store = read(&self.store)?;
+
+        let offset = match page {
+            0 => 0i64,
+            _ => (page - 1i64) * size,
+        };
+
+        Ok(store
+            .search_results(search)
+            .into_iter()
+            .skip(offset.max(0) as usize)
+            .take(size.max(0) as usize)
+            .collect())
+    }
+
+    fn delete_question(&self, id: i32) -> Result<bool, RepositoryError> {
+        let mut store = write(&self.store)?;
+
+        match store
+            .question_index(id)
+            .map(|index| &mut store.questions[index])
+            .filter(|record| record.deleted_on.is_none())
+        {
+            Some(record) => {
+                record.deleted_on = Some(Utc::now());
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn restore_question(&self, id: i32) -> Result<bool, RepositoryError> {
+        let mut store = write(&self.store)?;
+
+        match store
+            .question_index(id)
+            .map(|index| &mut store.questions[index])
+            .filter(|record| record.deleted_on.is_some())
+        {
+            Some(record) => {
+                record.deleted_on = None;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn list_deleted_questions(&self) -> Result<Vec<DeletedQuestion>, RepositoryError> {
+        let store = read(&self.store)?;
+
+        let mut deleted: Vec<DeletedQuestion> = store
+            .questions
+            .iter()
+            .filter_map(|record| {
+                record.deleted_on.map(|deleted_on| DeletedQuestion {
+                    question: record.question.clone(),
+                    deleted_on: deleted_on,
+                })
+            })
+            .collect();
+        deleted.sort_by(|a, b| b.deleted_on.cmp(&a.deleted_on));
+
+        Ok(deleted)
+    }
+
+    fn purge_questions(&self, before: DateTime<Utc>) -> Result<u64, RepositoryError> {
+        info!("purge_questions(before: '{}').", before);
+
+        let mut store = write(&self.store)?;
+
+        let count = store.questions.len();
+        store
+            .questions
+            .retain(|record| record.deleted_on.map_or(true, |d| d >= before));
+
+        Ok((count - store.questions.len()) as u64)
+    }
+}
+
+pub struct InMemoryQuizRepository {
+    store: Arc<RwLock<MemoryStore>>,
+}
+
+impl InMemoryQuizRepository {
+    pub fn new(store: Arc<RwLock<MemoryStore>>) -> InMemoryQuizRepository {
+        InMemoryQuizRepository { store: store }
+    }
+}
+
+impl QuizRepository for InMemoryQuizRepository {
+    fn save_session(&self, session: &QuizSession) -> Result<(), RepositoryError> {
+        info!("save_session(session: '{:?}').", session);
+
+        let mut store = write(&self.store)?;
+
+        if let Some(ref player_id) = session.player_id {
+            if !store.players.contains_key(player_id) {
+                return Err(RepositoryError::DatabaseError(
+                    "23503".into(),
+                    format!("Player '{}' does not exist", player_id),
+                ));
+            }
+        }
+        store
+            .quiz_sessions
+            .insert(session.id.clone(), session.clone());
+
+        Ok(())
+    }
+
+    fn get_session(&self, id: &str) -> Result<Option<QuizSession>, RepositoryError> {
+        Ok(read(&self.store)?.quiz_sessions.get(id).cloned())
+    }
+
+    fn list_sessions(&self, player_id: &str) -> Result<Vec<QuizSession>, RepositoryError> {
+        let mut sessions: Vec<QuizSession> = read(&self.store)?
+            .quiz_sessions
+            .values()
+            .filter(|session| session.player_id.as_ref().map(|id| id.as_str()) == Some(player_id))
+            .cloned()
+            .collect();
+        sessions.sort_by(|a, b| b.started_on.cmp(&a.started_on).then(a.id.cmp(&b.id)));
+        Ok(sessions)
+    }
+
+    fn save_answer(&self, session_id: &str, answer: &QuizAnswer) -> Result<bool, RepositoryError> {
+        let mut store = write(&self.store)?;
+
+        match store.quiz_sessions.get_mut(session_id) {
+            Some(ref mut session)
+                if session.finished_on.is_none()
+                    && !session
+                        .answers
+                        .iter()
+                        .any(|a| a.question_id == answer.question_id) =>
+            {
+                session.answers.push(answer.clone());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn finish_session(
+        &self,
+        id: &str,
+        finished_on: DateTime<Utc>,
+    ) -> Result<bool, RepositoryError> {
+        let mut store = write(&self.store)?;
+
+        match store.quiz_sessions.get_mut(id) {
+            Some(ref mut session) if session.finished_on.is_none() => {
+                session.finished_on = Some(finished_on);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+pub struct InMemoryPlayersRepository {
+    store: Arc<RwLock<MemoryStore>>,
+}
+
+impl InMemoryPlayersRepository {
+    pub fn new(store: Arc<RwLock<MemoryStore>>) -> InMemoryPlayersRepository {
+        InMemoryPlayersRepository { store: store }
+    }
+}
+
+impl PlayersRepository for InMemoryPlayersRepository {
+    fn save_player(&self, player: &Player, password_hash: &str) -> Result<bool, RepositoryError> {
+        info!("save_player(username: '{:?}').", player.username);
+
+        let mut store = write(&self.store)?;
+
+        if store
+            .players
+            .values()
+            .any(|record| record.player.username == player.username)
+        {
+            return Ok(false);
+        }
+        store.players.insert(
+            player.id.clone(),
+            PlayerRecord {
+                player: player.clone(),
+                password_hash: Some(password_hash.to_string()),
+                device_id: None,
+            },
+        );
+        Ok(true)
+    }
+
+    fn get_player(&self, id: &str) -> Result<Option<Player>, RepositoryError> {
+        Ok(read(&self.store)?
+            .players
+            .get(id)
+            .map(|record| record.player.clone()))
+    }
+
+    fn get_player_by_username(
+        &self,
+        username: &str,
+    ) -> Result<Option<(Player, String)>, RepositoryError> {
+        Ok(read(&self.store)?.players.values().find_map(|record| {
+            match (&record.player.username, &record.password_hash) {
+                (&Some(ref name), &Some(ref hash)) if name == username => {
+                    Some((record.player.clone(), hash.clone()))
+                }
+                _ => None,
+            }
+        }))
+    }
+
+    fn get_or_save_device_player(
+        &self,
+        device_id: &str,
+        player: &Player,
+    ) -> Result<Player, RepositoryError> {
+        let mut store = write(&self.store)?;
+
+        if let Some(record) = store
+            .players
+            .values()
+            .find(|record| record.device_id.as_ref().map(|id| id.as_str()) == Some(device_id))
+        {
+            return Ok(record.player.clone());
+        }
+        store.players.insert(
+            player.id.clone(),
+            PlayerRecord {
+                player: player.clone(),
+                password_hash: None,
+                device_id: Some(device_id.to_string()),
+            },
+        );
+        Ok(player.clone())
+    }
+
+    fn merge_player(
+        &self,
+        anonymous_id: &str,
+        into_id: &str,
+    ) -> Result<Option<u64>, RepositoryError> {
+        info!(
+            "merge_player(anonymous_id: '{}', into_id: '{}').",
+            anonymous_id, into_id
+        );
+
+        let mut store = write(&self.store)?;
+
+        match store.players.get(anonymous_id) {
+            Some(record) if record.player.username.is_none() => {}
+            _ => return Ok(None),
+        }
+        store.players.remove(anonymous_id);
+
+        let mut moved = 0;
+        for session in store.quiz_sessions.values_mut() {
+            if session.player_id.as_ref().map(|id| id.as_str()) == Some(anonymous_id) {
+                session.player_id = Some(into_id.to_string());
+                moved += 1;
+            }
+        }
+        Ok(Some(moved))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use models::{Difficulty, QuestionType};
+
+    fn question(category: &str, text: &str, choices: &[(&str, bool)]) -> Question {
+        Question {
+            id: None,
+            question: text.to_string(),
+            category: category.to_string(),
+            difficulty: Difficulty::Medium,
+            question_type: QuestionType::SingleChoice,
+            choices: choices
+                .iter()
+                .map(|&(title, correct)| Choice {
+                    id: None,
+                    title: title.to_string(),
+                    correct: correct,
+                    explanation: None,
+                    media: vec![],
+                })
+                .collect(),
+            explanation: None,
+            sources: vec![],
+            media: vec![],
+            tags: vec![],
+            free_text: None,
+            ordering: None,
+            matching: None,
+            numeric: None,
+        }
+    }
+
+    fn filter(category: &str) -> QuestionFilter {
+        QuestionFilter {
+            category: category.to_string(),
+            include_descendants: false,
+            difficulties: vec![],
+            tags: vec![],
+            question_types: vec![],
+        }
+    }
+
+    fn repositories() -> (InMemoryCategoriesRepository, InMemoryQuestionsRepository) {
+        let store = MemoryStore::new();
+        (
+            InMemoryCategoriesRepository::new(store.clone()),
+            InMemoryQuestionsRepository::new(store),
+        )
+    }
+
+    fn titles(questions: Vec<Question>) -> Vec<String> {
+        questions.into_iter().map(|q| q.question).collect()
+    }
+
+    #[test]
+    fn get_questions_pages_in_insertion_order() {
+        let (categories, questions) = repositories();
+        categories
+            .save_category_and_set_active("science", Some(true))
+            .unwrap();
+        for i in 1..6 {
+            questions
+                .save_question(
+                    &question("science", &format!("q{}", i), &[("a", true), ("b", false)]),
+                    "admin",
+                )
+                .unwrap();
+        }
+
+        let filter = filter("science");
+        assert_eq!(questions.count_questions(&filter).unwrap(), 5);
+        assert_eq!(
+            titles(questions.get_questions(&filter, 1, 2).unwrap()),
+            vec!["q1", "q2"]
+        );
+        assert_eq!(
+            titles(questions.get_questions(&filter, 2, 2).unwrap()),
+            vec!["q3", "q4"]
+        );
+        assert_eq!(
+            titles(questions.get_questions(&filter, 3, 2).unwrap()),
+            vec!["q5"]
+        );
+        assert!(questions.get_questions(&filter, 4, 2).unwrap().is_empty());
+        // Page 0 is treated as the first page.
+        assert_eq!(
+            titles(questions.get_questions(&filter, 0, 2).unwrap()),
+            vec!["q1", "q2"]
+        );
+    }
+
+    #[test]
+    fn inactive_categories_and_their_questions_are_hidden() {
+        let (categories, questions) = repositories();
+        categories
+            .save_category_and_set_active("history", Some(false))
+            .unwrap();
+        questions
+            .save_question(
+                &question("history", "q", &[("a", true), ("b", false)]),
+                "admin",
+            )
+            .unwrap();
+
+        assert!(categories.list_categories().unwrap().is_empty());
+        assert!(categories.get_category("history").unwrap().is_some());
+        assert_eq!(questions.count_questions(&filter("history")).unwrap(), 0);
+
+        match categories
+            .save_category_and_set_active("history", Some(true))
+            .unwrap()
+        {
+            SaveCategoryStatus::Created => {}
+            status => panic!("unexpected status {:?}", status),
+        }
+        let listed: Vec<String> = categories
+            .list_categories()
+            .unwrap()
+            .into_iter()
+            .map(|c| c.title)
+            .collect();
+        assert_eq!(listed, vec!["history"]);
+        assert_eq!(questions.count_questions(&filter("history")).unwrap(), 1);
+
+        // Saving an existing category without an activation leaves it as it is.
+        match categories
+            .save_category_and_set_active("history", None)
+            .unwrap()
+        {
+            SaveCategoryStatus::Exists => {}
+            status => panic!("unexpected status {:?}", status),
+        }
+        assert_eq!(categories.list_categories().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn save_question_requires_an_existing_category() {
+        let (_, questions) = repositories();
+        match questions.save_question(&question("nowhere", "q", &[]), "admin") {
+            Err(RepositoryError::DatabaseError(ref code, _)) => assert_eq!(code, "23503"),
+            result => panic!("unexpected result {:?}", result.map(|q| q.id)),
+        }
+    }
+
+    #[test]
+    fn save_and_update_question_assign_choice_ids() {
+        let (categories, questions) = repositories();
+        categories
+            .save_category_and_set_active("science", Some(true))
+            .unwrap();
+
+        let saved = questions
+            .save_question(
+                &question("science", "q", &[("a", true), ("b", false)]),
+                "admin",
+            )
+            .unwrap();
+        let id = saved.id.unwrap();
+        let choice_ids: Vec<i32> = saved.choices.iter().map(|c| c.id.unwrap()).collect();
+        assert_eq!(choice_ids.len(), 2);
+        assert!(choice_ids[0] != choice_ids[1]);
+
+        let mut edited = saved.clone();
+        edited.choices[0].title = "A".to_string();
+        edited.choices.remove(1);
+        edited
+            .choices
+            .push(question("science", "", &[("c", false)]).choices[0].clone());
+        let updated = questions.update_question(&edited, "admin").unwrap();
+
+        assert_eq!(updated.choices[0].id, Some(choice_ids[0]));
+        assert_eq!(updated.choices[0].title, "A");
+        let added_id = updated.choices[1].id.unwrap();
+        assert!(!choice_ids.contains(&added_id));
+
+        let stored = questions.get_question(id).unwrap().unwrap();
+        let stored_choices: Vec<(Option<i32>, String)> = stored
+            .choices
+            .into_iter()
+            .map(|c| (c.id, c.title))
+            .collect();
+        assert_eq!(
+            stored_choices,
+            vec![
+                (Some(choice_ids[0]), "A".to_string()),
+                (Some(added_id), "c".to_string()),
+            ]
+        );
+        assert_eq!(questions.list_revisions(id).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn update_question_rejects_choices_of_other_questions() {
+        let (categories, questions) = repositories();
+        categories
+            .save_category_and_set_active("science", Some(true))
+            .unwrap();
+        let first = questions
+            .save_question(&question("science", "q1", &[("a", true)]), "admin")
+            .unwrap();
+        let mut second = questions
+            .save_question(&question("science", "q2", &[("b", true)]), "admin")
+            .unwrap();
+
+        second.choices[0].id = first.choices[0].id;
+        match questions.update_question(&second, "admin") {
+            Err(RepositoryError::NotFound(_)) => {}
+            result => panic!("unexpected result {:?}", result.map(|q| q.id)),
+        }
+    }
+
+    #[test]
+    fn search_snippets_are_escaped_html() {
+        let (categories, questions) = repositories();
+        categories
+            .save_category_and_set_active("web", Some(true))
+            .unwrap();
+        questions
+            .save_question(
+                &question(
+                    "web",
+                    "Which <script> tag & attribute load code?",
+                    &[("<script src>", true), ("<img onerror='x'>", false)],
+                ),
+                "admin",
+            )
+            .unwrap();
+
+        let search = SearchFilter {
+            text: "script".to_string(),
+            category: None,
+        };
+        let results = questions.search_questions(&search, 1, 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].1.snippet,
+            "Which &lt;<mark>script</mark>&gt; tag &amp; attribute load code? / &lt;<mark>script</mark> src&gt; / &lt;img onerror=&#39;x&#39;&gt;"
+        );
+    }
+}
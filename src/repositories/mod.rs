@@ -0,0 +1,498 @@
+mod media;
+mod memory;
+mod pg;
+mod sqlite;
+
+use chrono::{DateTime, Utc};
+use models::{
+    Category, Choice, DeletedCategory, DeletedQuestion, Difficulty, Player, Question,
+    QuestionRevision, QuestionType, QuizAnswer, QuizSession, SearchHighlight, TagCount,
+};
+use r2d2::Pool;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use r2d2_postgres::{PostgresConnectionManager, TlsMode};
+use r2d2_sqlite::SqliteConnectionManager;
+use sampling::SampleKey;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use url::Url;
+
+pub use self::media::{LocalMediaStorage, MediaStorage};
+pub use self::memory::{
+    InMemoryCategoriesRepository, InMemoryPlayersRepository, InMemoryQuestionsRepository,
+    InMemoryQuizRepository, MemoryStore,
+};
+pub use self::pg::{
+    PostgresCategoriesRepository, PostgresPlayersRepository, PostgresQuestionsRepository,
+    PostgresQuizRepository,
+};
+pub use self::sqlite::{
+    SqliteCategoriesRepository, SqlitePlayersRepository, SqliteQuestionsRepository,
+    SqliteQuizRepository,
+};
+
+#[derive(Debug, GraphQLEnum)]
+pub enum SaveCategoryStatus {
+    Created,
+    Exists,
+}
+
+/// Selects the questions returned by `count_questions` and `get_questions`.
+#[derive(Debug)]
+pub struct QuestionFilter {
+    /// A category name, or the alias of a renamed or merged category.
+    pub category: String,
+    /// Also selects questions in the visible descendants of the category.
+    pub include_descendants: bool,
+    /// Only selects questions with one of these difficulties. Every difficulty is selected if empty.
+    pub difficulties: Vec<Difficulty>,
+    /// Only selects questions that have all of these tags, as normalized by `normalize_tags`.
+    pub tags: Vec<String>,
+    /// Only selects questions of one of these types. Every type is selected if empty.
+    pub question_types: Vec<QuestionType>,
+}
+
+impl QuestionFilter {
+    fn difficulty_names(&self) -> Vec<String> {
+        self.difficulties
+            .iter()
+            .map(|difficulty| difficulty.as_str().to_string())
+            .collect()
+    }
+
+    fn question_type_names(&self) -> Vec<String> {
+        self.question_types
+            .iter()
+            .map(|question_type| question_type.as_str().to_string())
+            .collect()
+    }
+}
+
+/// Selects the questions returned by `count_search_results` and `search_questions`.
+#[derive(Debug)]
+pub struct SearchFilter {
+    /// The words to look for in the question text and choice titles. Questions must contain all of them.
+    pub text: String,
+    /// Only searches the category (or the category it is an alias of) and its visible descendants.
+    /// Every active category is searched if `None`.
+    pub category: Option<String>,
+}
+
+/// Wrapped around the matched words by the storage, then replaced by `<mark>` and `</mark>` in `snippet_html`.
+/// Private-use characters rather than the tags themselves, so that they cannot be confused with markup in the question text.
+const HIGHLIGHT_START: &str = "\u{E000}";
+const HIGHLIGHT_END: &str = "\u{E001}";
+
+/// Turns a snippet highlighted with `HIGHLIGHT_START` and `HIGHLIGHT_END` into HTML that is safe to insert in a page:
+/// the text is escaped and the markers become `<mark>` and `</mark>`.
+/// Markers that would open a second `<mark>` or close one that is not open (which the question text itself may contain) are dropped.
+fn snippet_html(snippet: &str) -> String {
+    let mut html = String::with_capacity(snippet.len() + 16);
+    let mut marked = false;
+    for c in snippet.chars() {
+        match c {
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            _ if HIGHLIGHT_START.starts_with(c) => {
+                if !marked {
+                    html.push_str("<mark>");
+                    marked = true;
+                }
+            }
+            _ if HIGHLIGHT_END.starts_with(c) => {
+                if marked {
+                    html.push_str("</mark>");
+                    marked = false;
+                }
+            }
+            _ => html.push(c),
+        }
+    }
+    if marked {
+        html.push_str("</mark>");
+    }
+    html
+}
+
+#[derive(Debug)]
+pub enum RepositoryError {
+    ConnectionError(String),
+    DatabaseError(String, String),
+    IOError(String),
+    ConversionError(String),
+    NotFound(String),
+    UnknownError(Option<String>),
+}
+
+impl std::fmt::Display for RepositoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match *self {
+            RepositoryError::ConnectionError(ref message) => message,
+            RepositoryError::DatabaseError(_, ref message) => message,
+            RepositoryError::IOError(ref message) => message,
+            RepositoryError::ConversionError(ref message) => message,
+            RepositoryError::NotFound(ref message) => message,
+            RepositoryError::UnknownError(Some(ref message)) => message,
+            RepositoryError::UnknownError(None) => "Unknown Error",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+impl std::convert::From<r2d2::Error> for RepositoryError {
+    fn from(error: r2d2::Error) -> Self {
+        return RepositoryError::ConnectionError(format!("{}", error));
+    }
+}
+
+/// Revisions store a question's choices as a JSON array.
+fn choices_to_json(choices: &Vec<Choice>) -> Result<String, RepositoryError> {
+    serde_json::to_string(choices).map_err(|e| RepositoryError::ConversionError(format!("{}", e)))
+}
+
+fn choices_from_json(json: &str) -> Result<Vec<Choice>, RepositoryError> {
+    serde_json::from_str(json).map_err(|e| RepositoryError::ConversionError(format!("{}", e)))
+}
+
+/// Questions store their sources and media as JSON arrays, as do choices their media.
+fn list_to_json<T: Serialize>(list: &Vec<T>) -> Result<String, RepositoryError> {
+    serde_json::to_string(list).map_err(|e| RepositoryError::ConversionError(format!("{}", e)))
+}
+
+fn list_from_json<T: DeserializeOwned>(json: &str) -> Result<Vec<T>, RepositoryError> {
+    serde_json::from_str(json).map_err(|e| RepositoryError::ConversionError(format!("{}", e)))
+}
+
+/// `None` for revisions recorded before questions had sources, media or tags.
+fn revision_list_from_json<T: DeserializeOwned>(
+    json: Option<String>,
+) -> Result<Option<Vec<T>>, RepositoryError> {
+    match json {
+        Some(ref json) => list_from_json(json).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn difficulty_from_str(name: &str) -> Result<Difficulty, RepositoryError> {
+    Difficulty::from_str(name)
+        .ok_or_else(|| RepositoryError::ConversionError(format!("Unknown difficulty '{}'", name)))
+}
+
+fn question_type_from_str(name: &str) -> Result<QuestionType, RepositoryError> {
+    QuestionType::from_str(name).ok_or_else(|| {
+        RepositoryError::ConversionError(format!("Unknown question type '{}'", name))
+    })
+}
+
+pub trait CategoriesRepository: Send + Sync {
+    fn save_category(&self, category: &Category) -> Result<SaveCategoryStatus, RepositoryError> {
+        self.save_category_and_set_active(&category.title, None)
+    }
+
+    /// Inserts the category if it does not exist.
+    /// When `active` is provided, the category's active flag is set (or updated) to that value.
+    fn save_category_and_set_active(
+        &self,
+        category: &str,
+        active: Option<bool>,
+    ) -> Result<SaveCategoryStatus, RepositoryError>;
+
+    /// Lists active categories that have not been deleted, ordered by display order and then by title.
+    fn list_categories(&self) -> Result<Vec<Category>, RepositoryError>;
+
+    /// Loads a category, whether or not it is active or deleted.
+    fn get_category(&self, name: &str) -> Result<Option<Category>, RepositoryError>;
+
+    /// Updates the category's description, icon, colour, display order and featured flag.
+    /// Returns false if the category does not exist.
+    fn update_category(&self, category: &Category) -> Result<bool, RepositoryError>;
+
+    /// Nests the category in `parent`, or makes it a top-level category if `parent` is `None`.
+    /// Returns false if the category does not exist.
+    fn set_category_parent(&self, name: &str, parent: Option<&str>) -> Result<bool, RepositoryError>;
+
+    /// Returns true if the active flag of the category was changed.
+    fn set_category_active(&self, name: &str, active: bool) -> Result<bool, RepositoryError>;
+
+    /// Soft-deletes the category, hiding it and its questions until it is restored or purged.
+    /// Returns true if the category was deleted.
+    fn delete_category(&self, name: &str) -> Result<bool, RepositoryError>;
+
+    /// Returns true if the category was in the trash and has been restored.
+    fn restore_category(&self, name: &str) -> Result<bool, RepositoryError>;
+
+    /// Lists soft-deleted categories, most recently deleted first.
+    fn list_deleted_categories(&self) -> Result<Vec<DeletedCategory>, RepositoryError>;
+
+    /// Returns true if the category exists, whether or not it is active or deleted.
+    fn category_exists(&self, name: &str) -> Result<bool, RepositoryError>;
+
+    /// Renames the category, moving its questions and aliases, and records `from` as an alias of `to`.
+    /// Returns false if `from` does not exist.
+    fn rename_category(&self, from: &str, to: &str) -> Result<bool, RepositoryError>;
+
+    /// Moves every question, alias and child category of the `sources` categories into `target`, removes the sources and records their names as aliases of `target`.
+    /// Either all sources are merged or none are. Returns the number of questions moved.
+    fn merge_categories(&self, sources: &[String], target: &str) -> Result<u64, RepositoryError>;
+
+    /// Permanently removes categories deleted before `before`, along with all of their questions.
+    /// Returns the number of categories removed.
+    fn purge_categories(&self, before: DateTime<Utc>) -> Result<u64, RepositoryError>;
+}
+
+pub trait QuestionsRepository: Send + Sync {
+    /// Saves the question and its choices, returning the question with the generated ids set.
+    /// The saved question is recorded as its first revision, attributed to `author`.
+    fn save_question(&self, question: &Question, author: &str) -> Result<Question, RepositoryError>;
+
+    /// Loads a question and its choices, regardless of whether its category is active.
+    /// Deleted questions are not returned.
+    fn get_question(&self, id: i32) -> Result<Option<Question>, RepositoryError>;
+
+    /// Loads a question and its choices only if players can see it: it is not deleted and its category is active and not deleted.
+    fn get_visible_question(&self, id: i32) -> Result<Option<Question>, RepositoryError>;
+
+    /// Updates the question's text and category, and makes its stored choices match `question.choices`:
+    /// choices with an id are updated, choices without an id are inserted and stored choices that are no longer present are deleted.
+    /// The updated question is recorded as a new revision, attributed to `author`.
+    /// Returns the question with the ids of inserted choices set.
+    fn update_question(&self, question: &Question, author: &str) -> Result<Question, RepositoryError>;
+
+    /// Lists the revisions of the question, oldest first.
+    fn list_revisions(&self, question_id: i32) -> Result<Vec<QuestionRevision>, RepositoryError>;
+
+    fn get_revision(
+        &self,
+        question_id: i32,
+        revision: i32,
+    ) -> Result<Option<QuestionRevision>, RepositoryError>;
+
+    /// Counts the questions selected by the filter, or 0 if the category is not active.
+    /// The category of a saved or updated question may also be an alias.
+    /// Deleted questions and questions in deleted categories are never counted or returned.
+    /// Descendants of inactive or deleted categories are not included.
+    fn count_questions(&self, filter: &QuestionFilter) -> Result<i64, RepositoryError>;
+
+    /// Returns a page of the questions selected by the filter, ordered by id. Pages start at 1.
+    fn get_questions(
+        &self,
+        filter: &QuestionFilter,
+        page: i64,
+        size: i64,
+    ) -> Result<Vec<Question>, RepositoryError>;
+
+    /// Picks up to `count` ids of the questions selected by the filter, leaving out `exclude_ids`, in the order of
+    /// their sample key and then of their id. The other ids are never loaded.
+    fn sample_question_ids(
+        &self,
+        filter: &QuestionFilter,
+        key: &SampleKey,
+        exclude_ids: &[i32],
+        count: i64,
+    ) -> Result<Vec<i32>, RepositoryError>;
+
+    /// Loads the questions with the given ids that are not deleted, ordered by id.
+    fn get_questions_by_ids(&self, ids: &[i32]) -> Result<Vec<Question>, RepositoryError>;
+
+    /// Lists the tags of the questions that `count_questions` could select, with the number of questions
+    /// that have each, most used first. Tags that no such question has are left out.
+    fn list_tags(&self) -> Result<Vec<TagCount>, RepositoryError>;
+
+    /// Counts the questions that match the search. Deleted questions and questions in inactive or deleted categories are never counted or returned.
+    fn count_search_results(&self, search: &SearchFilter) -> Result<i64, RepositoryError>;
+
+    /// Returns a page of the questions that match the search, best match first, each with its highlight. Pages start at 1.
+    /// How words are matched and ranked depends on the storage: Postgres and SQLite stem English words, the in-memory storage matches word prefixes.
+    fn search_questions(
+        &self,
+        search: &SearchFilter,
+        page: i64,
+        size: i64,
+    ) -> Result<Vec<(Question, SearchHighlight)>, RepositoryError>;
+
+    /// Soft-deletes the question. Returns true if the question was deleted.
+    fn delete_question(&self, id: i32) -> Result<bool, RepositoryError>;
+
+    /// Returns true if the question was in the trash and has been restored.
+    fn restore_question(&self, id: i32) -> Result<bool, RepositoryError>;
+
+    /// Lists soft-deleted questions, most recently deleted first.
+    fn list_deleted_questions(&self) -> Result<Vec<DeletedQuestion>, RepositoryError>;
+
+    /// Permanently removes questions deleted before `before`. Returns the number of questions removed.
+    fn purge_questions(&self, before: DateTime<Utc>) -> Result<u64, RepositoryError>;
+}
+
+pub trait QuizRepository: Send + Sync {
+    /// Saves a new session. Its answers are saved with `save_answer`.
+    fn save_session(&self, session: &QuizSession) -> Result<(), RepositoryError>;
+
+    /// Loads a session with its answers.
+    fn get_session(&self, id: &str) -> Result<Option<QuizSession>, RepositoryError>;
+
+    /// Loads the sessions started by the player, with their answers, most recently started first.
+    fn list_sessions(&self, player_id: &str) -> Result<Vec<QuizSession>, RepositoryError>;
+
+    /// Records the answer unless its question was already answered or the session is finished.
+    /// Returns true if the answer was recorded.
+    fn save_answer(&self, session_id: &str, answer: &QuizAnswer) -> Result<bool, RepositoryError>;
+
+    /// Marks the session finished unless it already is. Returns true if the session was finished by this call.
+    fn finish_session(&self, id: &str, finished_on: DateTime<Utc>) -> Result<bool, RepositoryError>;
+}
+
+pub trait PlayersRepository: Send + Sync {
+    /// Saves a new player with their password hash. Returns false if the username is taken.
+    fn save_player(&self, player: &Player, password_hash: &str) -> Result<bool, RepositoryError>;
+
+    fn get_player(&self, id: &str) -> Result<Option<Player>, RepositoryError>;
+
+    /// Loads the player with the normalized username, with their password hash.
+    fn get_player_by_username(
+        &self,
+        username: &str,
+    ) -> Result<Option<(Player, String)>, RepositoryError>;
+
+    /// Loads the anonymous player playing on the device, saving `player` as that player if there is none yet.
+    fn get_or_save_device_player(
+        &self,
+        device_id: &str,
+        player: &Player,
+    ) -> Result<Player, RepositoryError>;
+
+    /// Moves the quiz sessions of an anonymous player to another player and removes the anonymous player,
+    /// all or nothing. Returns the number of sessions moved, or `None` if there is no such anonymous player.
+    fn merge_player(
+        &self,
+        anonymous_id: &str,
+        into_id: &str,
+    ) -> Result<Option<u64>, RepositoryError>;
+}
+
+/// The storage backend, selected by the scheme of the connection string
+/// (`postgres://...`, `sqlite://<path>` or `memory://`).
+pub enum Storage {
+    Postgres(Pool<PostgresConnectionManager>),
+    Sqlite(Pool<SqliteConnectionManager>),
+    InMemory(Arc<RwLock<MemoryStore>>),
+}
+
+impl Storage {
+    pub fn connect(conn_string: &str) -> Result<Storage, RepositoryError> {
+        let url = Url::parse(conn_string).map_err(|e| {
+            RepositoryError::ConnectionError(format!("Invalid connection string: {}", e))
+        })?;
+
+        match url.scheme() {
+            "postgres" | "postgresql" => {
+                let manager = PostgresConnectionManager::new(conn_string, TlsMode::None)?;
+                Ok(Storage::Postgres(Pool::new(manager)?))
+            }
+            // Everything after `sqlite://` is the database file path, e.g. `sqlite:///var/lib/quizzical.db`.
+            "sqlite" => match conn_string.strip_prefix("sqlite://") {
+                Some(path) if !path.is_empty() => {
+                    Ok(Storage::Sqlite(self::sqlite::sqlite_pool(path)?))
+                }
+                _ => Err(RepositoryError::ConnectionError(format!(
+                    "Invalid connection string '{}': expected sqlite://<path>",
+                    conn_string
+                ))),
+            },
+            "memory" => Ok(Storage::InMemory(MemoryStore::new())),
+            scheme => Err(RepositoryError::ConnectionError(format!(
+                "Unsupported storage scheme '{}'",
+                scheme
+            ))),
+        }
+    }
+
+    pub fn categories_repository(&self) -> Arc<dyn CategoriesRepository> {
+        match *self {
+            Storage::Postgres(ref pool) => {
+                Arc::new(PostgresCategoriesRepository::new(pool.clone()))
+            }
+            Storage::Sqlite(ref pool) => Arc::new(SqliteCategoriesRepository::new(pool.clone())),
+            Storage::InMemory(ref store) => {
+                Arc::new(InMemoryCategoriesRepository::new(store.clone()))
+            }
+        }
+    }
+
+    pub fn questions_repository(&self) -> Arc<dyn QuestionsRepository> {
+        match *self {
+            Storage::Postgres(ref pool) => Arc::new(PostgresQuestionsRepository::new(pool.clone())),
+            Storage::Sqlite(ref pool) => Arc::new(SqliteQuestionsRepository::new(pool.clone())),
+            Storage::InMemory(ref store) => {
+                Arc::new(InMemoryQuestionsRepository::new(store.clone()))
+            }
+        }
+    }
+
+    pub fn quiz_repository(&self) -> Arc<dyn QuizRepository> {
+        match *self {
+            Storage::Postgres(ref pool) => Arc::new(PostgresQuizRepository::new(pool.clone())),
+            Storage::Sqlite(ref pool) => Arc::new(SqliteQuizRepository::new(pool.clone())),
+            Storage::InMemory(ref store) => Arc::new(InMemoryQuizRepository::new(store.clone())),
+        }
+    }
+
+    pub fn players_repository(&self) -> Arc<dyn PlayersRepository> {
+        match *self {
+            Storage::Postgres(ref pool) => Arc::new(PostgresPlayersRepository::new(pool.clone())),
+            Storage::Sqlite(ref pool) => Arc::new(SqlitePlayersRepository::new(pool.clone())),
+            Storage::InMemory(ref store) => Arc::new(InMemoryPlayersRepository::new(store.clone())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn highlighted(text: &str) -> String {
+        format!("{}{}{}", HIGHLIGHT_START, text, HIGHLIGHT_END)
+    }
+
+    #[test]
+    fn snippet_html_escapes_the_text_and_marks_the_matches() {
+        let snippet = format!(
+            "Is {} < 2 && \"x\" > 'y'? <script>alert(1)</script>",
+            highlighted("<b>1</b>")
+        );
+        assert_eq!(
+            snippet_html(&snippet),
+            "Is <mark>&lt;b&gt;1&lt;/b&gt;</mark> &lt; 2 &amp;&amp; &quot;x&quot; &gt; &#39;y&#39;? &lt;script&gt;alert(1)&lt;/script&gt;"
+        );
+    }
+
+    #[test]
+    fn snippet_html_keeps_the_marks_balanced() {
+        let snippet = format!(
+            "{}a{}{}b{}{}",
+            HIGHLIGHT_END, HIGHLIGHT_START, HIGHLIGHT_START, HIGHLIGHT_END, HIGHLIGHT_END
+        );
+        assert_eq!(snippet_html(&snippet), "a<mark>b</mark>");
+        assert_eq!(
+            snippet_html(&format!("{}open", HIGHLIGHT_START)),
+            "<mark>open</mark>"
+        );
+        assert_eq!(snippet_html("plain & simple"), "plain &amp; simple");
+    }
+
+    #[test]
+    fn sqlite_connection_strings_need_a_path_after_the_slashes() {
+        for conn_string in &["sqlite:a", "sqlite:/var/db.sqlite", "sqlite://"] {
+            match Storage::connect(conn_string) {
+                Err(RepositoryError::ConnectionError(message)) => {
+                    assert!(message.contains("sqlite://<path>"), "{}", message)
+                }
+                Err(e) => panic!("unexpected error {:?}", e),
+                Ok(_) => panic!("'{}' was accepted", conn_string),
+            }
+        }
+    }
+}
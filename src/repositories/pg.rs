@@ -0,0 +1,1618 @@
+use super::{
+    choices_from_json, choices_to_json, difficulty_from_str, list_from_json, list_to_json,
+    question_type_from_str, revision_list_from_json, snippet_html, CategoriesRepository,
+    PlayersRepository, QuestionFilter, QuestionsRepository, QuizRepository, RepositoryError,
+    SaveCategoryStatus, SearchFilter, HIGHLIGHT_END, HIGHLIGHT_START,
+};
+use chrono::{DateTime, Utc};
+use log::{error, info};
+use models::{
+    Category, Choice, DeletedCategory, DeletedQuestion, Player, Question, QuestionRevision,
+    QuizAnswer, QuizSession, SearchHighlight, TagCount,
+};
+use postgres::rows::{Row, Rows};
+use postgres::transaction::Transaction;
+use postgres::types::ToSql;
+use r2d2::Pool;
+use r2d2_postgres::PostgresConnectionManager;
+use sampling::SampleKey;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+
+pub struct Connection {
+    pub pool: Pool<PostgresConnectionManager>,
+}
+
+impl Connection {
+    fn execute(&self, query: &str, params: &[&ToSql]) -> Result<u64, RepositoryError> {
+        return self
+            .pool
+            .get()
+            .map_err(|e| e.into())
+            .and_then(|c| c.execute(query, params).map_err(|e| e.into()));
+    }
+
+    fn query(&self, query: &str, params: &[&ToSql]) -> Result<Rows, RepositoryError> {
+        return self
+            .pool
+            .get()
+            .map_err(|e| e.into())
+            .and_then(|c| c.query(query, params).map_err(|e| e.into()));
+    }
+
+    fn transaction<T, F>(&self, do_transaction: F) -> Result<T, RepositoryError>
+    where
+        F: FnOnce(Transaction) -> Result<T, RepositoryError>,
+    {
+        return self.pool.get().map_err(|e| e.into()).and_then(|c| {
+            c.transaction()
+                .map_err(|e| e.into())
+                .and_then(|t| do_transaction(t))
+        });
+    }
+}
+
+impl std::convert::From<postgres::Error> for RepositoryError {
+    fn from(error: postgres::Error) -> Self {
+        if let Some(connection_error) = error.as_connection() {
+            return RepositoryError::ConnectionError(format!("{}", connection_error));
+        }
+
+        if let Some(db_error) = error.as_db() {
+            return RepositoryError::DatabaseError(
+                db_error.code.code().into(),
+                db_error.message.clone(),
+            );
+        }
+
+        if let Some(conversion_error) = error.as_conversion() {
+            return RepositoryError::ConversionError(format!("{}", conversion_error));
+        }
+
+        if let Some(io_error) = error.as_io() {
+            return RepositoryError::IOError(format!("{}", io_error));
+        }
+
+        return RepositoryError::UnknownError(Some(format!("{}", error)));
+    }
+}
+
+/// Records the state of a saved question as its next revision.
+fn insert_revision(
+    trans: &Transaction,
+    question: &Question,
+    author: &str,
+) -> Result<(), RepositoryError> {
+    let question_id = question.id.unwrap_or_default() as i64;
+    let choices = choices_to_json(&question.choices)?;
+    let free_text = answer_to_json(&question.free_text)?;
+    let ordering = answer_to_json(&question.ordering)?;
+    let matching = answer_to_json(&question.matching)?;
+    let numeric = answer_to_json(&question.numeric)?;
+    let sources = list_to_json(&question.sources)?;
+    let media = list_to_json(&question.media)?;
+    let tags = list_to_json(&question.tags)?;
+
+    trans
+        .execute(
+            "INSERT INTO question_revisions (question_id, revision, author, text, category, difficulty, question_type, choices, free_text, ordering, matching, numeric, explanation, sources, media, tags) SELECT $1, COALESCE(MAX(revision), 0) + 1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15 FROM question_revisions WHERE question_id = $1",
+            &[
+                &question_id,
+                &author,
+                &question.question,
+                &question.category,
+                &question.difficulty.as_str(),
+                &question.question_type.as_str(),
+                &choices,
+                &free_text,
+                &ordering,
+                &matching,
+                &numeric,
+                &question.explanation,
+                &sources,
+                &media,
+                &tags,
+            ],
+        )
+        .map_err(|e| {
+            error!(
+                "Insert revision failed for question_id '{}' with reason '{}'.",
+                question_id, e
+            );
+            e
+        })?;
+
+    Ok(())
+}
+
+/// The answers of free-text, ordering, matching and numeric questions are stored as JSON objects.
+fn answer_to_json<T: Serialize>(answer: &Option<T>) -> Result<Option<String>, RepositoryError> {
+    match *answer {
+        Some(ref answer) => serde_json::to_string(answer)
+            .map(Some)
+            .map_err(|e| RepositoryError::ConversionError(format!("{}", e))),
+        None => Ok(None),
+    }
+}
+
+fn answer_from_json<T: DeserializeOwned>(
+    json: Option<String>,
+) -> Result<Option<T>, RepositoryError> {
+    match json {
+        Some(ref json) => serde_json::from_str(json)
+            .map(Some)
+            .map_err(|e| RepositoryError::ConversionError(format!("{}", e))),
+        None => Ok(None),
+    }
+}
+
+/// The columns read by `question_from_row`, for a query on `questions q`.
+const QUESTION_COLUMNS: &str =
+    "q.id, q.text, q.category, q.difficulty, q.question_type, q.explanation, q.sources, q.media, q.free_text, q.ordering, q.matching, q.numeric";
+
+/// Maps a row selected with `QUESTION_COLUMNS`. The choices and tags are loaded separately.
+fn question_from_row(row: &Row) -> Result<Question, RepositoryError> {
+    let id: i64 = row.get("id");
+    let difficulty: String = row.get("difficulty");
+    let question_type: String = row.get("question_type");
+    let sources: String = row.get("sources");
+    let media: String = row.get("media");
+
+    Ok(Question {
+        id: Some(id as i32),
+        question: row.get("text"),
+        category: row.get("category"),
+        difficulty: difficulty_from_str(&difficulty)?,
+        question_type: question_type_from_str(&question_type)?,
+        choices: vec![],
+        explanation: row.get("explanation"),
+        sources: list_from_json(&sources)?,
+        media: list_from_json(&media)?,
+        tags: vec![],
+        free_text: answer_from_json(row.get("free_text"))?,
+        ordering: answer_from_json(row.get("ordering"))?,
+        matching: answer_from_json(row.get("matching"))?,
+        numeric: answer_from_json(row.get("numeric"))?,
+    })
+}
+
+/// The columns read by `category_from_row`.
+const CATEGORY_COLUMNS: &str = "name, parent, description, icon, colour, display_order, featured";
+
+fn category_from_row(row: &Row) -> Category {
+    Category {
+        title: row.get(0),
+        parent: row.get(1),
+        description: row.get(2),
+        icon: row.get(3),
+        colour: row.get(4),
+        display_order: row.get(5),
+        featured: row.get(6),
+    }
+}
+
+/// Selects the visible category named (or aliased) `$1` and, if `$2` is true, its visible descendants into `selected`.
+/// Descendants of inactive or deleted categories are not selected.
+const SELECTED_CATEGORIES: &str = "WITH RECURSIVE selected(name) AS (SELECT name FROM categories WHERE name = COALESCE((SELECT category FROM category_aliases WHERE alias = $1), $1) AND active = TRUE AND deleted_on IS NULL UNION SELECT c.name FROM categories c INNER JOIN selected s ON c.parent = s.name WHERE $2 AND c.active = TRUE AND c.deleted_on IS NULL) ";
+
+/// The questions selected by a `QuestionFilter`, given `SELECTED_CATEGORIES`, the difficulty names as `$3`, the tags as `$4`
+/// and the question type names as `$5`.
+/// A question has all the tags if it has as many of them as there are, since the filter's tags are distinct.
+const FILTERED_QUESTIONS: &str = "q.category IN (SELECT name FROM selected) AND q.deleted_on IS NULL AND (cardinality($3::text[]) = 0 OR q.difficulty = ANY($3)) AND (cardinality($4::text[]) = 0 OR (SELECT COUNT(*) FROM question_tags qt INNER JOIN tags t ON t.id = qt.tag_id WHERE qt.question_id = q.id AND t.name = ANY($4)) = cardinality($4::text[])) AND (cardinality($5::text[]) = 0 OR q.question_type = ANY($5))";
+
+/// The questions in an active category that is not deleted, which players can see.
+const VISIBLE_QUESTIONS: &str =
+    "q.category IN (SELECT name FROM categories WHERE active = TRUE AND deleted_on IS NULL)";
+
+/// The text search configuration that questions are indexed and searched with.
+const SEARCH_CONFIG: &str = "english";
+
+/// The questions matching a `SearchFilter`, given `SELECTED_CATEGORIES` and `query`, the `tsquery` parsed from the search text.
+/// Without a category `$1` is null, nothing is selected and every active category is searched instead.
+const SEARCHED_QUESTIONS: &str = "q.search_vector @@ query AND q.deleted_on IS NULL AND (CASE WHEN $1::text IS NULL THEN q.category IN (SELECT name FROM categories WHERE active = TRUE AND deleted_on IS NULL) ELSE q.category IN (SELECT name FROM selected) END)";
+
+/// Parses the search text `$4` with the configuration `$3` the way web search engines do: words are required,
+/// "quoted phrases" must appear together, `or` separates alternatives and `-word` excludes a word.
+const SEARCH_QUERY: &str = "websearch_to_tsquery($3::text::regconfig, $4) query";
+
+/// Recomputes the question's `search_vector` from its text and its saved choices, the text weighing more.
+fn update_search_vector(trans: &Transaction, question_id: i64) -> Result<(), RepositoryError> {
+    trans.execute(
+        "UPDATE questions q SET search_vector = setweight(to_tsvector($2::text::regconfig, q.text), 'A') || setweight(to_tsvector($2::text::regconfig, COALESCE((SELECT string_agg(c.text, ' / ' ORDER BY c.id) FROM choices c WHERE c.question_id = q.id), '')), 'B') WHERE q.id = $1",
+        &[&question_id, &SEARCH_CONFIG],
+    )?;
+    Ok(())
+}
+
+/// Replaces the tags of the question, creating the tags that do not exist yet.
+fn save_tags(
+    trans: &Transaction,
+    question_id: i64,
+    tags: &Vec<String>,
+) -> Result<(), RepositoryError> {
+    trans.execute(
+        "DELETE FROM question_tags WHERE question_id = $1",
+        &[&question_id],
+    )?;
+    if tags.is_empty() {
+        return Ok(());
+    }
+
+    trans.execute(
+        "INSERT INTO tags (name) SELECT unnest($1::text[]) ON CONFLICT (name) DO NOTHING",
+        &[tags],
+    )?;
+    trans.execute(
+        "INSERT INTO question_tags (question_id, tag_id) SELECT $1, id FROM tags WHERE name = ANY($2)",
+        &[&question_id, tags],
+    )?;
+    Ok(())
+}
+
+/// Returns the category that `name` is an alias of, or `name` itself if it is not an alias.
+fn resolve_category(trans: &Transaction, name: &str) -> Result<String, RepositoryError> {
+    let rows = trans.query(
+        "SELECT category FROM category_aliases WHERE alias = $1",
+        &[&name],
+    )?;
+
+    Ok(match rows.is_empty() {
+        true => name.to_string(),
+        false => rows.get(0).get(0),
+    })
+}
+
+pub struct PostgresCategoriesRepository {
+    pub conn: Connection,
+}
+
+impl PostgresCategoriesRepository {
+    pub fn new(pool: Pool<PostgresConnectionManager>) -> PostgresCategoriesRepository {
+        return PostgresCategoriesRepository {
+            conn: Connection { pool: pool },
+        };
+    }
+}
+
+impl CategoriesRepository for PostgresCategoriesRepository {
+    fn save_category_and_set_active(
+        &self,
+        category: &str,
+        active: Option<bool>,
+    ) -> Result<SaveCategoryStatus, RepositoryError> {
+        info!("save_category(category: '{:?}').", category);
+
+        let (field_names, value_placeholders, on_conflict, values) = match active {
+            Some(x) => (
+                "(name,active)",
+                "($1,$2)",
+                "ON CONFLICT(name) DO UPDATE SET active=$2",
+                vec![&category as &ToSql, &active as &ToSql],
+            ),
+            None => (
+                "(name)",
+                "($1)",
+                "ON CONFLICT DO NOTHING",
+                vec![&category as &ToSql],
+            ),
+        };
+
+        let query_string = &format!(
+            "INSERT INTO categories {} VALUES {} {};",
+            field_names, value_placeholders, on_conflict
+        );
+
+        let affected_rows = self.conn.transaction(|trans| {
+            // A new category takes precedence over an alias with the same name.
+            trans.execute(
+                "DELETE FROM category_aliases WHERE alias = $1",
+                &[&category],
+            )?;
+            let affected_rows = trans.execute(query_string, values.as_slice())?;
+
+            trans.set_commit();
+            trans.finish()?;
+
+            Ok(affected_rows)
+        })?;
+
+        info!(
+            "Inserting category suceeded with affected rows '{:?}'.",
+            affected_rows
+        );
+
+        Ok(match affected_rows {
+            x if x > 0u64 => SaveCategoryStatus::Created,
+            _ => SaveCategoryStatus::Exists,
+        })
+    }
+
+    fn list_categories(&self) -> Result<Vec<Category>, RepositoryError> {
+        let rows = &self.conn.query(
+            &format!(
+                "SELECT {} FROM categories WHERE active = true AND deleted_on IS NULL ORDER BY display_order, name",
+                CATEGORY_COLUMNS
+            ),
+            &[],
+        )?;
+
+        Ok(rows.iter().map(|row| category_from_row(&row)).collect())
+    }
+
+    fn get_category(&self, name: &str) -> Result<Option<Category>, RepositoryError> {
+        let rows = &self.conn.query(
+            &format!(
+                "SELECT {} FROM categories WHERE name = $1",
+                CATEGORY_COLUMNS
+            ),
+            &[&name],
+        )?;
+
+        Ok(rows.iter().next().map(|row| category_from_row(&row)))
+    }
+
+    fn update_category(&self, category: &Category) -> Result<bool, RepositoryError> {
+        info!("update_category(category: '{:?}').", category);
+
+        let affected_rows = self.conn.execute(
+            "UPDATE categories SET description = $1, icon = $2, colour = $3, display_order = $4, featured = $5 WHERE name = $6",
+            &[
+                &category.description,
+                &category.icon,
+                &category.colour,
+                &category.display_order,
+                &category.featured,
+                &category.title,
+            ],
+        )?;
+
+        Ok(affected_rows > 0u64)
+    }
+
+    fn set_category_parent(
+        &self,
+        name: &str,
+        parent: Option<&str>,
+    ) -> Result<bool, RepositoryError> {
+        let affected_rows = self.conn.execute(
+            "UPDATE categories SET parent = $1 WHERE name = $2",
+            &[&parent, &name],
+        )?;
+
+        Ok(affected_rows > 0u64)
+    }
+
+    fn set_category_active(&self, name: &str, active: bool) -> Result<bool, RepositoryError> {
+        let affected_rows = self.conn.execute(
+            "UPDATE categories SET active = $1 WHERE name = $2 AND active != $1",
+            &[&active, &name],
+        )?;
+
+        Ok(affected_rows > 0u64)
+    }
+
+    fn delete_category(&self, name: &str) -> Result<bool, RepositoryError> {
+        let affected_rows = self.conn.execute(
+            "UPDATE categories SET deleted_on = now() WHERE name = $1 AND deleted_on IS NULL",
+            &[&name],
+        )?;
+
+        Ok(affected_rows > 0u64)
+    }
+
+    fn restore_category(&self, name: &str) -> Result<bool, RepositoryError> {
+        let affected_rows = self.conn.execute(
+            "UPDATE categories SET deleted_on = NULL WHERE name = $1 AND deleted_on IS NOT NULL",
+            &[&name],
+        )?;
+
+        Ok(affected_rows > 0u64)
+    }
+
+    fn list_deleted_categories(&self) -> Result<Vec<DeletedCategory>, RepositoryError> {
+        let rows = &self.conn.query(
+            "SELECT name, deleted_on FROM categories WHERE deleted_on IS NOT NULL ORDER BY deleted_on DESC",
+            &[],
+        )?;
+
+        Ok(rows
+            .iter()
+            .map(|row| DeletedCategory {
+                title: row.get(0),
+                deleted_on: row.get(1),
+            })
+            .collect())
+    }
+
+    fn category_exists(&self, name: &str) -> Result<bool, RepositoryError> {
+        let rows = &self
+            .conn
+            .query("SELECT 1 FROM categories WHERE name = $1", &[&name])?;
+
+        Ok(!rows.is_empty())
+    }
+
+    fn rename_category(&self, from: &str, to: &str) -> Result<bool, RepositoryError> {
+        info!("rename_category(from: '{}', to: '{}').", from, to);
+
+        return self.conn.transaction(|trans| {
+            trans.execute("DELETE FROM category_aliases WHERE alias = $1", &[&to])?;
+
+            // Questions and existing aliases follow the new name through `ON UPDATE CASCADE`.
+            let renamed = trans.execute(
+                "UPDATE categories SET name = $1 WHERE name = $2",
+                &[&to, &from],
+            )?;
+            if renamed == 0 {
+                return Ok(false);
+            }
+
+            trans.execute(
+                "INSERT INTO category_aliases (alias, category) VALUES ($1, $2)",
+                &[&from, &to],
+            )?;
+
+            trans.set_commit();
+            trans.finish()?;
+
+            Ok(true)
+        });
+    }
+
+    fn merge_categories(&self, sources: &[String], target: &str) -> Result<u64, RepositoryError> {
+        info!(
+            "merge_categories(sources: '{:?}', target: '{}').",
+            sources, target
+        );
+
+        return self.conn.transaction(|trans| {
+            let mut moved = 0;
+
+            for source in sources {
+                moved += trans.execute(
+                    "UPDATE questions SET category = $1 WHERE category = $2",
+                    &[&target, source],
+                )?;
+                trans.execute(
+                    "UPDATE category_aliases SET category = $1 WHERE category = $2",
+                    &[&target, source],
+                )?;
+                trans.execute(
+                    "UPDATE categories SET parent = $1 WHERE parent = $2",
+                    &[&target, source],
+                )?;
+
+                let removed = trans.execute("DELETE FROM categories WHERE name = $1", &[source])?;
+                if removed == 0 {
+                    return Err(RepositoryError::NotFound(format!(
+                        "Category '{}' does not exist",
+                        source
+                    )));
+                }
+
+                trans.execute(
+                    "INSERT INTO category_aliases (alias, category) VALUES ($1, $2)",
+                    &[source, &target],
+                )?;
+            }
+
+            trans.set_commit();
+            trans.finish()?;
+
+            Ok(moved)
+        });
+    }
+
+    fn purge_categories(&self, before: DateTime<Utc>) -> Result<u64, RepositoryError> {
+        info!("purge_categories(before: '{}').", before);
+
+        return self.conn.transaction(|trans| {
+            trans.execute(
+                "DELETE FROM questions WHERE category IN (SELECT name FROM categories WHERE deleted_on < $1)",
+                &[&before],
+            )?;
+            let purged = trans.execute("DELETE FROM categories WHERE deleted_on < $1", &[&before])?;
+
+            trans.set_commit();
+            trans.finish()?;
+
+            Ok(purged)
+        });
+    }
+}
+
+pub struct PostgresQuestionsRepository {
+    pub conn: Connection,
+}
+
+impl PostgresQuestionsRepository {
+    pub fn new(pool: Pool<PostgresConnectionManager>) -> PostgresQuestionsRepository {
+        return PostgresQuestionsRepository {
+            conn: Connection { pool: pool },
+        };
+    }
+
+    /// Loads the choices of the given questions, grouped by question id.
+    fn load_choices(
+        &self,
+        question_ids: &Vec<i64>,
+    ) -> Result<HashMap<i64, Vec<Choice>>, RepositoryError> {
+        let choices_rows = &self
+            .conn
+            .query(
+                "SELECT id,text,correct,question_id,explanation,media FROM choices WHERE question_id = ANY($1) ORDER BY id",
+                &[question_ids],
+            )
+            .map_err(|e| {
+                error!(
+                    "Error loading choices for questions '{:?}': {}",
+                    question_ids, e
+                );
+                e
+            })?;
+
+        let mut choices_map: HashMap<i64, Vec<Choice>> = HashMap::new();
+        for choice_row in choices_rows {
+            let question_id: i64 = choice_row.get(3);
+            let choice_id: i64 = choice_row.get(0);
+            let media: String = choice_row.get(5);
+            let choice = Choice {
+                id: Some(choice_id as i32),
+                title: choice_row.get(1),
+                correct: choice_row.get(2),
+                explanation: choice_row.get(4),
+                media: list_from_json(&media)?,
+            };
+
+            if let Some(choices) = choices_map.get_mut(&question_id) {
+                choices.push(choice);
+                continue;
+            }
+
+            choices_map.insert(question_id, vec![choice]);
+        }
+
+        Ok(choices_map)
+    }
+
+    /// Loads the tags of the given questions, grouped by question id and sorted like `normalize_tags` sorts them.
+    fn load_tags(
+        &self,
+        question_ids: &Vec<i64>,
+    ) -> Result<HashMap<i64, Vec<String>>, RepositoryError> {
+        let tag_rows = &self
+            .conn
+            .query(
+                "SELECT qt.question_id, t.name FROM question_tags qt INNER JOIN tags t ON t.id = qt.tag_id WHERE qt.question_id = ANY($1) ORDER BY t.name COLLATE \"C\"",
+                &[question_ids],
+            )
+            .map_err(|e| {
+                error!("Error loading tags for questions '{:?}': {}", question_ids, e);
+                e
+            })?;
+
+        let mut tags_map: HashMap<i64, Vec<String>> = HashMap::new();
+        for tag_row in tag_rows {
+            let question_id: i64 = tag_row.get(0);
+            tags_map
+                .entry(question_id)
+                .or_insert_with(Vec::new)
+                .push(tag_row.get(1));
+        }
+
+        Ok(tags_map)
+    }
+
+    /// Loads a question that is not deleted and, if `visible_only`, whose category is visible.
+    fn load_question(
+        &self,
+        id: i32,
+        visible_only: bool,
+    ) -> Result<Option<Question>, RepositoryError> {
+        let question_id = id as i64;
+
+        let question_rows =
+            &self
+                .conn
+                .query(
+                    &format!(
+                    "SELECT {} FROM questions q WHERE q.id = $1 AND q.deleted_on IS NULL AND ({})",
+                    QUESTION_COLUMNS,
+                    if visible_only { VISIBLE_QUESTIONS } else { "TRUE" }
+                ),
+                    &[&question_id],
+                )
+                .map_err(|e| {
+                    error!("Error loading question '{}': {}", id, e);
+                    e
+                })?;
+
+        if question_rows.is_empty() {
+            return Ok(None);
+        }
+
+        let mut question = question_from_row(&question_rows.get(0))?;
+        let mut choices_map = self.load_choices(&vec![question_id])?;
+        question.choices = choices_map.remove(&question_id).unwrap_or(vec![]);
+        let mut tags_map = self.load_tags(&vec![question_id])?;
+        question.tags = tags_map.remove(&question_id).unwrap_or(vec![]);
+
+        Ok(Some(question))
+    }
+}
+
+impl QuestionsRepository for PostgresQuestionsRepository {
+    fn save_question(
+        &self,
+        question: &Question,
+        author: &str,
+    ) -> Result<Question, RepositoryError> {
+        info!("save_question(question: '{:?}').", question);
+
+        return self.conn.transaction(|trans| {
+            info!("Inserting question '{:?}' into database.", question);
+            let category = resolve_category(&trans, &question.category)?;
+            let free_text = answer_to_json(&question.free_text)?;
+            let ordering = answer_to_json(&question.ordering)?;
+            let matching = answer_to_json(&question.matching)?;
+            let numeric = answer_to_json(&question.numeric)?;
+            let sources = list_to_json(&question.sources)?;
+            let media = list_to_json(&question.media)?;
+
+            let id_rows = &trans
+                .query(
+                    "INSERT INTO questions (text, category, difficulty, question_type, free_text, ordering, matching, numeric, explanation, sources, media) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) RETURNING id",
+                    &[
+                        &question.question,
+                        &category,
+                        &question.difficulty.as_str(),
+                        &question.question_type.as_str(),
+                        &free_text,
+                        &ordering,
+                        &matching,
+                        &numeric,
+                        &question.explanation,
+                        &sources,
+                        &media,
+                    ],
+                )
+                .or_else(|e| {
+                    error!(
+                        "Insert question failed for question: '{:?}', with reason: '{:?}'.",
+                        question, e
+                    );
+                    //rollback will happen when transaction is dropped (i.e. Destructor)
+                    trans.set_rollback();
+                    Err(e)
+                })?;
+
+            info!(
+                "Insert question succeeded for question: '{:?}', with updated rows: '{:?}'.",
+                question, id_rows,
+            );
+
+            let question_id: i64 = id_rows
+                .iter()
+                .next()
+                .and_then(|row| row.get(0))
+                .ok_or(RepositoryError::UnknownError(Some(
+                    "Failed to get question id".into(),
+                )))
+                .map_err(|e| {
+                    error!(
+                        "Insert question succeeded but no id received for question: '{:?}'.",
+                        question
+                    );
+                    trans.set_rollback();
+                    e
+                })?;
+
+            //Since we don't know how many choices a question has, we need to build a query string for bulk insert manually.
+
+            //value_placeholders refers to the `($1, $2)` part of the query.
+            let mut value_placeholders: Vec<String> = vec![];
+            //total is the number of fields to be inserted per choice multiplied by the number of choices
+            let num_fields = 5;
+            let total = num_fields * question.choices.len();
+
+            for i in (0..total).step_by(num_fields) {
+                let fields: Vec<String> = (i + 1..i + 1 + num_fields)
+                    .map(|n| format!("${}", n))
+                    .collect();
+                value_placeholders.push(format!("({})", fields.join(", ")))
+            }
+
+            //join all the value placeholders i.e. ($1,$2), ($3,$4)
+            let joined_value_placeholders = value_placeholders.join(",");
+
+            let query_string = &format!(
+                "INSERT INTO choices (question_id, text, correct, explanation, media) VALUES {} RETURNING id",
+                joined_value_placeholders
+            );
+
+            let choices_media = question
+                .choices
+                .iter()
+                .map(|choice| list_to_json(&choice.media))
+                .collect::<Result<Vec<String>, RepositoryError>>()?;
+
+            let mut values: Vec<&ToSql> = vec![];
+            for (choice, media) in question.choices.iter().zip(choices_media.iter()) {
+                values.push(&question_id);
+                values.push(&choice.title);
+                values.push(&choice.correct);
+                values.push(&choice.explanation);
+                values.push(media);
+            }
+
+            info!(
+                "Will insert choices for question id '{}' using query '{}' and values '{:?}'.",
+                question_id, query_string, values
+            );
+
+            // Free-text questions have no choices, and `VALUES` cannot be empty.
+            let ids: Vec<i64> = if question.choices.is_empty() {
+                vec![]
+            } else {
+                let rows: Rows = trans.query(query_string, values.as_slice()).or_else(|e| {
+                    error!(
+                        "Bulk insert choices failed for question_id: '{}', reason: {}.",
+                        question_id, e
+                    );
+                    //rollback will happen when transaction is dropped (i.e. Destructor)
+                    trans.set_rollback();
+                    Err(e)
+                })?;
+                rows.iter().map(|row| row.get(0)).collect()
+            };
+
+            // Create a new vector of choices, with the id field set.
+            let choices_with_ids = question
+                .choices
+                .iter()
+                .zip(ids.iter())
+                .map(|choice_id_tuple| {
+                    let choice = choice_id_tuple.0;
+                    let id = choice_id_tuple.1;
+                    Choice {
+                        id: Some(*id as i32),
+                        ..choice.clone()
+                    }
+                })
+                .collect();
+
+            let saved_question = Question {
+                id: Some(question_id as i32),
+                question: question.question.clone(),
+                category: category,
+                difficulty: question.difficulty,
+                question_type: question.question_type,
+                choices: choices_with_ids,
+                explanation: question.explanation.clone(),
+                sources: question.sources.clone(),
+                media: question.media.clone(),
+                tags: question.tags.clone(),
+                free_text: question.free_text.clone(),
+                ordering: question.ordering.clone(),
+                matching: question.matching.clone(),
+                numeric: question.numeric.clone(),
+            };
+
+            save_tags(&trans, question_id, &saved_question.tags)?;
+            update_search_vector(&trans, question_id)?;
+            insert_revision(&trans, &saved_question, author)?;
+
+            trans.set_commit();
+
+            trans
+                .finish()
+                .map_err(|e| {
+                    error!(
+                        "Finishing insert question failed for question_id '{}' with reason '{}'.",
+                        question_id, e
+                    );
+                    e.into()
+                })
+                .and(Ok(saved_question))
+        });
+    }
+
+    fn get_question(&self, id: i32) -> Result<Option<Question>, RepositoryError> {
+        self.load_question(id, false)
+    }
+
+    fn get_visible_question(&self, id: i32) -> Result<Option<Question>, RepositoryError> {
+        self.load_question(id, true)
+    }
+
+    fn update_question(
+        &self,
+        question: &Question,
+        author: &str,
+    ) -> Result<Question, RepositoryError> {
+        info!("update_question(question: '{:?}').", question);
+
+        let question_id = question.id.ok_or(RepositoryError::UnknownError(Some(
+            "Question to update has no id".into(),
+        )))? as i64;
+
+        return self.conn.transaction(|trans| {
+            let category = resolve_category(&trans, &question.category)?;
+            let free_text = answer_to_json(&question.free_text)?;
+            let ordering = answer_to_json(&question.ordering)?;
+            let matching = answer_to_json(&question.matching)?;
+            let numeric = answer_to_json(&question.numeric)?;
+            let sources = list_to_json(&question.sources)?;
+            let media = list_to_json(&question.media)?;
+            let updated_rows = trans
+                .execute(
+                    "UPDATE questions SET text = $1, category = $2, difficulty = $3, question_type = $4, free_text = $5, ordering = $6, matching = $7, numeric = $8, explanation = $9, sources = $10, media = $11 WHERE id = $12 AND deleted_on IS NULL",
+                    &[
+                        &question.question,
+                        &category,
+                        &question.difficulty.as_str(),
+                        &question.question_type.as_str(),
+                        &free_text,
+                        &ordering,
+                        &matching,
+                        &numeric,
+                        &question.explanation,
+                        &sources,
+                        &media,
+                        &question_id,
+                    ],
+                )
+                .map_err(|e| {
+                    error!(
+                        "Update question failed for question: '{:?}', with reason: '{:?}'.",
+                        question, e
+                    );
+                    e
+                })?;
+
+            if updated_rows == 0 {
+                return Err(RepositoryError::NotFound(format!(
+                    "Question {} does not exist",
+                    question_id
+                )));
+            }
+
+            // Choices that are not part of the updated question are removed.
+            let kept_ids: Vec<i64> = question
+                .choices
+                .iter()
+                .filter_map(|choice| choice.id.map(|id| id as i64))
+                .collect();
+
+            trans.execute(
+                "DELETE FROM choices WHERE question_id = $1 AND NOT (id = ANY($2))",
+                &[&question_id, &kept_ids],
+            )?;
+
+            let mut choices_with_ids: Vec<Choice> = Vec::with_capacity(question.choices.len());
+            for choice in question.choices.iter() {
+                let media = list_to_json(&choice.media)?;
+                let choice_id: i64 = match choice.id {
+                    Some(id) => {
+                        let choice_id = id as i64;
+                        let updated_rows = trans.execute(
+                            "UPDATE choices SET text = $1, correct = $2, explanation = $3, media = $4 WHERE id = $5 AND question_id = $6",
+                            &[
+                                &choice.title,
+                                &choice.correct,
+                                &choice.explanation,
+                                &media,
+                                &choice_id,
+                                &question_id,
+                            ],
+                        )?;
+
+                        if updated_rows == 0 {
+                            return Err(RepositoryError::NotFound(format!(
+                                "Choice {} does not belong to question {}",
+                                choice_id, question_id
+                            )));
+                        }
+                        choice_id
+                    }
+                    None => trans
+                        .query(
+                            "INSERT INTO choices (question_id, text, correct, explanation, media) VALUES ($1, $2, $3, $4, $5) RETURNING id",
+                            &[
+                                &question_id,
+                                &choice.title,
+                                &choice.correct,
+                                &choice.explanation,
+                                &media,
+                            ],
+                        )?
+                        .get(0)
+                        .get(0),
+                };
+
+                choices_with_ids.push(Choice {
+                    id: Some(choice_id as i32),
+                    ..choice.clone()
+                });
+            }
+
+            let updated_question = Question {
+                id: question.id,
+                question: question.question.clone(),
+                category: category,
+                difficulty: question.difficulty,
+                question_type: question.question_type,
+                choices: choices_with_ids,
+                explanation: question.explanation.clone(),
+                sources: question.sources.clone(),
+                media: question.media.clone(),
+                tags: question.tags.clone(),
+                free_text: question.free_text.clone(),
+                ordering: question.ordering.clone(),
+                matching: question.matching.clone(),
+                numeric: question.numeric.clone(),
+            };
+
+            save_tags(&trans, question_id, &updated_question.tags)?;
+            update_search_vector(&trans, question_id)?;
+            insert_revision(&trans, &updated_question, author)?;
+
+            trans.set_commit();
+
+            trans
+                .finish()
+                .map_err(|e| {
+                    error!(
+                        "Finishing update question failed for question_id '{}' with reason '{}'.",
+                        question_id, e
+                    );
+                    e.into()
+                })
+                .and(Ok(updated_question))
+        });
+    }
+
+    fn list_revisions(&self, question_id: i32) -> Result<Vec<QuestionRevision>, RepositoryError> {
+        let rows = &self.conn.query(
+            "SELECT revision,author,created_on,text,category,choices,difficulty,question_type,free_text,ordering,matching,numeric,explanation,sources,media,tags FROM question_revisions WHERE question_id = $1 ORDER BY revision",
+            &[&(question_id as i64)],
+        )?;
+
+        let mut revisions: Vec<QuestionRevision> = Vec::with_capacity(rows.len());
+        for row in rows {
+            let choices: String = row.get(5);
+            let difficulty: Option<String> = row.get(6);
+            let question_type: Option<String> = row.get(7);
+            revisions.push(QuestionRevision {
+                revision: row.get(0),
+                author: row.get(1),
+                created_on: row.get(2),
+                question: row.get(3),
+                category: row.get(4),
+                difficulty: match difficulty {
+                    Some(ref name) => Some(difficulty_from_str(name)?),
+                    None => None,
+                },
+                question_type: match question_type {
+                    Some(ref name) => Some(question_type_from_str(name)?),
+                    None => None,
+                },
+                choices: choices_from_json(&choices)?,
+                free_text: answer_from_json(row.get(8))?,
+                ordering: answer_from_json(row.get(9))?,
+                matching: answer_from_json(row.get(10))?,
+                numeric: answer_from_json(row.get(11))?,
+                explanation: row.get(12),
+                sources: revision_list_from_json(row.get(13))?,
+                media: revision_list_from_json(row.get(14))?,
+                tags: revision_list_from_json(row.get(15))?,
+            });
+        }
+
+        Ok(revisions)
+    }
+
+    fn get_revision(
+        &self,
+        question_id: i32,
+        revision: i32,
+    ) -> Result<Option<QuestionRevision>, RepositoryError> {
+        let rows = &self.conn.query(
+            "SELECT author,created_on,text,category,choices,difficulty,question_type,free_text,ordering,matching,numeric,explanation,sources,media,tags FROM question_revisions WHERE question_id = $1 AND revision = $2",
+            &[&(question_id as i64), &revision],
+        )?;
+
+        if rows.is_empty() {
+            return Ok(None);
+        }
+
+        let row = rows.get(0);
+        let choices: String = row.get(4);
+        let difficulty: Option<String> = row.get(5);
+        let question_type: Option<String> = row.get(6);
+
+        Ok(Some(QuestionRevision {
+            revision: revision,
+            author: row.get(0),
+            created_on: row.get(1),
+            question: row.get(2),
+            category: row.get(3),
+            difficulty: match difficulty {
+                Some(ref name) => Some(difficulty_from_str(name)?),
+                None => None,
+            },
+            question_type: match question_type {
+                Some(ref name) => Some(question_type_from_str(name)?),
+                None => None,
+            },
+            choices: choices_from_json(&choices)?,
+            free_text: answer_from_json(row.get(7))?,
+            ordering: answer_from_json(row.get(8))?,
+            matching: answer_from_json(row.get(9))?,
+            numeric: answer_from_json(row.get(10))?,
+            explanation: row.get(11),
+            sources: revision_list_from_json(row.get(12))?,
+            media: revision_list_from_json(row.get(13))?,
+            tags: revision_list_from_json(row.get(14))?,
+        }))
+    }
+
+    fn count_questions(&self, filter: &QuestionFilter) -> Result<i64, RepositoryError> {
+        let count_rows = &self
+            .conn
+            .query(
+                &format!(
+                    "{}SELECT COUNT(q.id) FROM questions q WHERE {}",
+                    SELECTED_CATEGORIES, FILTERED_QUESTIONS
+                ),
+                &[
+                    &filter.category,
+                    &filter.include_descendants,
+                    &filter.difficulty_names(),
+                    &filter.tags,
+                    &filter.question_type_names(),
+                ],
+            )
+            .map_err(|e| {
+                error!("Error counting questions for filter '{:?}': {}", filter, e);
+                e
+            })?;
+
+        let count: i64 = match count_rows.is_empty() {
+            true => 0i64,
+            false => count_rows.get(0).get(0),
+        };
+
+        Ok(count)
+    }
+
+    fn get_questions(
+        &self,
+        filter: &QuestionFilter,
+        page: i64,
+        size: i64,
+    ) -> Result<Vec<Question>, RepositoryError> {
+        let offset = match page {
+            0 => 0i64,
+            _ => (page - 1i64) * size,
+        };
+
+        let question_rows = &self
+            .conn
+            .query(
+                &format!(
+                    "{}SELECT {} FROM questions q WHERE {} ORDER BY q.id LIMIT $6 OFFSET $7",
+                    SELECTED_CATEGORIES, QUESTION_COLUMNS, FILTERED_QUESTIONS
+                ),
+                &[
+                    &filter.category,
+                    &filter.include_descendants,
+                    &filter.difficulty_names(),
+                    &filter.tags,
+                    &filter.question_type_names(),
+                    &size,
+                    &offset,
+                ],
+            )
+            .map_err(|e| {
+                error!("Error loading questions for filter '{:?}': {}", filter, e);
+                e
+            })?;
+
+        if question_rows.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut question_ids: Vec<i64> = vec![];
+        for question_row in question_rows {
+            let id: i64 = question_row.get(0);
+            question_ids.push(id);
+        }
+
+        let choices_map = self.load_choices(&question_ids)?;
+        let mut tags_map = self.load_tags(&question_ids)?;
+
+        let mut questions: Vec<Question> = Vec::with_capacity(question_rows.len());
+        for question_row in question_rows {
+            let mut question = question_from_row(&question_row)?;
+            let id = question.id.unwrap_or_default() as i64;
+            question.choices = choices_map.get(&id).unwrap_or(&vec![]).to_vec();
+            question.tags = tags_map.remove(&id).unwrap_or(vec![]);
+            questions.push(question);
+        }
+
+        Ok(questions)
+    }
+
+    fn sample_question_ids(
+        &self,
+        filter: &QuestionFilter,
+        key: &SampleKey,
+        exclude_ids: &[i32],
+        count: i64,
+    ) -> Result<Vec<i32>, RepositoryError> {
+        let exclude_ids: Vec<i64> = exclude_ids.iter().map(|id| *id as i64).collect();
+
+        // `SampleKey::key`, one step per subquery. Every step is taken modulo 2^31 - 1, so no product overflows a BIGINT.
+        let id_rows = &self
+            .conn
+            .query(
+                &format!(
+                    "{}SELECT id FROM (SELECT id, (y * y + $9) % 2147483647 AS sample_key FROM (SELECT id, (x * x + $8) % 2147483647 AS y FROM (SELECT q.id, (q.id % 2147483647 * $6 + $7) % 2147483647 AS x FROM questions q WHERE {} AND NOT (q.id = ANY($10))) AS xs) AS ys) AS keyed ORDER BY sample_key, id LIMIT $11",
+                    SELECTED_CATEGORIES, FILTERED_QUESTIONS
+                ),
+                &[
+                    &filter.category,
+                    &filter.include_descendants,
+                    &filter.difficulty_names(),
+                    &filter.tags,
+                    &filter.question_type_names(),
+                    &key.multiplier,
+                    &key.offset,
+                    &key.first_round,
+                    &key.second_round,
+                    &exclude_ids,
+                    &count,
+                ],
+            )
+            .map_err(|e| {
+                error!(
+                    "Error sampling question ids for filter '{:?}': {}",
+                    filter, e
+                );
+                e
+            })?;
+
+        Ok(id_rows
+            .iter()
+            .map(|row| {
+                let id: i64 = row.get(0);
+                id as i32
+            })
+            .collect())
+    }
+
+    fn get_questions_by_ids(&self, ids: &[i32]) -> Result<Vec<Question>, RepositoryError> {
+        let question_ids: Vec<i64> = ids.iter().map(|id| *id as i64).collect();
+
+        let question_rows = &self
+            .conn
+            .query(
+                &format!(
+                    "SELECT {} FROM questions q WHERE q.id = ANY($1) AND q.deleted_on IS NULL ORDER BY q.id",
+                    QUESTION_COLUMNS
+                ),
+                &[&question_ids],
+            )
+            .map_err(|e| {
+                error!("Error loading questions '{:?}': {}", ids, e);
+                e
+            })?;
+
+        if question_rows.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut choices_map = self.load_choices(&question_ids)?;
+        let mut tags_map = self.load_tags(&question_ids)?;
+
+        let mut questions: Vec<Question> = Vec::with_capacity(question_rows.len());
+        for question_row in question_rows {
+            let mut question = question_from_row(&question_row)?;
+            let id = question.id.unwrap_or_default() as i64;
+            question.choices = choices_map.remove(&id).unwrap_or(vec![]);
+            question.tags = tags_map.remove(&id).unwrap_or(vec![]);
+            questions.push(question);
+        }
+
+        Ok(questions)
+    }
+
+    fn list_tags(&self) -> Result<Vec<TagCount>, RepositoryError> {
+        let rows = &self.conn.query(
+            "SELECT t.name, COUNT(*) FROM tags t INNER JOIN question_tags qt ON qt.tag_id = t.id INNER JOIN questions q ON q.id = qt.question_id INNER JOIN categories c ON c.name = q.category WHERE q.deleted_on IS NULL AND c.active = TRUE AND c.deleted_on IS NULL GROUP BY t.name ORDER BY COUNT(*) DESC, t.name COLLATE \"C\"",
+            &[],
+        )?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let count: i64 = row.get(1);
+                TagCount {
+                    name: row.get(0),
+                    count: count as i32,
+                }
+            })
+            .collect())
+    }
+
+    fn count_search_results(&self, search: &SearchFilter) -> Result<i64, RepositoryError> {
+        let count_rows = &self
+            .conn
+            .query(
+                &format!(
+                    "{}SELECT COUNT(q.id) FROM questions q, {} WHERE {}",
+                    SELECTED_CATEGORIES, SEARCH_QUERY, SEARCHED_QUESTIONS
+                ),
+                &[&search.category, &true, &SEARCH_CONFIG, &search.text],
+            )
+            .map_err(|e| {
+                error!("Error counting questions for search '{:?}': {}", search, e);
+                e
+            })?;
+
+        let count: i64 = match count_rows.is_empty() {
+            true => 0i64,
+            false => count_rows.get(0).get(0),
+        };
+
+        Ok(count)
+    }
+
+    fn search_questions(
+        &self,
+        search: &SearchFilter,
+        page: i64,
+        size: i64,
+    ) -> Result<Vec<(Question, SearchHighlight)>, RepositoryError> {
+        let offset = match page {
+            0 => 0i64,
+            _ => (page - 1i64) * size,
+        };
+
+        // The snippet is taken from the question text followed by its choice titles, so that matched choices are shown too.
+        let question_rows = &self
+            .conn
+            .query(
+                &format!(
+                    "{}SELECT {}, ts_rank(q.search_vector, query)::float8 AS rank, ts_headline($3::text::regconfig, q.text || COALESCE(' / ' || (SELECT string_agg(c.text, ' / ' ORDER BY c.id) FROM choices c WHERE c.question_id = q.id), ''), query, $7) AS snippet FROM questions q, {} WHERE {} ORDER BY rank DESC, q.id LIMIT $5 OFFSET $6",
+                    SELECTED_CATEGORIES, QUESTION_COLUMNS, SEARCH_QUERY, SEARCHED_QUESTIONS
+                ),
+                &[
+                    &search.category,
+                    &true,
+                    &SEARCH_CONFIG,
+                    &search.text,
+                    &size,
+                    &offset,
+                    &format!("StartSel={}, StopSel={}", HIGHLIGHT_START, HIGHLIGHT_END),
+                ],
+            )
+            .map_err(|e| {
+                error!("Error searching questions for search '{:?}': {}", search, e);
+                e
+            })?;
+
+        if question_rows.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let question_ids: Vec<i64> = question_rows.iter().map(|row| row.get("id")).collect();
+        let mut choices_map = self.load_choices(&question_ids)?;
+        let mut tags_map = self.load_tags(&question_ids)?;
+
+        let mut results: Vec<(Question, SearchHighlight)> = Vec::with_capacity(question_rows.len());
+        for question_row in question_rows {
+            let mut question = question_from_row(&question_row)?;
+            let id = question.id.unwrap_or_default() as i64;
+            question.choices = choices_map.remove(&id).unwrap_or(vec![]);
+            question.tags = tags_map.remove(&id).unwrap_or(vec![]);
+
+            let highlight = SearchHighlight {
+                question_id: id as i32,
+                rank: question_row.get("rank"),
+                snippet: snippet_html(&question_row.get::<_, String>("snippet")),
+            };
+            results.push((question, highlight));
+        }
+
+        Ok(results)
+    }
+
+    fn delete_question(&self, id: i32) -> Result<bool, RepositoryError> {
+        let affected_rows = self.conn.execute(
+            "UPDATE questions SET deleted_on = now() WHERE id = $1 AND deleted_on IS NULL",
+            &[&(id as i64)],
+        )?;
+
+        Ok(affected_rows > 0u64)
+    }
+
+    fn restore_question(&self, id: i32) -> Result<bool, RepositoryError> {
+        let affected_rows = self.conn.execute(
+            "UPDATE questions SET deleted_on = NULL WHERE id = $1 AND deleted_on IS NOT NULL",
+            &[&(id as i64)],
+        )?;
+
+        Ok(affected_rows > 0u64)
+    }
+
+    fn list_deleted_questions(&self) -> Result<Vec<DeletedQuestion>, RepositoryError> {
+        let question_rows = &self.conn.query(
+            &format!(
+                "SELECT {}, q.deleted_on FROM questions q WHERE q.deleted_on IS NOT NULL ORDER BY q.deleted_on DESC",
+                QUESTION_COLUMNS
+            ),
+            &[],
+        )?;
+
+        let question_ids: Vec<i64> = question_rows.iter().map(|row| row.get("id")).collect();
+        let mut choices_map = self.load_choices(&question_ids)?;
+        let mut tags_map = self.load_tags(&question_ids)?;
+
+        let mut deleted: Vec<DeletedQuestion> = Vec::with_capacity(question_rows.len());
+        for row in question_rows.iter() {
+            let mut question = question_from_row(&row)?;
+            let id = question.id.unwrap_or_default() as i64;
+            question.choices = choices_map.remove(&id).unwrap_or(vec![]);
+            question.tags = tags_map.remove(&id).unwrap_or(vec![]);
+            deleted.push(DeletedQuestion {
+                question: question,
+                deleted_on: row.get("deleted_on"),
+            });
+        }
+
+        Ok(deleted)
+    }
+
+    fn purge_questions(&self, before: DateTime<Utc>) -> Result<u64, RepositoryError> {
+        info!("purge_questions(before: '{}').", before);
+
+        // Choices are removed by `ON DELETE CASCADE`.
+        self.conn
+            .execute("DELETE FROM questions WHERE deleted_on < $1", &[&before])
+    }
+}
+
+pub struct PostgresQuizRepository {
+    pub conn: Connection,
+}
+
+impl PostgresQuizRepository {
+    pub fn new(pool: Pool<PostgresConnectionManager>) -> PostgresQuizRepository {
+        return PostgresQuizRepository {
+            conn: Connection { pool: pool },
+        };
+    }
+}
+
+impl QuizRepository for PostgresQuizRepository {
+    fn save_session(&self, session: &QuizSession) -> Result<(), RepositoryError> {
+        info!("save_session(session: '{:?}').", session);
+
+        self.conn
+            .execute(
+                "INSERT INTO quiz_sessions (id, category, player_id, question_ids, started_on) VALUES ($1, $2, $3, $4, $5)",
+                &[
+                    &session.id,
+                    &session.category,
+                    &session.player_id,
+                    &list_to_json(&session.question_ids)?,
+                    &session.started_on,
+                ],
+            )
+            .map_err(|e| {
+                error!("Insert quiz session failed for session '{}': {}", session.id, e);
+                e
+            })?;
+
+        Ok(())
+    }
+
+    fn get_session(&self, id: &str) -> Result<Option<QuizSession>, RepositoryError> {
+        let session_rows = &self.conn.query(
+            "SELECT id, category, player_id, question_ids, started_on, finished_on FROM quiz_sessions WHERE id = $1",
+            &[&id],
+        )?;
+
+        if session_rows.is_empty() {
+            return Ok(None);
+        }
+        let row = session_rows.get(0);
+        let question_ids: String = row.get(3);
+
+        let answer_rows = &self.conn.query(
+            "SELECT question_id, choice_id, correct, answered_on FROM quiz_answers WHERE session_id = $1 ORDER BY answered_on, question_id",
+            &[&id],
+        )?;
+        let answers = answer_rows
+            .iter()
+            .map(|answer_row| {
+                let question_id: i64 = answer_row.get(0);
+                let choice_id: i64 = answer_row.get(1);
+                QuizAnswer {
+                    question_id: question_id as i32,
+                    choice_id: choice_id as i32,
+                    correct: answer_row.get(2),
+                    answered_on: answer_row.get(3),
+                }
+            })
+            .collect();
+
+        Ok(Some(QuizSession {
+            id: row.get(0),
+            category: row.get(1),
+            player_id: row.get(2),
+            question_ids: list_from_json(&question_ids)?,
+            answers: answers,
+            started_on: row.get(4),
+            finished_on: row.get(5),
+        }))
+    }
+
+    fn list_sessions(&self, player_id: &str) -> Result<Vec<QuizSession>, RepositoryError> {
+        let rows = &self.conn.query(
+            "SELECT id FROM quiz_sessions WHERE player_id = $1 ORDER BY started_on DESC, id",
+            &[&player_id],
+        )?;
+
+        let mut sessions = vec![];
+        for row in rows.iter() {
+            let id: String = row.get(0);
+            sessions.extend(self.get_session(&id)?);
+        }
+        Ok(sessions)
+    }
+
+    fn save_answer(&self, session_id: &str, answer: &QuizAnswer) -> Result<bool, RepositoryError> {
+        // Inserted through the session, so that nothing is inserted once it is finished.
+        let inserted = self.conn.execute(
+            "INSERT INTO quiz_answers (session_id, question_id, choice_id, correct, answered_on) SELECT id, $2, $3, $4, $5 FROM quiz_sessions WHERE id = $1 AND finished_on IS NULL ON CONFLICT DO NOTHING",
+            &[
+                &session_id,
+                &(answer.question_id as i64),
+                &(answer.choice_id as i64),
+                &answer.correct,
+                &answer.answered_on,
+            ],
+        )?;
+
+        Ok(inserted > 0)
+    }
+
+    fn finish_session(
+        &self,
+        id: &str,
+        finished_on: DateTime<Utc>,
+    ) -> Result<bool, RepositoryError> {
+        let updated = self.conn.execute(
+            "UPDATE quiz_sessions SET finished_on = $1 WHERE id = $2 AND finished_on IS NULL",
+            &[&finished_on, &id],
+        )?;
+
+        Ok(updated > 0)
+    }
+}
+
+pub struct PostgresPlayersRepository {
+    pub conn: Connection,
+}
+
+impl PostgresPlayersRepository {
+    pub fn new(pool: Pool<PostgresConnectionManager>) -> PostgresPlayersRepository {
+        return PostgresPlayersRepository {
+            conn: Connection { pool: pool },
+        };
+    }
+}
+
+fn player_from_row(row: &Row) -> Player {
+    Player {
+        id: row.get("id"),
+        username: row.get("username"),
+        created_on: row.get("created_on"),
+    }
+}
+
+impl PlayersRepository for PostgresPlayersRepository {
+    fn save_player(&self, player: &Player, password_hash: &str) -> Result<bool, RepositoryError> {
+        info!("save_player(username: '{:?}').", player.username);
+
+        let inserted = self.conn.execute(
+            "INSERT INTO players (id, username, password_hash, created_on) VALUES ($1, $2, $3, $4) ON CONFLICT (username) DO NOTHING",
+            &[&player.id, &player.username, &password_hash, &player.created_on],
+        )?;
+
+        Ok(inserted > 0)
+    }
+
+    fn get_player(&self, id: &str) -> Result<Option<Player>, RepositoryError> {
+        let rows = &self.conn.query(
+            "SELECT id, username, created_on FROM players WHERE id = $1",
+            &[&id],
+        )?;
+
+        Ok(rows.iter().next().map(|row| player_from_row(&row)))
+    }
+
+    fn get_player_by_username(
+        &self,
+        username: &str,
+    ) -> Result<Option<(Player, String)>, RepositoryError> {
+        let rows = &self.conn.query(
+            "SELECT id, username, created_on, password_hash FROM players WHERE username = $1",
+            &[&username],
+        )?;
+
+        Ok(rows
+            .iter()
+            .next()
+            .map(|row| (player_from_row(&row), row.get("password_hash"))))
+    }
+
+    fn get_or_save_device_player(
+        &self,
+        device_id: &str,
+        player: &Player,
+    ) -> Result<Player, RepositoryError> {
+        self.conn.execute(
+            "INSERT INTO players (id, device_id, created_on) VALUES ($1, $2, $3) ON CONFLICT (device_id) DO NOTHING",
+            &[&player.id, &device_id, &player.created_on],
+        )?;
+
+        let rows = &self.conn.query(
+            "SELECT id, username, created_on FROM players WHERE device_id = $1",
+            &[&device_id],
+        )?;
+        rows.iter()
+            .next()
+            .map(|row| player_from_row(&row))
+            .ok_or(RepositoryError::NotFound(format!(
+                "No player for device '{}'",
+                device_id
+            )))
+    }
+
+    fn merge_player(
+        &self,
+        anonymous_id: &str,
+        into_id: &str,
+    ) -> Result<Option<u64>, RepositoryError> {
+        info!(
+            "merge_player(anonymous_id: '{}', into_id: '{}').",
+            anonymous_id, into_id
+        );
+
+        self.conn.transaction(|trans| {
+            // The sessions are moved first, as they reference the player. A concurrent merge of the same
+            // player waits for their locks, then finds no player to delete and rolls back.
+            let moved = trans.execute(
+                "UPDATE quiz_sessions SET player_id = $1 WHERE player_id = $2",
+                &[&into_id, &anonymous_id],
+            )?;
+            let deleted = trans.execute(
+                "DELETE FROM players WHERE id = $1 AND username IS NULL",
+                &[&anonymous_id],
+            )?;
+            if deleted == 0 {
+                return Ok(None);
+            }
+
+            trans.set_commit();
+            trans.finish()?;
+
+            Ok(Some(moved))
+        })
+    }
+}
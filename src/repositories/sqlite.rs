@@ -0,0 +1,1777 @@
+use chrono::{DateTime, Utc};
+use log::{error, info};
+use models::{
+    Category, Choice, DeletedCategory, DeletedQuestion, Difficulty, FreeTextAnswer, MatchingAnswer,
+    NumericAnswer, OrderingAnswer, Player, Question, QuestionRevision, QuestionType, QuizAnswer,
+    QuizSession, SearchHighlight, TagCount,
+};
+use r2d2::{Pool, PooledConnection};
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, Type, ValueRef};
+use rusqlite::{params_from_iter, OptionalExtension};
+use sampling::SampleKey;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+
+use super::{
+    choices_from_json, choices_to_json, list_to_json, revision_list_from_json, snippet_html,
+    CategoriesRepository, PlayersRepository, QuestionFilter, QuestionsRepository, QuizRepository,
+    RepositoryError, SaveCategoryStatus, SearchFilter, HIGHLIGHT_END, HIGHLIGHT_START,
+};
+
+impl std::convert::From<rusqlite::Error> for RepositoryError {
+    fn from(error: rusqlite::Error) -> Self {
+        match error {
+            rusqlite::Error::SqliteFailure(e, message) => RepositoryError::DatabaseError(
+                format!("{}", e.extended_code),
+                message.unwrap_or_else(|| format!("{}", e)),
+            ),
+            rusqlite::Error::FromSqlConversionFailure(..)
+            | rusqlite::Error::IntegralValueOutOfRange(..)
+            | rusqlite::Error::InvalidColumnType(..)
+            | rusqlite::Error::ToSqlConversionFailure(..) => {
+                RepositoryError::ConversionError(format!("{}", error))
+            }
+            _ => RepositoryError::UnknownError(Some(format!("{}", error))),
+        }
+    }
+}
+
+impl ToSql for Difficulty {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for Difficulty {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let name = value.as_str()?;
+        Difficulty::from_str(name)
+            .ok_or_else(|| FromSqlError::Other(format!("Unknown difficulty '{}'", name).into()))
+    }
+}
+
+impl ToSql for QuestionType {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for QuestionType {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let name = value.as_str()?;
+        QuestionType::from_str(name)
+            .ok_or_else(|| FromSqlError::Other(format!("Unknown question type '{}'", name).into()))
+    }
+}
+
+/// The answers of free-text, ordering, matching and numeric questions are stored as JSON objects.
+macro_rules! json_column {
+    ($answer:ty) => {
+        impl ToSql for $answer {
+            fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+                serde_json::to_string(self)
+                    .map(ToSqlOutput::from)
+                    .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+            }
+        }
+
+        impl FromSql for $answer {
+            fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+                serde_json::from_str(value.as_str()?).map_err(|e| FromSqlError::Other(Box::new(e)))
+            }
+        }
+    };
+}
+
+json_column!(FreeTextAnswer);
+json_column!(OrderingAnswer);
+json_column!(MatchingAnswer);
+json_column!(NumericAnswer);
+
+/// Creates a connection pool for the SQLite database file at `path`.
+/// Foreign keys are off by default in SQLite, so they are enabled on every pooled connection.
+pub fn sqlite_pool(path: &str) -> Result<Pool<SqliteConnectionManager>, RepositoryError> {
+    let manager = SqliteConnectionManager::file(path)
+        .with_init(|c| c.execute_batch("PRAGMA foreign_keys = ON; PRAGMA journal_mode = WAL;"));
+    Ok(Pool::new(manager)?)
+}
+
+pub struct SqliteConnection {
+    pub pool: Pool<SqliteConnectionManager>,
+}
+
+impl SqliteConnection {
+    fn get(&self) -> Result<PooledConnection<SqliteConnectionManager>, RepositoryError> {
+        self.pool.get().map_err(|e| e.into())
+    }
+}
+
+/// Builds `?, ?, ?` for a query with `count` positional parameters.
+fn placeholders(count: usize) -> String {
+    vec!["?"; count].join(",")
+}
+
+/// Loads the choices of the given questions, grouped by question id.
+fn load_choices(
+    conn: &rusqlite::Connection,
+    question_ids: &[i64],
+) -> Result<HashMap<i64, Vec<Choice>>, RepositoryError> {
+    let mut choices_map: HashMap<i64, Vec<Choice>> = HashMap::new();
+    if question_ids.is_empty() {
+        return Ok(choices_map);
+    }
+
+    // SQLite has no array parameters, so the `IN` list is built with one placeholder per id.
+    let query_string = format!(
+        "SELECT id,text,correct,question_id,explanation,media FROM choices WHERE question_id IN ({}) ORDER BY id",
+        placeholders(question_ids.len())
+    );
+    let mut statement = conn.prepare(&query_string)?;
+    let mut rows = statement
+        .query(params_from_iter(question_ids.iter()))
+        .map_err(|e| {
+            error!(
+                "Error loading choices for questions '{:?}': {}",
+                question_ids, e
+            );
+            e
+        })?;
+
+    while let Some(row) = rows.next()? {
+        let choice_id: i64 = row.get(0)?;
+        let question_id: i64 = row.get(3)?;
+
+        choices_map
+            .entry(question_id)
+            .or_insert_with(Vec::new)
+            .push(Choice {
+                id: Some(choice_id as i32),
+                title: row.get(1)?,
+                correct: row.get(2)?,
+                explanation: row.get(4)?,
+                media: list_from_row(row, "media")?,
+            });
+    }
+
+    Ok(choices_map)
+}
+
+/// Loads the tags of the given questions, grouped by question id and sorted like `normalize_tags` sorts them.
+fn load_tags(
+    conn: &rusqlite::Connection,
+    question_ids: &[i64],
+) -> Result<HashMap<i64, Vec<String>>, RepositoryError> {
+    let mut tags_map: HashMap<i64, Vec<String>> = HashMap::new();
+    if question_ids.is_empty() {
+        return Ok(tags_map);
+    }
+
+    let query_string = format!(
+        "SELECT qt.question_id, t.name FROM question_tags qt INNER JOIN tags t ON t.id = qt.tag_id WHERE qt.question_id IN ({}) ORDER BY t.name",
+        placeholders(question_ids.len())
+    );
+    let mut statement = conn.prepare(&query_string)?;
+    let mut rows = statement
+        .query(params_from_iter(question_ids.iter()))
+        .map_err(|e| {
+            error!(
+                "Error loading tags for questions '{:?}': {}",
+                question_ids, e
+            );
+            e
+        })?;
+
+    while let Some(row) = rows.next()? {
+        let question_id: i64 = row.get(0)?;
+        tags_map
+            .entry(question_id)
+            .or_insert_with(Vec::new)
+            .push(row.get(1)?);
+    }
+
+    Ok(tags_map)
+}
+
+/// Replaces the tags of the question, creating the tags that do not exist yet.
+fn save_tags(
+    conn: &rusqlite::Connection,
+    question_id: i64,
+    tags: &[String],
+) -> Result<(), RepositoryError> {
+    conn.execute(
+        "DELETE FROM question_tags WHERE question_id = ?1",
+        params![question_id],
+    )?;
+
+    for tag in tags {
+        conn.execute(
+            "INSERT OR IGNORE INTO tags (name) VALUES (?1)",
+            params![tag],
+        )?;
+        conn.execute(
+            "INSERT INTO question_tags (question_id, tag_id) SELECT ?1, id FROM tags WHERE name = ?2",
+            params![question_id, tag],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Replaces the question's row in the `question_search` index with its text and its saved choices.
+fn update_search_index(
+    conn: &rusqlite::Connection,
+    question_id: i64,
+) -> Result<(), RepositoryError> {
+    conn.execute(
+        "DELETE FROM question_search WHERE rowid = ?1",
+        params![question_id],
+    )?;
+    conn.execute(
+        "INSERT INTO question_search (rowid, text, choices) SELECT q.id, q.text, COALESCE((SELECT group_concat(text, ' / ') FROM (SELECT c.text FROM choices c WHERE c.question_id = q.id ORDER BY c.id)), '') FROM questions q WHERE q.id = ?1",
+        params![question_id],
+    )?;
+    Ok(())
+}
+
+/// Removes the `question_search` rows of questions that were permanently removed.
+fn purge_search_index(conn: &rusqlite::Connection) -> Result<(), RepositoryError> {
+    conn.execute(
+        "DELETE FROM question_search WHERE rowid NOT IN (SELECT id FROM questions)",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Builds an FTS5 query requiring every word of the search text, or `None` if it has no words.
+/// Each word is quoted, so that the FTS5 query syntax in the text is searched for literally.
+fn search_match(text: &str) -> Option<String> {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("\"{}\"", word))
+        .collect();
+
+    if words.is_empty() {
+        None
+    } else {
+        Some(words.join(" "))
+    }
+}
+
+/// Records the state of a saved question as its next revision.
+fn insert_revision(
+    conn: &rusqlite::Connection,
+    question: &Question,
+    author: &str,
+) -> Result<(), RepositoryError> {
+    let question_id = question.id.unwrap_or_default() as i64;
+    let choices = choices_to_json(&question.choices)?;
+    let sources = list_to_json(&question.sources)?;
+    let media = list_to_json(&question.media)?;
+    let tags = list_to_json(&question.tags)?;
+
+    conn.execute(
+        "INSERT INTO question_revisions (question_id, revision, author, created_on, text, category, difficulty, question_type, choices, free_text, ordering, matching, numeric, explanation, sources, media, tags) SELECT ?1, COALESCE(MAX(revision), 0) + 1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16 FROM question_revisions WHERE question_id = ?1",
+        params![
+            question_id,
+            author,
+            Utc::now(),
+            question.question,
+            question.category,
+            question.difficulty,
+            question.question_type,
+            choices,
+            question.free_text,
+            question.ordering,
+            question.matching,
+            question.numeric,
+            question.explanation,
+            sources,
+            media,
+            tags
+        ],
+    )
+    .map_err(|e| {
+        error!(
+            "Insert revision failed for question_id '{}' with reason '{}'.",
+            question_id, e
+        );
+        e
+    })?;
+
+    Ok(())
+}
+
+/// The columns read by `category_from_row`.
+const CATEGORY_COLUMNS: &str = "name, parent, description, icon, colour, display_order, featured";
+
+fn category_from_row(row: &rusqlite::Row) -> rusqlite::Result<Category> {
+    Ok(Category {
+        title: row.get(0)?,
+        parent: row.get(1)?,
+        description: row.get(2)?,
+        icon: row.get(3)?,
+        colour: row.get(4)?,
+        display_order: row.get(5)?,
+        featured: row.get(6)?,
+    })
+}
+
+/// Selects the visible category named (or aliased) `?1` and, if `?2` is true, its visible descendants into `selected`.
+/// Descendants of inactive or deleted categories are not selected.
+const SELECTED_CATEGORIES: &str = "WITH RECURSIVE selected(name) AS (SELECT name FROM categories WHERE name = COALESCE((SELECT category FROM category_aliases WHERE alias = ?1), ?1) AND active = 1 AND deleted_on IS NULL UNION SELECT c.name FROM categories c INNER JOIN selected s ON c.parent = s.name WHERE ?2 AND c.active = 1 AND c.deleted_on IS NULL) ";
+
+/// The questions selected by a `QuestionFilter`, given `SELECTED_CATEGORIES` and JSON arrays of the difficulty names as `?3`, the tags as `?4`
+/// and the question type names as `?5`.
+/// SQLite has no array parameters, so the lists are passed as JSON and expanded with `json_each`.
+/// A question has all the tags if it has as many of them as there are, since the filter's tags are distinct.
+const FILTERED_QUESTIONS: &str = "q.category IN (SELECT name FROM selected) AND q.deleted_on IS NULL AND (json_array_length(?3) = 0 OR q.difficulty IN (SELECT value FROM json_each(?3))) AND (json_array_length(?4) = 0 OR (SELECT COUNT(*) FROM question_tags qt INNER JOIN tags t ON t.id = qt.tag_id WHERE qt.question_id = q.id AND t.name IN (SELECT value FROM json_each(?4))) = json_array_length(?4)) AND (json_array_length(?5) = 0 OR q.question_type IN (SELECT value FROM json_each(?5)))";
+
+/// The questions in an active category that is not deleted, which players can see.
+const VISIBLE_QUESTIONS: &str =
+    "q.category IN (SELECT name FROM categories WHERE active = 1 AND deleted_on IS NULL)";
+
+/// The questions matching a `SearchFilter`, given `SELECTED_CATEGORIES` and `question_search` joined on `q.id`.
+/// Without a category `?1` is null, nothing is selected and every active category is searched instead.
+const SEARCHED_QUESTIONS: &str = "question_search MATCH ?3 AND q.deleted_on IS NULL AND (CASE WHEN ?1 IS NULL THEN q.category IN (SELECT name FROM categories WHERE active = 1 AND deleted_on IS NULL) ELSE q.category IN (SELECT name FROM selected) END)";
+
+fn tags_json(filter: &QuestionFilter) -> Result<String, RepositoryError> {
+    serde_json::to_string(&filter.tags)
+        .map_err(|e| RepositoryError::ConversionError(format!("{}", e)))
+}
+
+fn question_types_json(filter: &QuestionFilter) -> Result<String, RepositoryError> {
+    serde_json::to_string(&filter.question_type_names())
+        .map_err(|e| RepositoryError::ConversionError(format!("{}", e)))
+}
+
+fn difficulties_json(filter: &QuestionFilter) -> Result<String, RepositoryError> {
+    serde_json::to_string(&filter.difficulty_names())
+        .map_err(|e| RepositoryError::ConversionError(format!("{}", e)))
+}
+
+/// Returns the category that `name` is an alias of, or `name` itself if it is not an alias.
+fn resolve_category(conn: &rusqlite::Connection, name: &str) -> Result<String, RepositoryError> {
+    let category: Option<String> = conn
+        .query_row(
+            "SELECT category FROM category_aliases WHERE alias = ?1",
+            params![name],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    Ok(category.unwrap_or_else(|| name.to_string()))
+}
+
+/// The columns read by `question_from_row`, for a query on `questions q`.
+const QUESTION_COLUMNS: &str =
+    "q.id, q.text, q.category, q.difficulty, q.question_type, q.explanation, q.sources, q.media, q.free_text, q.ordering, q.matching, q.numeric";
+
+/// Reads a column holding a JSON array.
+fn list_from_row<T: DeserializeOwned>(
+    row: &rusqlite::Row,
+    column: &str,
+) -> rusqlite::Result<Vec<T>> {
+    let json: String = row.get(column)?;
+    serde_json::from_str(&json).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(
+            row.as_ref().column_index(column).unwrap_or_default(),
+            Type::Text,
+            Box::new(e),
+        )
+    })
+}
+
+/// Maps a row selected with `QUESTION_COLUMNS`. The choices and tags are loaded separately.
+fn question_from_row(row: &rusqlite::Row) -> rusqlite::Result<Question> {
+    let id: i64 = row.get("id")?;
+
+    Ok(Question {
+        id: Some(id as i32),
+        question: row.get("text")?,
+        category: row.get("category")?,
+        difficulty: row.get("difficulty")?,
+        question_type: row.get("question_type")?,
+        choices: vec![],
+        explanation: row.get("explanation")?,
+        sources: list_from_row(row, "sources")?,
+        media: list_from_row(row, "media")?,
+        tags: vec![],
+        free_text: row.get("free_text")?,
+        ordering: row.get("ordering")?,
+        matching: row.get("matching")?,
+        numeric: row.get("numeric")?,
+    })
+}
+
+/// Maps a `question_revisions` row selected as `revision,author,created_on,text,category,choices,difficulty,question_type,free_text,ordering,matching,numeric,explanation,sources,media,tags`.
+fn revision_from_row(row: &rusqlite::Row) -> Result<QuestionRevision, RepositoryError> {
+    let choices: String = row.get(5)?;
+
+    Ok(QuestionRevision {
+        revision: row.get(0)?,
+        author: row.get(1)?,
+        created_on: row.get(2)?,
+        question: row.get(3)?,
+        category: row.get(4)?,
+        difficulty: row.get(6)?,
+        question_type: row.get(7)?,
+        choices: choices_from_json(&choices)?,
+        free_text: row.get(8)?,
+        ordering: row.get(9)?,
+        matching: row.get(10)?,
+        numeric: row.get(11)?,
+        explanation: row.get(12)?,
+        sources: revision_list_from_json(row.get(13)?)?,
+        media: revision_list_from_json(row.get(14)?)?,
+        tags: revision_list_from_json(row.get(15)?)?,
+    })
+}
+
+pub struct SqliteCategoriesRepository {
+    pub conn: SqliteConnection,
+}
+
+impl SqliteCategoriesRepository {
+    pub fn new(pool: Pool<SqliteConnectionManager>) -> SqliteCategoriesRepository {
+        SqliteCategoriesRepository {
+            conn: SqliteConnection { pool: pool },
+        }
+    }
+}
+
+impl CategoriesRepository for SqliteCategoriesRepository {
+    fn save_category_and_set_active(
+        &self,
+        category: &str,
+        active: Option<bool>,
+    ) -> Result<SaveCategoryStatus, RepositoryError> {
+        info!("save_category(category: '{:?}').", category);
+
+        let mut conn = self.conn.get()?;
+        let trans = conn.transaction()?;
+
+        // A new category takes precedence over an alias with the same name.
+        trans.execute(
+            "DELETE FROM category_aliases WHERE alias = ?1",
+            params![category],
+        )?;
+
+        let inserted = match active {
+            Some(active) => trans.execute(
+                "INSERT OR IGNORE INTO categories (name, active) VALUES (?1, ?2)",
+                params![category, active],
+            )?,
+            None => trans.execute(
+                "INSERT OR IGNORE INTO categories (name) VALUES (?1)",
+                params![category],
+            )?,
+        };
+
+        // Postgres reports the row affected by `ON CONFLICT DO UPDATE` as created, so the same is done here.
+        let affected_rows = match (inserted, active) {
+            (0, Some(active)) => trans.execute(
+                "UPDATE categories SET active = ?1 WHERE name = ?2",
+                params![active, category],
+            )?,
+            _ => inserted,
+        };
+
+        trans.commit()?;
+
+        info!(
+            "Inserting category suceeded with affected rows '{:?}'.",
+            affected_rows
+        );
+
+        Ok(match affected_rows {
+            x if x > 0 => SaveCategoryStatus::Created,
+            _ => SaveCategoryStatus::Exists,
+        })
+    }
+
+    fn list_categories(&self) -> Result<Vec<Category>, RepositoryError> {
+        let conn = self.conn.get()?;
+        let mut statement = conn.prepare(&format!(
+            "SELECT {} FROM categories WHERE active = 1 AND deleted_on IS NULL ORDER BY display_order, name",
+            CATEGORY_COLUMNS
+        ))?;
+
+        let categories = statement
+            .query_map([], category_from_row)?
+            .collect::<Result<Vec<Category>, _>>()?;
+
+        Ok(categories)
+    }
+
+    fn get_category(&self, name: &str) -> Result<Option<Category>, RepositoryError> {
+        let category = self
+            .conn
+            .get()?
+            .query_row(
+                &format!(
+                    "SELECT {} FROM categories WHERE name = ?1",
+                    CATEGORY_COLUMNS
+                ),
+                params![name],
+                category_from_row,
+            )
+            .optional()?;
+
+        Ok(category)
+    }
+
+    fn update_category(&self, category: &Category) -> Result<bool, RepositoryError> {
+        info!("update_category(category: '{:?}').", category);
+
+        let affected_rows = self.conn.get()?.execute(
+            "UPDATE categories SET description = ?1, icon = ?2, colour = ?3, display_order = ?4, featured = ?5 WHERE name = ?6",
+            params![
+                category.description,
+                category.icon,
+                category.colour,
+                category.display_order,
+                category.featured,
+                category.title
+            ],
+        )?;
+
+        Ok(affected_rows > 0)
+    }
+
+    fn set_category_parent(
+        &self,
+        name: &str,
+        parent: Option<&str>,
+    ) -> Result<bool, RepositoryError> {
+        let affected_rows = self.conn.get()?.execute(
+            "UPDATE categories SET parent = ?1 WHERE name = ?2",
+            params![parent, name],
+        )?;
+
+        Ok(affected_rows > 0)
+    }
+
+    fn set_category_active(&self, name: &str, active: bool) -> Result<bool, RepositoryError> {
+        let affected_rows = self.conn.get()?.execute(
+            "UPDATE categories SET active = ?1 WHERE name = ?2 AND active != ?1",
+            params![active, name],
+        )?;
+
+        Ok(affected_rows > 0)
+    }
+
+    fn delete_category(&self, name: &str) -> Result<bool, RepositoryError> {
+        let affected_rows = self.conn.get()?.execute(
+            "UPDATE categories SET deleted_on = ?1 WHERE name = ?2 AND deleted_on IS NULL",
+            params![Utc::now(), name],
+        )?;
+
+        Ok(affected_rows > 0)
+    }
+
+    fn restore_category(&self, name: &str) -> Result<bool, RepositoryError> {
+        let affected_rows = self.conn.get()?.execute(
+            "UPDATE categories SET deleted_on = NULL WHERE name = ?1 AND deleted_on IS NOT NULL",
+            params![name],
+        )?;
+
+        Ok(affected_rows > 0)
+    }
+
+    fn list_deleted_categories(&self) -> Result<Vec<DeletedCategory>, RepositoryError> {
+        let conn = self.conn.get()?;
+        let mut statement = conn.prepare(
+            "SELECT name, deleted_on FROM categories WHERE deleted_on IS NOT NULL ORDER BY deleted_on DESC",
+        )?;
+
+        let categories = statement
+            .query_map([], |row| {
+                Ok(DeletedCategory {
+                    title: row.get(0)?,
+                    deleted_on: row.get(1)?,
+                })
+            })?
+            .collect::<Result<Vec<DeletedCategory>, _>>()?;
+
+        Ok(categories)
+    }
+
+    fn category_exists(&self, name: &str) -> Result<bool, RepositoryError> {
+        let exists = self
+            .conn
+            .get()?
+            .query_row(
+                "SELECT 1 FROM categories WHERE name = ?1",
+                params![name],
+                |_| Ok(()),
+            )
+            .optional()?;
+
+        Ok(exists.is_some())
+    }
+
+    fn rename_category(&self, from: &str, to: &str) -> Result<bool, RepositoryError> {
+        info!("rename_category(from: '{}', to: '{}').", from, to);
+
+        let mut conn = self.conn.get()?;
+        let trans = conn.transaction()?;
+
+        trans.execute("DELETE FROM category_aliases WHERE alias = ?1", params![to])?;
+
+        // Questions and existing aliases follow the new name through `ON UPDATE CASCADE`.
+        let renamed = trans.execute(
+            "UPDATE categories SET name = ?1 WHERE name = ?2",
+            params![to, from],
+        )?;
+        if renamed == 0 {
+            return Ok(false);
+        }
+
+        trans.execute(
+            "INSERT INTO category_aliases (alias, category) VALUES (?1, ?2)",
+            params![from, to],
+        )?;
+
+        trans.commit()?;
+
+        Ok(true)
+    }
+
+    fn merge_categories(&self, sources: &[String], target: &str) -> Result<u64, RepositoryError> {
+        info!(
+            "merge_categories(sources: '{:?}', target: '{}').",
+            sources, target
+        );
+
+        let mut conn = self.conn.get()?;
+        let trans = conn.transaction()?;
+        let mut moved = 0;
+
+        for source in sources {
+            moved += trans.execute(
+                "UPDATE questions SET category = ?1 WHERE category = ?2",
+                params![target, source],
+            )?;
+            trans.execute(
+                "UPDATE category_aliases SET category = ?1 WHERE category = ?2",
+                params![target, source],
+            )?;
+            trans.execute(
+                "UPDATE categories SET parent = ?1 WHERE parent = ?2",
+                params![target, source],
+            )?;
+
+            let removed =
+                trans.execute("DELETE FROM categories WHERE name = ?1", params![source])?;
+            if removed == 0 {
+                return Err(RepositoryError::NotFound(format!(
+                    "Category '{}' does not exist",
+                    source
+                )));
+            }
+
+            trans.execute(
+                "INSERT INTO category_aliases (alias, category) VALUES (?1, ?2)",
+                params![source, target],
+            )?;
+        }
+
+        trans.commit()?;
+
+        Ok(moved as u64)
+    }
+
+    fn purge_categories(&self, before: DateTime<Utc>) -> Result<u64, RepositoryError> {
+        info!("purge_categories(before: '{}').", before);
+
+        let mut conn = self.conn.get()?;
+        let trans = conn.transaction()?;
+
+        trans.execute(
+            "DELETE FROM questions WHERE category IN (SELECT name FROM categories WHERE deleted_on < ?1)",
+            params![before],
+        )?;
+        let purged = trans.execute(
+            "DELETE FROM categories WHERE deleted_on < ?1",
+            params![before],
+        )?;
+        purge_search_index(&trans)?;
+
+        trans.commit()?;
+
+        Ok(purged as u64)
+    }
+}
+
+pub struct SqliteQuestionsRepository {
+    pub conn: SqliteConnection,
+}
+
+impl SqliteQuestionsRepository {
+    pub fn new(pool: Pool<SqliteConnectionManager>) -> SqliteQuestionsRepository {
+        SqliteQuestionsRepository {
+            conn: SqliteConnection { pool: pool },
+        }
+    }
+
+    /// Loads a question that is not deleted and, if `visible_only`, whose category is visible.
+    fn load_question(
+        &self,
+        id: i32,
+        visible_only: bool,
+    ) -> Result<Option<Question>, RepositoryError> {
+        let question_id = id as i64;
+        let conn = self.conn.get()?;
+
+        let question = conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM questions q WHERE q.id = ?1 AND q.deleted_on IS NULL AND ({})",
+                    QUESTION_COLUMNS,
+                    if visible_only { VISIBLE_QUESTIONS } else { "1" }
+                ),
+                params![question_id],
+                question_from_row,
+            )
+            .optional()
+            .map_err(|e| {
+                error!("Error loading question '{}': {}", id, e);
+                e
+            })?;
+
+        let mut question = match question {
+            Some(question) => question,
+            None => return Ok(None),
+        };
+
+        let mut choices_map = load_choices(&conn, &[question_id])?;
+        question.choices = choices_map.remove(&question_id).unwrap_or_else(Vec::new);
+        let mut tags_map = load_tags(&conn, &[question_id])?;
+        question.tags = tags_map.remove(&question_id).unwrap_or_else(Vec::new);
+
+        Ok(Some(question))
+    }
+}
+
+impl QuestionsRepository for SqliteQuestionsRepository {
+    fn save_question(
+        &self,
+        question: &Question,
+        author: &str,
+    ) -> Result<Question, RepositoryError> {
+        info!("save_question(question: '{:?}').", question);
+
+        let mut conn = self.conn.get()?;
+        // The transaction is rolled back when it is dropped without being committed.
+        let trans = conn.transaction()?;
+        let category = resolve_category(&trans, &question.category)?;
+
+        trans
+            .execute(
+                "INSERT INTO questions (text, category, difficulty, question_type, free_text, ordering, matching, numeric, explanation, sources, media) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                params![
+                    question.question,
+                    category,
+                    question.difficulty,
+                    question.question_type,
+                    question.free_text,
+                    question.ordering,
+                    question.matching,
+                    question.numeric,
+                    question.explanation,
+                    list_to_json(&question.sources)?,
+                    list_to_json(&question.media)?
+                ],
+            )
+            .map_err(|e| {
+                error!(
+                    "Insert question failed for question: '{:?}', with reason: '{:?}'.",
+                    question, e
+                );
+                e
+            })?;
+        let question_id = trans.last_insert_rowid();
+
+        let mut choices_with_ids: Vec<Choice> = Vec::with_capacity(question.choices.len());
+        {
+            let mut statement = trans
+                .prepare("INSERT INTO choices (question_id, text, correct, explanation, media) VALUES (?1, ?2, ?3, ?4, ?5)")?;
+
+            for choice in question.choices.iter() {
+                statement
+                    .execute(params![
+                        question_id,
+                        choice.title,
+                        choice.correct,
+                        choice.explanation,
+                        list_to_json(&choice.media)?
+                    ])
+                    .map_err(|e| {
+                        error!(
+                            "Insert choice failed for question_id: '{}', reason: {}.",
+                            question_id, e
+                        );
+                        e
+                    })?;
+
+                choices_with_ids.push(Choice {
+                    id: Some(trans.last_insert_rowid() as i32),
+                    ..choice.clone()
+                });
+            }
+        }
+
+        let saved_question = Question {
+            id: Some(question_id as i32),
+            question: question.question.clone(),
+            category: category,
+            difficulty: question.difficulty,
+            question_type: question.question_type,
+            choices: choices_with_ids,
+            explanation: question.explanation.clone(),
+            sources: question.sources.clone(),
+            media: question.media.clone(),
+            tags: question.tags.clone(),
+            free_text: question.free_text.clone(),
+            ordering: question.ordering.clone(),
+            matching: question.matching.clone(),
+            numeric: question.numeric.clone(),
+        };
+
+        save_tags(&trans, question_id, &saved_question.tags)?;
+        update_search_index(&trans, question_id)?;
+        insert_revision(&trans, &saved_question, author)?;
+
+        trans.commit().map_err(|e| {
+            error!(
+                "Finishing insert question failed for question_id '{}' with reason '{}'.",
+                question_id, e
+            );
+            e
+        })?;
+
+        Ok(saved_question)
+    }
+
+    fn get_question(&self, id: i32) -> Result<Option<Question>, RepositoryError> {
+        self.load_question(id, false)
+    }
+
+    fn get_visible_question(&self, id: i32) -> Result<Option<Question>, RepositoryError> {
+        self.load_question(id, true)
+    }
+
+    fn update_question(
+        &self,
+        question: &Question,
+        author: &str,
+    ) -> Result<Question, RepositoryError> {
+        info!("update_question(question: '{:?}').", question);
+
+        let question_id = question.id.ok_or(RepositoryError::UnknownError(Some(
+            "Question to update has no id".into(),
+        )))? as i64;
+
+        let mut conn = self.conn.get()?;
+        // The transaction is rolled back when it is dropped without being committed.
+        let trans = conn.transaction()?;
+        let category = resolve_category(&trans, &question.category)?;
+
+        let updated_rows = trans
+            .execute(
+                "UPDATE questions SET text = ?1, category = ?2, difficulty = ?3, question_type = ?4, free_text = ?5, ordering = ?6, matching = ?7, numeric = ?8, explanation = ?9, sources = ?10, media = ?11 WHERE id = ?12 AND deleted_on IS NULL",
+                params![
+                    question.question,
+                    category,
+                    question.difficulty,
+                    question.question_type,
+                    question.free_text,
+                    question.ordering,
+                    question.matching,
+                    question.numeric,
+                    question.explanation,
+                    list_to_json(&question.sources)?,
+                    list_to_json(&question.media)?,
+                    question_id
+                ],
+            )
+            .map_err(|e| {
+                error!(
+                    "Update question failed for question: '{:?}', with reason: '{:?}'.",
+                    question, e
+                );
+                e
+            })?;
+
+        if updated_rows == 0 {
+            return Err(RepositoryError::NotFound(format!(
+                "Question {} does not exist",
+                question_id
+            )));
+        }
+
+        // Choices that are not part of the updated question are removed.
+        let kept_ids: Vec<i64> = question
+            .choices
+            .iter()
+            .filter_map(|choice| choice.id.map(|id| id as i64))
+            .collect();
+        {
+            let query_string = format!(
+                "DELETE FROM choices WHERE question_id = ? AND id NOT IN ({})",
+                placeholders(kept_ids.len())
+            );
+            let values = Some(question_id)
+                .into_iter()
+                .chain(kept_ids.iter().cloned());
+
+            trans.execute(&query_string, params_from_iter(values))?;
+        }
+
+        let mut choices_with_ids: Vec<Choice> = Vec::with_capacity(question.choices.len());
+        for choice in question.choices.iter() {
+            let media = list_to_json(&choice.media)?;
+            let choice_id: i64 = match choice.id {
+                Some(id) => {
+                    let choice_id = id as i64;
+                    let updated_rows = trans.execute(
+                        "UPDATE choices SET text = ?1, correct = ?2, explanation = ?3, media = ?4 WHERE id = ?5 AND question_id = ?6",
+                        params![
+                            choice.title,
+                            choice.correct,
+                            choice.explanation,
+                            media,
+                            choice_id,
+                            question_id
+                        ],
+                    )?;
+
+                    if updated_rows == 0 {
+                        return Err(RepositoryError::NotFound(format!(
+                            "Choice {} does not belong to question {}",
+                            choice_id, question_id
+                        )));
+                    }
+                    choice_id
+                }
+                None => {
+                    trans.execute(
+                        "INSERT INTO choices (question_id, text, correct, explanation, media) VALUES (?1, ?2, ?3, ?4, ?5)",
+                        params![
+                            question_id,
+                            choice.title,
+                            choice.correct,
+                            choice.explanation,
+                            media
+                        ],
+                    )?;
+                    trans.last_insert_rowid()
+                }
+            };
+
+            choices_with_ids.push(Choice {
+                id: Some(choice_id as i32),
+                ..choice.clone()
+            });
+        }
+
+        let updated_question = Question {
+            id: question.id,
+            question: question.question.clone(),
+            category: category,
+            difficulty: question.difficulty,
+            question_type: question.question_type,
+            choices: choices_with_ids,
+            explanation: question.explanation.clone(),
+            sources: question.sources.clone(),
+            media: question.media.clone(),
+            tags: question.tags.clone(),
+            free_text: question.free_text.clone(),
+            ordering: question.ordering.clone(),
+            matching: question.matching.clone(),
+            numeric: question.numeric.clone(),
+        };
+
+        save_tags(&trans, question_id, &updated_question.tags)?;
+        update_search_index(&trans, question_id)?;
+        insert_revision(&trans, &updated_question, author)?;
+
+        trans.commit().map_err(|e| {
+            error!(
+                "Finishing update question failed for question_id '{}' with reason '{}'.",
+                question_id, e
+            );
+            e
+        })?;
+
+        Ok(updated_question)
+    }
+
+    fn list_revisions(&self, question_id: i32) -> Result<Vec<QuestionRevision>, RepositoryError> {
+        let conn = self.conn.get()?;
+        let mut statement = conn.prepare(
+            "SELECT revision,author,created_on,text,category,choices,difficulty,question_type,free_text,ordering,matching,numeric,explanation,sources,media,tags FROM question_revisions WHERE question_id = ?1 ORDER BY revision",
+        )?;
+
+        let mut revisions: Vec<QuestionRevision> = vec![];
+        let mut rows = statement.query(params![question_id])?;
+        while let Some(row) = rows.next()? {
+            revisions.push(revision_from_row(row)?);
+        }
+
+        Ok(revisions)
+    }
+
+    fn get_revision(
+        &self,
+        question_id: i32,
+        revision: i32,
+    ) -> Result<Option<QuestionRevision>, RepositoryError> {
+        let conn = self.conn.get()?;
+        let mut statement = conn.prepare(
+            "SELECT revision,author,created_on,text,category,choices,difficulty,question_type,free_text,ordering,matching,numeric,explanation,sources,media,tags FROM question_revisions WHERE question_id = ?1 AND revision = ?2",
+        )?;
+
+        let mut rows = statement.query(params![question_id, revision])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(revision_from_row(row)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn count_questions(&self, filter: &QuestionFilter) -> Result<i64, RepositoryError> {
+        let count: i64 = self
+            .conn
+            .get()?
+            .query_row(
+                &format!(
+                    "{}SELECT COUNT(q.id) FROM questions q WHERE {}",
+                    SELECTED_CATEGORIES, FILTERED_QUESTIONS
+                ),
+                params![
+                    filter.category,
+                    filter.include_descendants,
+                    difficulties_json(filter)?,
+                    tags_json(filter)?,
+                    question_types_json(filter)?
+                ],
+                |row| row.get(0),
+            )
+            .map_err(|e| {
+                error!("Error counting questions for filter '{:?}': {}", filter, e);
+                e
+            })?;
+
+        Ok(count)
+    }
+
+    fn get_questions(
+        &self,
+        filter: &QuestionFilter,
+        page: i64,
+        size: i64,
+    ) -> Result<Vec<Question>, RepositoryError> {
+        let offset = match page {
+            0 => 0i64,
+            _ => (page - 1i64) * size,
+        };
+
+        let conn = self.conn.get()?;
+
+        let questions: Vec<Question> = {
+            let mut statement = conn.prepare(&format!(
+                "{}SELECT {} FROM questions q WHERE {} ORDER BY q.id LIMIT ?6 OFFSET ?7",
+                SELECTED_CATEGORIES, QUESTION_COLUMNS, FILTERED_QUESTIONS
+            ))?;
+            let rows = statement
+                .query_map(
+                    params![
+                        filter.category,
+                        filter.include_descendants,
+                        difficulties_json(filter)?,
+                        tags_json(filter)?,
+                        question_types_json(filter)?,
+                        size,
+                        offset
+                    ],
+                    question_from_row,
+                )?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| {
+                    error!("Error loading questions for filter '{:?}': {}", filter, e);
+                    e
+                })?;
+            rows
+        };
+
+        if questions.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let question_ids: Vec<i64> = questions
+            .iter()
+            .map(|question| question.id.unwrap_or_default() as i64)
+            .collect();
+
+        let mut choices_map = load_choices(&conn, &question_ids)?;
+        let mut tags_map = load_tags(&conn, &question_ids)?;
+
+        Ok(questions
+            .into_iter()
+            .map(|mut question| {
+                let id = question.id.unwrap_or_default() as i64;
+                question.choices = choices_map.remove(&id).unwrap_or_else(Vec::new);
+                question.tags = tags_map.remove(&id).unwrap_or_else(Vec::new);
+                question
+            })
+            .collect())
+    }
+
+    fn sample_question_ids(
+        &self,
+        filter: &QuestionFilter,
+        key: &SampleKey,
+        exclude_ids: &[i32],
+        count: i64,
+    ) -> Result<Vec<i32>, RepositoryError> {
+        let exclude_ids = serde_json::to_string(exclude_ids)
+            .map_err(|e| RepositoryError::ConversionError(format!("{}", e)))?;
+
+        // `SampleKey::key`, one step per subquery. Every step is taken modulo 2^31 - 1, so no product overflows an INTEGER.
+        let conn = self.conn.get()?;
+        let mut statement = conn.prepare(&format!(
+            "{}SELECT id FROM (SELECT id, (y * y + ?9) % 2147483647 AS sample_key FROM (SELECT id, (x * x + ?8) % 2147483647 AS y FROM (SELECT q.id, (q.id % 2147483647 * ?6 + ?7) % 2147483647 AS x FROM questions q WHERE {} AND q.id NOT IN (SELECT value FROM json_each(?10))))) ORDER BY sample_key, id LIMIT ?11",
+            SELECTED_CATEGORIES, FILTERED_QUESTIONS
+        ))?;
+
+        let ids = statement
+            .query_map(
+                params![
+                    filter.category,
+                    filter.include_descendants,
+                    difficulties_json(filter)?,
+                    tags_json(filter)?,
+                    question_types_json(filter)?,
+                    key.multiplier,
+                    key.offset,
+                    key.first_round,
+                    key.second_round,
+                    exclude_ids,
+                    count
+                ],
+                |row| row.get(0),
+            )?
+            .collect::<Result<Vec<i32>, _>>()
+            .map_err(|e| {
+                error!(
+                    "Error sampling question ids for filter '{:?}': {}",
+                    filter, e
+                );
+                e
+            })?;
+
+        Ok(ids)
+    }
+
+    fn get_questions_by_ids(&self, ids: &[i32]) -> Result<Vec<Question>, RepositoryError> {
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let conn = self.conn.get()?;
+
+        let questions: Vec<Question> = {
+            let mut statement = conn.prepare(&format!(
+                "SELECT {} FROM questions q WHERE q.id IN ({}) AND q.deleted_on IS NULL ORDER BY q.id",
+                QUESTION_COLUMNS,
+                placeholders(ids.len())
+            ))?;
+            let rows = statement
+                .query_map(params_from_iter(ids.iter()), question_from_row)?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| {
+                    error!("Error loading questions '{:?}': {}", ids, e);
+                    e
+                })?;
+            rows
+        };
+
+        let question_ids: Vec<i64> = questions
+            .iter()
+            .map(|question| question.id.unwrap_or_default() as i64)
+            .collect();
+        let mut choices_map = load_choices(&conn, &question_ids)?;
+        let mut tags_map = load_tags(&conn, &question_ids)?;
+
+        Ok(questions
+            .into_iter()
+            .map(|mut question| {
+                let id = question.id.unwrap_or_default() as i64;
+                question.choices = choices_map.remove(&id).unwrap_or_else(Vec::new);
+                question.tags = tags_map.remove(&id).unwrap_or_else(Vec::new);
+                question
+            })
+            .collect())
+    }
+
+    fn list_tags(&self) -> Result<Vec<TagCount>, RepositoryError> {
+        let conn = self.conn.get()?;
+        let mut statement = conn.prepare(
+            "SELECT t.name, COUNT(*) FROM tags t INNER JOIN question_tags qt ON qt.tag_id = t.id INNER JOIN questions q ON q.id = qt.question_id INNER JOIN categories c ON c.name = q.category WHERE q.deleted_on IS NULL AND c.active = 1 AND c.deleted_on IS NULL GROUP BY t.name ORDER BY COUNT(*) DESC, t.name",
+        )?;
+
+        let tags = statement
+            .query_map([], |row| {
+                Ok(TagCount {
+                    name: row.get(0)?,
+                    count: row.get(1)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(tags)
+    }
+
+    fn count_search_results(&self, search: &SearchFilter) -> Result<i64, RepositoryError> {
+        let search_match = match search_match(&search.text) {
+            Some(search_match) => search_match,
+            None => return Ok(0),
+        };
+
+        let count: i64 = self
+            .conn
+            .get()?
+            .query_row(
+                &format!(
+                    "{}SELECT COUNT(q.id) FROM question_search INNER JOIN questions q ON q.id = question_search.rowid WHERE {}",
+                    SELECTED_CATEGORIES, SEARCHED_QUESTIONS
+                ),
+                params![search.category, true, search_match],
+                |row| row.get(0),
+            )
+            .map_err(|e| {
+                error!("Error counting questions for search '{:?}': {}", search, e);
+                e
+            })?;
+
+        Ok(count)
+    }
+
+    fn search_questions(
+        &self,
+        search: &SearchFilter,
+        page: i64,
+        size: i64,
+    ) -> Result<Vec<(Question, SearchHighlight)>, RepositoryError> {
+        let search_match = match search_match(&search.text) {
+            Some(search_match) => search_match,
+            None => return Ok(vec![]),
+        };
+
+        let offset = match page {
+            0 => 0i64,
+            _ => (page - 1i64) * size,
+        };
+
+        let conn = self.conn.get()?;
+
+        // bm25 scores better matches lower, and a match in the question text counts twice as much as one in the choices.
+        let results: Vec<(Question, SearchHighlight)> = {
+            let mut statement = conn.prepare(&format!(
+                "{}SELECT {}, -bm25(question_search, 2.0, 1.0) AS search_rank, snippet(question_search, -1, ?4, ?5, '…', 16) AS snippet FROM question_search INNER JOIN questions q ON q.id = question_search.rowid WHERE {} ORDER BY search_rank DESC, q.id LIMIT ?6 OFFSET ?7",
+                SELECTED_CATEGORIES, QUESTION_COLUMNS, SEARCHED_QUESTIONS
+            ))?;
+            let rows = statement
+                .query_map(
+                    params![
+                        search.category,
+                        true,
+                        search_match,
+                        HIGHLIGHT_START,
+                        HIGHLIGHT_END,
+                        size,
+                        offset
+                    ],
+                    |row| {
+                        let question = question_from_row(row)?;
+                        let highlight = SearchHighlight {
+                            question_id: question.id.unwrap_or_default(),
+                            rank: row.get("search_rank")?,
+                            snippet: snippet_html(&row.get::<_, String>("snippet")?),
+                        };
+                        Ok((question, highlight))
+                    },
+                )?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| {
+                    error!("Error searching questions for search '{:?}': {}", search, e);
+                    e
+                })?;
+            rows
+        };
+
+        let question_ids: Vec<i64> = results
+            .iter()
+            .map(|(question, _)| question.id.unwrap_or_default() as i64)
+            .collect();
+        let mut choices_map = load_choices(&conn, &question_ids)?;
+        let mut tags_map = load_tags(&conn, &question_ids)?;
+
+        Ok(results
+            .into_iter()
+            .map(|(mut question, highlight)| {
+                let id = question.id.unwrap_or_default() as i64;
+                question.choices = choices_map.remove(&id).unwrap_or_else(Vec::new);
+                question.tags = tags_map.remove(&id).unwrap_or_else(Vec::new);
+                (question, highlight)
+            })
+            .collect())
+    }
+
+    fn delete_question(&self, id: i32) -> Result<bool, RepositoryError> {
+        let affected_rows = self.conn.get()?.execute(
+            "UPDATE questions SET deleted_on = ?1 WHERE id = ?2 AND deleted_on IS NULL",
+            params![Utc::now(), id],
+        )?;
+
+        Ok(affected_rows > 0)
+    }
+
+    fn restore_question(&self, id: i32) -> Result<bool, RepositoryError> {
+        let affected_rows = self.conn.get()?.execute(
+            "UPDATE questions SET deleted_on = NULL WHERE id = ?1 AND deleted_on IS NOT NULL",
+            params![id],
+        )?;
+
+        Ok(affected_rows > 0)
+    }
+
+    fn list_deleted_questions(&self) -> Result<Vec<DeletedQuestion>, RepositoryError> {
+        let conn = self.conn.get()?;
+
+        let deleted: Vec<DeletedQuestion> = {
+            let mut statement = conn.prepare(&format!(
+                "SELECT {}, q.deleted_on FROM questions q WHERE q.deleted_on IS NOT NULL ORDER BY q.deleted_on DESC",
+                QUESTION_COLUMNS
+            ))?;
+            let rows = statement
+                .query_map([], |row| {
+                    Ok(DeletedQuestion {
+                        question: question_from_row(row)?,
+                        deleted_on: row.get("deleted_on")?,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            rows
+        };
+
+        let question_ids: Vec<i64> = deleted
+            .iter()
+            .map(|d| d.question.id.unwrap_or_default() as i64)
+            .collect();
+        let mut choices_map = load_choices(&conn, &question_ids)?;
+        let mut tags_map = load_tags(&conn, &question_ids)?;
+
+        Ok(deleted
+            .into_iter()
+            .map(|mut d| {
+                let id = d.question.id.unwrap_or_default() as i64;
+                d.question.choices = choices_map.remove(&id).unwrap_or_else(Vec::new);
+                d.question.tags = tags_map.remove(&id).unwrap_or_else(Vec::new);
+                d
+            })
+            .collect())
+    }
+
+    fn purge_questions(&self, before: DateTime<Utc>) -> Result<u64, RepositoryError> {
+        info!("purge_questions(before: '{}').", before);
+
+        let mut conn = self.conn.get()?;
+        let trans = conn.transaction()?;
+
+        // Choices are removed by `ON DELETE CASCADE`, but the search index is a virtual table without foreign keys.
+        let purged = trans.execute(
+            "DELETE FROM questions WHERE deleted_on < ?1",
+            params![before],
+        )?;
+        purge_search_index(&trans)?;
+
+        trans.commit()?;
+
+        Ok(purged as u64)
+    }
+}
+
+pub struct SqliteQuizRepository {
+    pub conn: SqliteConnection,
+}
+
+impl SqliteQuizRepository {
+    pub fn new(pool: Pool<SqliteConnectionManager>) -> SqliteQuizRepository {
+        SqliteQuizRepository {
+            conn: SqliteConnection { pool: pool },
+        }
+    }
+}
+
+impl QuizRepository for SqliteQuizRepository {
+    fn save_session(&self, session: &QuizSession) -> Result<(), RepositoryError> {
+        info!("save_session(session: '{:?}').", session);
+
+        self.conn
+            .get()?
+            .execute(
+                "INSERT INTO quiz_sessions (id, category, player_id, question_ids, started_on) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    session.id,
+                    session.category,
+                    session.player_id,
+                    list_to_json(&session.question_ids)?,
+                    session.started_on
+                ],
+            )
+            .map_err(|e| {
+                error!("Insert quiz session failed for session '{}': {}", session.id, e);
+                e
+            })?;
+
+        Ok(())
+    }
+
+    fn get_session(&self, id: &str) -> Result<Option<QuizSession>, RepositoryError> {
+        let conn = self.conn.get()?;
+
+        let session = conn
+            .query_row(
+                "SELECT id, category, player_id, question_ids, started_on, finished_on FROM quiz_sessions WHERE id = ?1",
+                params![id],
+                |row| {
+                    Ok(QuizSession {
+                        id: row.get("id")?,
+                        category: row.get("category")?,
+                        player_id: row.get("player_id")?,
+                        question_ids: list_from_row(row, "question_ids")?,
+                        answers: vec![],
+                        started_on: row.get("started_on")?,
+                        finished_on: row.get("finished_on")?,
+                    })
+                },
+            )
+            .optional()?;
+
+        let mut session = match session {
+            Some(session) => session,
+            None => return Ok(None),
+        };
+
+        let mut statement = conn.prepare(
+            "SELECT question_id, choice_id, correct, answered_on FROM quiz_answers WHERE session_id = ?1 ORDER BY answered_on, question_id",
+        )?;
+        session.answers = statement
+            .query_map(params![id], |row| {
+                Ok(QuizAnswer {
+                    question_id: row.get(0)?,
+                    choice_id: row.get(1)?,
+                    correct: row.get(2)?,
+                    answered_on: row.get(3)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Some(session))
+    }
+
+    fn list_sessions(&self, player_id: &str) -> Result<Vec<QuizSession>, RepositoryError> {
+        let ids = {
+            let conn = self.conn.get()?;
+            let mut statement = conn.prepare(
+                "SELECT id FROM quiz_sessions WHERE player_id = ?1 ORDER BY started_on DESC, id",
+            )?;
+            let ids = statement
+                .query_map(params![player_id], |row| row.get::<_, String>(0))?
+                .collect::<Result<Vec<_>, _>>()?;
+            ids
+        };
+
+        let mut sessions = vec![];
+        for id in ids {
+            sessions.extend(self.get_session(&id)?);
+        }
+        Ok(sessions)
+    }
+
+    fn save_answer(&self, session_id: &str, answer: &QuizAnswer) -> Result<bool, RepositoryError> {
+        // Inserted through the session, so that nothing is inserted once it is finished.
+        let inserted = self.conn.get()?.execute(
+            "INSERT OR IGNORE INTO quiz_answers (session_id, question_id, choice_id, correct, answered_on) SELECT id, ?2, ?3, ?4, ?5 FROM quiz_sessions WHERE id = ?1 AND finished_on IS NULL",
+            params![
+                session_id,
+                answer.question_id,
+                answer.choice_id,
+                answer.correct,
+                answer.answered_on
+            ],
+        )?;
+
+        Ok(inserted > 0)
+    }
+
+    fn finish_session(
+        &self,
+        id: &str,
+        finished_on: DateTime<Utc>,
+    ) -> Result<bool, RepositoryError> {
+        let updated = self.conn.get()?.execute(
+            "UPDATE quiz_sessions SET finished_on = ?1 WHERE id = ?2 AND finished_on IS NULL",
+            params![finished_on, id],
+        )?;
+
+        Ok(updated > 0)
+    }
+}
+
+pub struct SqlitePlayersRepository {
+    pub conn: SqliteConnection,
+}
+
+impl SqlitePlayersRepository {
+    pub fn new(pool: Pool<SqliteConnectionManager>) -> SqlitePlayersRepository {
+        SqlitePlayersRepository {
+            conn: SqliteConnection { pool: pool },
+        }
+    }
+}
+
+fn player_from_row(row: &rusqlite::Row) -> rusqlite::Result<Player> {
+    Ok(Player {
+        id: row.get("id")?,
+        username: row.get("username")?,
+        created_on: row.get("created_on")?,
+    })
+}
+
+impl PlayersRepository for SqlitePlayersRepository {
+    fn save_player(&self, player: &Player, password_hash: &str) -> Result<bool, RepositoryError> {
+        info!("save_player(username: '{:?}').", player.username);
+
+        let inserted = self.conn.get()?.execute(
+            "INSERT INTO players (id, username, password_hash, created_on) VALUES (?1, ?2, ?3, ?4) ON CONFLICT (username) DO NOTHING",
+            params![player.id, player.username, password_hash, player.created_on],
+        )?;
+
+        Ok(inserted > 0)
+    }
+
+    fn get_player(&self, id: &str) -> Result<Option<Player>, RepositoryError> {
+        Ok(self
+            .conn
+            .get()?
+            .query_row(
+                "SELECT id, username, created_on FROM players WHERE id = ?1",
+                params![id],
+                player_from_row,
+            )
+            .optional()?)
+    }
+
+    fn get_player_by_username(
+        &self,
+        username: &str,
+    ) -> Result<Option<(Player, String)>, RepositoryError> {
+        Ok(self
+            .conn
+            .get()?
+            .query_row(
+                "SELECT id, username, created_on, password_hash FROM players WHERE username = ?1",
+                params![username],
+                |row| Ok((player_from_row(row)?, row.get("password_hash")?)),
+            )
+            .optional()?)
+    }
+
+    fn get_or_save_device_player(
+        &self,
+        device_id: &str,
+        player: &Player,
+    ) -> Result<Player, RepositoryError> {
+        let conn = self.conn.get()?;
+
+        conn.execute(
+            "INSERT INTO players (id, device_id, created_on) VALUES (?1, ?2, ?3) ON CONFLICT (device_id) DO NOTHING",
+            params![player.id, device_id, player.created_on],
+        )?;
+
+        Ok(conn.query_row(
+            "SELECT id, username, created_on FROM players WHERE device_id = ?1",
+            params![device_id],
+            player_from_row,
+        )?)
+    }
+
+    fn merge_player(
+        &self,
+        anonymous_id: &str,
+        into_id: &str,
+    ) -> Result<Option<u64>, RepositoryError> {
+        info!(
+            "merge_player(anonymous_id: '{}', into_id: '{}').",
+            anonymous_id, into_id
+        );
+
+        let mut conn = self.conn.get()?;
+        let trans = conn.transaction()?;
+
+        // The sessions are moved first, as they reference the player. Nothing is committed if there is no
+        // anonymous player to delete.
+        let moved = trans.execute(
+            "UPDATE quiz_sessions SET player_id = ?1 WHERE player_id = ?2",
+            params![into_id, anonymous_id],
+        )?;
+        let deleted = trans.execute(
+            "DELETE FROM players WHERE id = ?1 AND username IS NULL",
+            params![anonymous_id],
+        )?;
+        if deleted == 0 {
+            return Ok(None);
+        }
+
+        trans.commit()?;
+
+        Ok(Some(moved as u64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use migrations::Migrator;
+    use models::{Difficulty, QuestionType, Source};
+
+    fn pool() -> Pool<SqliteConnectionManager> {
+        // A single connection, as every connection to `:memory:` opens a database of its own.
+        let manager = SqliteConnectionManager::memory()
+            .with_init(|c| c.execute_batch("PRAGMA foreign_keys = ON;"));
+        let pool = Pool::builder().max_size(1).build(manager).unwrap();
+        Migrator::sqlite(pool.clone()).up().unwrap();
+        pool
+    }
+
+    fn repositories() -> (SqliteCategoriesRepository, SqliteQuestionsRepository) {
+        let pool = pool();
+        (
+            SqliteCategoriesRepository::new(pool.clone()),
+            SqliteQuestionsRepository::new(pool),
+        )
+    }
+
+    fn choice(title: &str, correct: bool) -> Choice {
+        Choice {
+            id: None,
+            title: title.to_string(),
+            correct: correct,
+            explanation: None,
+            media: vec![],
+        }
+    }
+
+    #[test]
+    fn search_snippets_are_escaped_html() {
+        let (categories, questions) = repositories();
+        categories
+            .save_category_and_set_active("web", Some(true))
+            .unwrap();
+        let question = Question {
+            id: None,
+            question: "Which <script> tag & attribute load code?".to_string(),
+            category: "web".to_string(),
+            difficulty: Difficulty::Medium,
+            question_type: QuestionType::SingleChoice,
+            choices: vec![
+                choice("<script src>", true),
+                choice("<img onerror='x'>", false),
+            ],
+            explanation: None,
+            sources: vec![],
+            media: vec![],
+            tags: vec![],
+            free_text: None,
+            ordering: None,
+            matching: None,
+            numeric: None,
+        };
+        questions.save_question(&question, "admin").unwrap();
+
+        let search = SearchFilter {
+            text: "script".to_string(),
+            category: None,
+        };
+        let results = questions.search_questions(&search, 1, 10).unwrap();
+        assert_eq!(results.len(), 1);
+        let snippet = &results[0].1.snippet;
+        assert!(snippet.contains("&lt;<mark>script</mark>&gt; tag &amp; attribute"));
+        assert!(!snippet.contains("<script"));
+        assert!(!snippet.contains("<img"));
+        assert!(!snippet.contains(HIGHLIGHT_START) && !snippet.contains(HIGHLIGHT_END));
+    }
+
+    #[test]
+    fn reverting_to_an_old_revision_keeps_what_it_did_not_record() {
+        let (categories, questions) = repositories();
+        categories
+            .save_category_and_set_active("space", Some(true))
+            .unwrap();
+        let mut question = Question {
+            id: None,
+            question: "Which planet is the largest?".to_string(),
+            category: "space".to_string(),
+            difficulty: Difficulty::Medium,
+            question_type: QuestionType::SingleChoice,
+            choices: vec![choice("Jupiter", true), choice("Mars", false)],
+            explanation: Some(
+                "Jupiter is more than twice as massive as the other planets combined.".into(),
+            ),
+            sources: vec![Source {
+                url: "https://science.nasa.gov/jupiter/".to_string(),
+                title: None,
+            }],
+            media: vec![],
+            tags: vec!["planets".to_string()],
+            free_text: None,
+            ordering: None,
+            matching: None,
+            numeric: None,
+        };
+        question = questions.save_question(&question, "admin").unwrap();
+        let id = question.id.unwrap();
+        question.question = "Which planet is the biggest?".to_string();
+        let current = questions.update_question(&question, "admin").unwrap();
+
+        // Revisions recorded before questions had explanations, sources, media and tags have NULL in those columns.
+        questions
+            .conn
+            .get()
+            .unwrap()
+            .execute(
+                "UPDATE question_revisions SET explanation = NULL, sources = NULL, media = NULL, tags = NULL WHERE question_id = ?1 AND revision = 1",
+                params![id],
+            )
+            .unwrap();
+        let revision = questions.get_revision(id, 1).unwrap().unwrap();
+        assert!(revision.sources.is_none() && revision.media.is_none() && revision.tags.is_none());
+
+        let reverted = questions
+            .update_question(&Question::from_revision(&current, revision), "admin")
+            .unwrap();
+        assert_eq!(reverted.question, "Which planet is the largest?");
+        assert_eq!(reverted.explanation, current.explanation);
+        assert_eq!(reverted.sources.len(), 1);
+        assert_eq!(reverted.tags, vec!["planets"]);
+
+        let stored = questions.get_question(id).unwrap().unwrap();
+        assert_eq!(stored.explanation, current.explanation);
+        assert_eq!(stored.sources[0].url, "https://science.nasa.gov/jupiter/");
+        assert_eq!(stored.tags, vec!["planets"]);
+    }
+}
@@ -0,0 +1,207 @@
+use uuid::Uuid;
+
+/// The SplitMix64 generator. It is tiny and fully specified, so a seed picks the same questions
+/// on every platform and release, which a general purpose random number crate does not promise.
+pub struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> SplitMix64 {
+        SplitMix64 { state: seed }
+    }
+
+    /// A generator seeded with fresh randomness, for shuffles that players must not be able to repeat.
+    pub fn unseeded() -> SplitMix64 {
+        let uuid = Uuid::new_v4();
+        let mut seed = 0u64;
+        for byte in uuid.as_bytes()[..8].iter() {
+            seed = seed << 8 | u64::from(*byte);
+        }
+        SplitMix64::new(seed)
+    }
+
+    /// A generator for one of many independent streams derived from the same seed, e.g. one per question.
+    pub fn stream(seed: u64, stream: u64) -> SplitMix64 {
+        let mut mixer = SplitMix64::new(stream);
+        SplitMix64::new(seed ^ mixer.next_u64())
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..bound`, using Lemire's multiply-and-shift instead of a biased modulo.
+    pub fn below(&mut self, bound: usize) -> usize {
+        ((self.next_u64() as u128 * bound as u128) >> 64) as usize
+    }
+
+    /// Moves a random selection of `count` items to the front of `items`, in random order, and drops the rest.
+    /// Only the selected positions are shuffled, so sampling a few items from many is cheap.
+    pub fn sample<T>(&mut self, items: &mut Vec<T>, count: usize) {
+        let count = count.min(items.len());
+        for i in 0..count {
+            let j = i + self.below(items.len() - i);
+            items.swap(i, j);
+        }
+        items.truncate(count);
+    }
+
+    pub fn shuffle<T>(&mut self, items: &mut Vec<T>) {
+        let count = items.len();
+        self.sample(items, count);
+    }
+}
+
+/// The prime 2^31 - 1. Sample keys are computed modulo it, so that every product fits in the 64-bit integers
+/// that Postgres, SQLite and Rust share.
+pub const SAMPLE_MODULUS: i64 = 2_147_483_647;
+
+/// Orders questions by a key derived from their id and a seed, so that the database can pick a seeded sample with
+/// `ORDER BY key, id LIMIT count` rather than return every id. The key is an affine map of the id followed by two
+/// rounds of squaring, all modulo `SAMPLE_MODULUS`; the SQL of each storage computes it exactly like `key`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampleKey {
+    pub multiplier: i64,
+    pub offset: i64,
+    pub first_round: i64,
+    pub second_round: i64,
+}
+
+impl SampleKey {
+    pub fn new(seed: u64) -> SampleKey {
+        let mut rng = SplitMix64::new(seed);
+        let modulus = SAMPLE_MODULUS as usize;
+        SampleKey {
+            // Never zero, so that the affine map gives every id its own value.
+            multiplier: 1 + rng.below(modulus - 1) as i64,
+            offset: rng.below(modulus) as i64,
+            first_round: rng.below(modulus) as i64,
+            second_round: rng.below(modulus) as i64,
+        }
+    }
+
+    pub fn key(&self, id: i64) -> i64 {
+        let x = (id % SAMPLE_MODULUS * self.multiplier + self.offset) % SAMPLE_MODULUS;
+        let y = (x * x + self.first_round) % SAMPLE_MODULUS;
+        (y * y + self.second_round) % SAMPLE_MODULUS
+    }
+}
+
+/// A seed for callers that did not choose one. It is returned to them so that they can repeat the sample.
+pub fn random_seed() -> i32 {
+    let uuid = Uuid::new_v4();
+    let bytes = uuid.as_bytes();
+    // The sign bit is cleared so that seeds are never negative.
+    i32::from(bytes[0] & 0x7F) << 24
+        | i32::from(bytes[1]) << 16
+        | i32::from(bytes[2]) << 8
+        | i32::from(bytes[3])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn letters(text: &str) -> Vec<char> {
+        text.chars().collect()
+    }
+
+    #[test]
+    fn split_mix_64_matches_the_reference_outputs() {
+        let mut rng = SplitMix64::new(0);
+        assert_eq!(rng.next_u64(), 0xE220_A839_7B1D_CDAF);
+        assert_eq!(rng.next_u64(), 0x6E78_9E6A_A1B9_65F4);
+        assert_eq!(rng.next_u64(), 0x06C4_5D18_8009_454F);
+    }
+
+    #[test]
+    fn below_stays_within_the_bound() {
+        let mut rng = SplitMix64::new(42);
+        let values: Vec<usize> = (0..10).map(|_| rng.below(10)).collect();
+        assert_eq!(values, vec![7, 1, 2, 3, 0, 8, 2, 8, 3, 6]);
+
+        let mut rng = SplitMix64::new(1);
+        assert!((0..1000).all(|_| rng.below(3) < 3));
+        assert_eq!(rng.below(1), 0);
+    }
+
+    #[test]
+    fn shuffle_is_determined_by_the_seed() {
+        let mut items = letters("abcdefgh");
+        SplitMix64::new(7).shuffle(&mut items);
+        assert_eq!(items, letters("dbhfaegc"));
+
+        let mut again = letters("abcdefgh");
+        SplitMix64::new(7).shuffle(&mut again);
+        assert_eq!(again, items);
+
+        let mut other = letters("abcdefgh");
+        SplitMix64::new(8).shuffle(&mut other);
+        assert!(other != items);
+        other.sort();
+        assert_eq!(other, letters("abcdefgh"));
+    }
+
+    #[test]
+    fn streams_of_a_seed_shuffle_independently() {
+        let mut first = letters("abcd");
+        SplitMix64::stream(42, 3).shuffle(&mut first);
+        let mut second = letters("abcd");
+        SplitMix64::stream(42, 4).shuffle(&mut second);
+        assert_eq!(first, letters("bcad"));
+        assert_eq!(second, letters("cbad"));
+    }
+
+    #[test]
+    fn sample_keeps_count_items() {
+        let mut items: Vec<i32> = (1..=10).collect();
+        SplitMix64::new(3).sample(&mut items, 4);
+        assert_eq!(items.len(), 4);
+        let mut sorted = items.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(sorted.len(), 4);
+
+        let mut few = vec![1, 2];
+        SplitMix64::new(3).sample(&mut few, 5);
+        assert_eq!(few.len(), 2);
+    }
+
+    #[test]
+    fn sample_key_is_determined_by_the_seed() {
+        let key = SampleKey::new(7);
+        assert_eq!(key, SampleKey::new(7));
+        assert!(key != SampleKey::new(8));
+        assert_eq!(key.key(1), 1_863_799_616);
+        assert_eq!(key.key(2), 1_421_126_138);
+
+        // The order that the storages sort the ids 1 to 12 in for seed 42.
+        let key = SampleKey::new(42);
+        let mut ids: Vec<i64> = (1..=12).collect();
+        ids.sort_by_key(|id| (key.key(*id), *id));
+        assert_eq!(ids[..4].to_vec(), vec![7, 9, 2, 10]);
+    }
+
+    #[test]
+    fn sample_key_does_not_overflow() {
+        let key = SampleKey {
+            multiplier: SAMPLE_MODULUS - 1,
+            offset: SAMPLE_MODULUS - 1,
+            first_round: SAMPLE_MODULUS - 1,
+            second_round: SAMPLE_MODULUS - 1,
+        };
+        for id in &[0, 1, SAMPLE_MODULUS - 1, SAMPLE_MODULUS, ::std::i64::MAX] {
+            let value = key.key(*id);
+            assert!(value >= 0 && value < SAMPLE_MODULUS);
+        }
+
+        // Ids are only used modulo the prime.
+        let key = SampleKey::new(7);
+        assert_eq!(key.key(SAMPLE_MODULUS * 3 + 5), key.key(5));
+    }
+}
@@ -1,14 +1,25 @@
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chrono::{DateTime, Duration, Utc};
 use md5::{Digest, Md5};
 use models::*;
 use repositories::*;
+use sampling::{random_seed, SampleKey, SplitMix64};
 use std::collections::HashMap;
 use std::fmt;
-use std::sync::Arc;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use thumbnails;
+use tokens::{TokenClaims, TokenKind, TokenSigner};
 use uuid::Uuid;
 
+#[derive(Debug)]
 pub enum ServiceError {
     ValidationError(ValidationError),
     RepositoryError(RepositoryError),
+    AuthorizationError(AuthorizationError),
 }
 
 impl std::fmt::Display for ServiceError {
@@ -16,6 +27,7 @@ impl std::fmt::Display for ServiceError {
         match self {
             ServiceError::ValidationError(e) => write!(f, "{}", e),
             ServiceError::RepositoryError(e) => write!(f, "{}", e),
+            ServiceError::AuthorizationError(e) => write!(f, "{}", e),
         }
     }
 }
@@ -32,23 +44,144 @@ impl std::convert::From<RepositoryError> for ServiceError {
     }
 }
 
+impl std::convert::From<AuthorizationError> for ServiceError {
+    fn from(error: AuthorizationError) -> Self {
+        return ServiceError::AuthorizationError(error);
+    }
+}
+
 pub struct CategoriesService {
-    pub repo: Arc<CategoriesRepository>,
+    pub repo: Arc<dyn CategoriesRepository>,
 }
 
 impl CategoriesService {
-    pub fn new(repo: CategoriesRepository) -> CategoriesService {
-        return CategoriesService {
-            repo: Arc::new(repo),
-        };
+    pub fn new(repo: Arc<dyn CategoriesRepository>) -> CategoriesService {
+        return CategoriesService { repo: repo };
     }
 
     pub fn categories(&self) -> Result<Vec<Category>, ServiceError> {
         return (*self.repo).list_categories().map_err(|e| e.into());
     }
 
+    pub fn category(&self, name: &str) -> Result<Option<Category>, ServiceError> {
+        return (*self.repo).get_category(name).map_err(|e| e.into());
+    }
+
+    /// Lists the active child categories of the category.
+    pub fn children(&self, name: &str) -> Result<Vec<Category>, ServiceError> {
+        Ok(self
+            .categories()?
+            .into_iter()
+            .filter(|category| category.parent.as_ref().map(|p| p.as_str()) == Some(name))
+            .collect())
+    }
+
+    /// Builds the tree of active categories.
+    /// Categories whose parent is inactive or deleted are listed at the top level.
+    pub fn category_tree(&self) -> Result<Vec<CategoryNode>, ServiceError> {
+        let categories = self.categories()?;
+        let titles: Vec<&str> = categories.iter().map(|c| c.title.as_str()).collect();
+
+        fn node(title: &str, categories: &Vec<Category>, path: &mut Vec<String>) -> CategoryNode {
+            path.push(title.to_string());
+            let mut children: Vec<CategoryNode> = vec![];
+            for child in categories
+                .iter()
+                .filter(|c| c.parent.as_ref().map(|p| p.as_str()) == Some(title))
+            {
+                // A cycle can only be left behind by concurrent edits, but must not recurse forever.
+                if !path.contains(&child.title) {
+                    children.push(node(&child.title, categories, path));
+                }
+            }
+            path.pop();
+
+            CategoryNode {
+                title: title.to_string(),
+                children: children,
+            }
+        }
+
+        Ok(categories
+            .iter()
+            .filter(|c| match c.parent {
+                Some(ref parent) => !titles.contains(&parent.as_str()),
+                None => true,
+            })
+            .map(|c| node(&c.title, &categories, &mut vec![]))
+            .collect())
+    }
+
+    /// Saves the category. If the category has a parent, it is nested in (or moved to) that parent.
     pub fn save_category(&self, category: &Category) -> Result<SaveCategoryStatus, ServiceError> {
-        return (*self.repo).save_category(category).map_err(|e| e.into());
+        if let Some(ref parent) = category.parent {
+            self.ensure_category_exists(parent)?;
+        }
+
+        let status = (*self.repo).save_category(category)?;
+        if let Some(ref parent) = category.parent {
+            self.set_category_parent(&category.title, Some(parent))?;
+        }
+
+        Ok(status)
+    }
+
+    pub fn update_category(
+        &self,
+        name: &str,
+        patch: &CategoryPatch,
+    ) -> Result<Category, ServiceError> {
+        let mut category = self.existing_category(name)?;
+
+        patch.apply(&mut category);
+        Category::validate(&category)?;
+
+        if !(*self.repo).update_category(&category)? {
+            return Err(
+                RepositoryError::NotFound(format!("Category '{}' does not exist", name)).into(),
+            );
+        }
+
+        Ok(category)
+    }
+
+    /// Returns the names of the category's ancestors, starting with its parent.
+    fn ancestors(&self, name: &str) -> Result<Vec<String>, ServiceError> {
+        let mut ancestors: Vec<String> = vec![];
+        let mut current = (*self.repo).get_category(name)?;
+
+        while let Some(parent) = current.and_then(|c| c.parent) {
+            if ancestors.contains(&parent) {
+                break;
+            }
+            current = (*self.repo).get_category(&parent)?;
+            ancestors.push(parent);
+        }
+
+        Ok(ancestors)
+    }
+
+    /// Nests the category in `parent`, or makes it a top-level category if `parent` is `None`.
+    pub fn set_category_parent(
+        &self,
+        name: &str,
+        parent: Option<&str>,
+    ) -> Result<Category, ServiceError> {
+        self.ensure_category_exists(name)?;
+
+        if let Some(parent) = parent {
+            self.ensure_category_exists(parent)?;
+            if parent == name || self.ancestors(parent)?.iter().any(|a| a == name) {
+                return Err(ValidationError::new(format!(
+                    "Category '{}' can not be nested in itself or one of its descendants",
+                    name
+                ))
+                .into());
+            }
+        }
+
+        (*self.repo).set_category_parent(name, parent)?;
+        self.existing_category(name)
     }
 
     pub fn save_category_and_set_active(
@@ -60,44 +193,603 @@ impl CategoriesService {
             .save_category_and_set_active(category, active)
             .map_err(|e| e.into());
     }
+
+    fn existing_category(&self, name: &str) -> Result<Category, ServiceError> {
+        let category = (*self.repo)
+            .get_category(name)?
+            .ok_or(RepositoryError::NotFound(format!(
+                "Category '{}' does not exist",
+                name
+            )))?;
+        Ok(category)
+    }
+
+    fn ensure_category_exists(&self, name: &str) -> Result<(), ServiceError> {
+        if !(*self.repo).category_exists(name)? {
+            return Err(
+                RepositoryError::NotFound(format!("Category '{}' does not exist", name)).into(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Renames the category. The old name remains usable as an alias of the new one.
+    pub fn rename_category(&self, from: &str, to: &str) -> Result<Category, ServiceError> {
+        let to = to.trim();
+        if to.is_empty() {
+            return Err(ValidationError::new("Category name must not be empty".into()).into());
+        }
+
+        self.ensure_category_exists(from)?;
+        if (*self.repo).category_exists(to)? {
+            return Err(ValidationError::new(format!(
+                "Category '{}' already exists, use mergeCategories to combine categories",
+                to
+            ))
+            .into());
+        }
+
+        if !(*self.repo).rename_category(from, to)? {
+            return Err(
+                RepositoryError::NotFound(format!("Category '{}' does not exist", from)).into(),
+            );
+        }
+
+        self.existing_category(to)
+    }
+
+    /// Moves the questions of every source category into `target` and removes the sources.
+    /// The source names remain usable as aliases of `target`.
+    pub fn merge_categories(
+        &self,
+        sources: &Vec<String>,
+        target: &str,
+    ) -> Result<Category, ServiceError> {
+        let mut unique_sources: Vec<String> = vec![];
+        for source in sources.iter() {
+            if !unique_sources.contains(source) {
+                unique_sources.push(source.clone());
+            }
+        }
+
+        if unique_sources.is_empty() {
+            return Err(
+                ValidationError::new("At least one source category is required".into()).into(),
+            );
+        }
+        if unique_sources.iter().any(|source| source == target) {
+            return Err(ValidationError::new(format!(
+                "Category '{}' can not be merged into itself",
+                target
+            ))
+            .into());
+        }
+
+        self.ensure_category_exists(target)?;
+        for source in unique_sources.iter() {
+            self.ensure_category_exists(source)?;
+        }
+
+        let ancestors = self.ancestors(target)?;
+        if let Some(source) = unique_sources.iter().find(|s| ancestors.contains(s)) {
+            return Err(ValidationError::new(format!(
+                "Category '{}' can not be merged into its descendant '{}'",
+                source, target
+            ))
+            .into());
+        }
+
+        (*self.repo).merge_categories(&unique_sources, target)?;
+
+        self.existing_category(target)
+    }
+
+    pub fn delete_category(&self, name: &str) -> Result<bool, ServiceError> {
+        return (*self.repo).delete_category(name).map_err(|e| e.into());
+    }
+
+    pub fn restore_category(&self, name: &str) -> Result<bool, ServiceError> {
+        return (*self.repo).restore_category(name).map_err(|e| e.into());
+    }
+
+    pub fn deleted_categories(&self) -> Result<Vec<DeletedCategory>, ServiceError> {
+        return (*self.repo).list_deleted_categories().map_err(|e| e.into());
+    }
+
+    pub fn purge_categories(&self, before: DateTime<Utc>) -> Result<u64, ServiceError> {
+        return (*self.repo).purge_categories(before).map_err(|e| e.into());
+    }
 }
 
 pub struct QuestionsService {
-    pub repo: Arc<QuestionsRepository>,
+    pub repo: Arc<dyn QuestionsRepository>,
+    pub media: Arc<dyn MediaStorage>,
 }
 
 impl QuestionsService {
-    pub fn new(repo: QuestionsRepository) -> QuestionsService {
+    pub fn new(
+        repo: Arc<dyn QuestionsRepository>,
+        media: Arc<dyn MediaStorage>,
+    ) -> QuestionsService {
         return QuestionsService {
-            repo: Arc::new(repo),
+            repo: repo,
+            media: media,
         };
     }
 
+    /// Attachments must refer to media that has been uploaded. The resized variants of attached images
+    /// are generated here, so players never wait for them.
+    fn prepare_media(&self, question: &Question) -> Result<(), ServiceError> {
+        let attachments = question.media.iter().chain(
+            question
+                .choices
+                .iter()
+                .flat_map(|choice| choice.media.iter()),
+        );
+
+        for attachment in attachments {
+            if !(*self.media).exists(&attachment.media_id)? {
+                return Err(ValidationError::new(format!(
+                    "Media '{}' has not been uploaded",
+                    attachment.media_id
+                ))
+                .into());
+            }
+            generate_variants(&*self.media, &attachment.media_id)?;
+        }
+        Ok(())
+    }
+
     pub fn questions(
         &self,
-        category: &str,
+        filter: &QuestionFilter,
         page: i64,
         size: i64,
     ) -> Result<Vec<Question>, ServiceError> {
         return (*self.repo)
-            .get_questions(category, page, size)
-            .map_err(|e| e.into());;
+            .get_questions(filter, page, size)
+            .map_err(|e| e.into());
     }
 
-    pub fn save_question(&self, question: &Question) -> Result<Question, ServiceError> {
+    pub fn save_question(
+        &self,
+        question: &Question,
+        author: &str,
+    ) -> Result<Question, ServiceError> {
         Question::validate(question).map_err(|e| ServiceError::from(e))?;
-        return (*self.repo).save_question(question).map_err(|e| e.into());;
+        self.prepare_media(question)?;
+        return (*self.repo)
+            .save_question(question, author)
+            .map_err(|e| e.into());
+    }
+
+    fn existing_question(&self, id: i32) -> Result<Question, ServiceError> {
+        let question = (*self.repo)
+            .get_question(id)?
+            .ok_or(RepositoryError::NotFound(format!(
+                "Question {} does not exist",
+                id
+            )))?;
+        Ok(question)
+    }
+
+    /// The question, if players can see it. Answers are only checked for visible questions,
+    /// so that walking through question ids cannot reveal the answers of questions in hidden categories.
+    fn visible_question(&self, id: i32) -> Result<Question, ServiceError> {
+        let question = (*self.repo)
+            .get_visible_question(id)?
+            .ok_or(RepositoryError::NotFound(format!(
+                "Question {} does not exist",
+                id
+            )))?;
+        Ok(question)
+    }
+
+    pub fn update_question(
+        &self,
+        id: i32,
+        patch: &QuestionPatch,
+        author: &str,
+    ) -> Result<Question, ServiceError> {
+        let mut question = self.existing_question(id)?;
+
+        patch.apply(&mut question)?;
+        Question::validate(&question)?;
+        self.prepare_media(&question)?;
+
+        return (*self.repo)
+            .update_question(&question, author)
+            .map_err(|e| e.into());
+    }
+
+    pub fn revisions(&self, id: i32) -> Result<Vec<QuestionRevision>, ServiceError> {
+        return (*self.repo).list_revisions(id).map_err(|e| e.into());
+    }
+
+    /// Restores the wording, category and choices of an earlier revision.
+    /// The revert is itself recorded as a new revision, so history is never rewritten.
+    pub fn revert_question(
+        &self,
+        id: i32,
+        revision: i32,
+        author: &str,
+    ) -> Result<Question, ServiceError> {
+        let current = self.existing_question(id)?;
+        let snapshot =
+            (*self.repo)
+                .get_revision(id, revision)?
+                .ok_or(RepositoryError::NotFound(format!(
+                    "Question {} has no revision {}",
+                    id, revision
+                )))?;
+
+        let question = Question::from_revision(&current, snapshot);
+        Question::validate(&question)?;
+
+        return (*self.repo)
+            .update_question(&question, author)
+            .map_err(|e| e.into());
+    }
+
+    /// Checks a player's answer to a free-text question against its accepted answers.
+    pub fn check_text_answer(&self, id: i32, answer: &str) -> Result<AnswerCheck, ServiceError> {
+        let question = self.visible_question(id)?;
+
+        match question.free_text {
+            Some(ref free_text) => Ok(free_text.check(answer)),
+            None => Err(ValidationError::new(format!(
+                "Question {} is not a free-text question",
+                id
+            ))
+            .into()),
+        }
+    }
+
+    /// Scores a player's ordering of the items of an ordering question.
+    pub fn check_ordering_answer(
+        &self,
+        id: i32,
+        items: &[String],
+    ) -> Result<AnswerCheck, ServiceError> {
+        let question = self.visible_question(id)?;
+
+        match question.ordering {
+            Some(ref ordering) => Ok(ordering.check(items)?),
+            None => Err(ValidationError::new(format!(
+                "Question {} is not an ordering question",
+                id
+            ))
+            .into()),
+        }
+    }
+
+    /// Scores a player's pairs for a matching question.
+    pub fn check_matching_answer(
+        &self,
+        id: i32,
+        pairs: &[MatchingPair],
+    ) -> Result<AnswerCheck, ServiceError> {
+        let question = self.visible_question(id)?;
+
+        match question.matching {
+            Some(ref matching) => Ok(matching.check(pairs)?),
+            None => Err(ValidationError::new(format!(
+                "Question {} is not a matching question",
+                id
+            ))
+            .into()),
+        }
+    }
+
+    /// Checks a player's answer to a numeric question, optionally given with its unit.
+    pub fn check_numeric_answer(
+        &self,
+        id: i32,
+        value: f64,
+        unit: Option<&str>,
+    ) -> Result<AnswerCheck, ServiceError> {
+        let question = self.visible_question(id)?;
+
+        match question.numeric {
+            Some(ref numeric) => Ok(numeric.check(value, unit)?),
+            None => Err(
+                ValidationError::new(format!("Question {} is not a numeric question", id)).into(),
+            ),
+        }
     }
 
-    pub fn count_questions(&self, category: &str) -> Result<i64, ServiceError> {
-        return (*self.repo).count_questions(category).map_err(|e| e.into());;
+    /// Reveals whether one choice of a question is correct, with the explanations, without revealing the other choices.
+    pub fn check_choice_answer(
+        &self,
+        id: i32,
+        choice_id: i32,
+    ) -> Result<ChoiceCheck, ServiceError> {
+        let question = self.visible_question(id)?;
+
+        let choice = question
+            .choices
+            .iter()
+            .find(|choice| choice.id == Some(choice_id))
+            .ok_or(ValidationError::new(format!(
+                "Choice {} is not a choice of question {}",
+                choice_id, id
+            )))?;
+
+        Ok(ChoiceCheck {
+            question_id: id,
+            choice_id: choice_id,
+            correct: choice.correct,
+            explanation: question.explanation.clone(),
+            choice_explanation: choice.explanation.clone(),
+            sources: question.sources.clone(),
+        })
+    }
+
+    pub fn count_questions(&self, filter: &QuestionFilter) -> Result<i64, ServiceError> {
+        return (*self.repo).count_questions(filter).map_err(|e| e.into());
+    }
+
+    pub fn tags(&self) -> Result<Vec<TagCount>, ServiceError> {
+        return (*self.repo).list_tags().map_err(|e| e.into());
+    }
+
+    /// Picks `count` random questions selected by the filter, leaving out `exclude_ids`, and shuffles their choices.
+    /// The same seed picks the same questions in the same order with the same choice order, as long as the selected questions do not change.
+    /// The storage picks the sample, so only the picked questions are loaded.
+    pub fn random_questions(
+        &self,
+        filter: &QuestionFilter,
+        count: i32,
+        seed: i32,
+        exclude_ids: &[i32],
+    ) -> Result<Vec<Question>, ServiceError> {
+        if count < 1 || count > MAX_RANDOM_QUESTIONS {
+            return Err(ValidationError::new(format!(
+                "The number of random questions must be between 1 and {}",
+                MAX_RANDOM_QUESTIONS
+            ))
+            .into());
+        }
+
+        sample_questions(&*self.repo, filter, count, seed, exclude_ids)
+    }
+
+    pub fn count_search_results(&self, search: &SearchFilter) -> Result<i64, ServiceError> {
+        validate_search(search)?;
+        return (*self.repo)
+            .count_search_results(search)
+            .map_err(|e| e.into());
+    }
+
+    pub fn search_questions(
+        &self,
+        search: &SearchFilter,
+        page: i64,
+        size: i64,
+    ) -> Result<Vec<(Question, SearchHighlight)>, ServiceError> {
+        validate_search(search)?;
+        return (*self.repo)
+            .search_questions(search, page, size)
+            .map_err(|e| e.into());
+    }
+
+    pub fn delete_question(&self, id: i32) -> Result<bool, ServiceError> {
+        return (*self.repo).delete_question(id).map_err(|e| e.into());
+    }
+
+    pub fn restore_question(&self, id: i32) -> Result<bool, ServiceError> {
+        return (*self.repo).restore_question(id).map_err(|e| e.into());
+    }
+
+    pub fn deleted_questions(&self) -> Result<Vec<DeletedQuestion>, ServiceError> {
+        return (*self.repo).list_deleted_questions().map_err(|e| e.into());
+    }
+
+    pub fn purge_questions(&self, before: DateTime<Utc>) -> Result<u64, ServiceError> {
+        return (*self.repo).purge_questions(before).map_err(|e| e.into());
+    }
+}
+
+pub struct QuizService {
+    pub repo: Arc<dyn QuizRepository>,
+    pub questions: Arc<dyn QuestionsRepository>,
+}
+
+impl QuizService {
+    pub fn new(
+        repo: Arc<dyn QuizRepository>,
+        questions: Arc<dyn QuestionsRepository>,
+    ) -> QuizService {
+        return QuizService {
+            repo: repo,
+            questions: questions,
+        };
+    }
+
+    /// A session started by a player is only found for that player, so that others cannot answer or finish it.
+    fn existing_session(
+        &self,
+        id: &str,
+        player_id: Option<&str>,
+    ) -> Result<QuizSession, ServiceError> {
+        let session = (*self.repo)
+            .get_session(id)?
+            .filter(|session| {
+                session.player_id.is_none()
+                    || session.player_id.as_ref().map(|id| id.as_str()) == player_id
+            })
+            .ok_or(RepositoryError::NotFound(format!(
+                "Quiz session '{}' does not exist",
+                id
+            )))?;
+        Ok(session)
+    }
+
+    /// Starts a quiz with random single-choice and true/false questions from the category.
+    /// A quiz started by a player is added to their history.
+    pub fn start(
+        &self,
+        category: &str,
+        count: i32,
+        include_descendants: bool,
+        player_id: Option<&str>,
+    ) -> Result<StartedQuiz, ServiceError> {
+        if count < 1 || count > MAX_RANDOM_QUESTIONS {
+            return Err(ValidationError::new(format!(
+                "The number of quiz questions must be between 1 and {}",
+                MAX_RANDOM_QUESTIONS
+            ))
+            .into());
+        }
+
+        let filter = QuestionFilter {
+            category: category.to_string(),
+            include_descendants: include_descendants,
+            difficulties: vec![],
+            tags: vec![],
+            question_types: QUIZ_QUESTION_TYPES.to_vec(),
+        };
+        let questions = sample_questions(&*self.questions, &filter, count, random_seed(), &[])?;
+        if questions.is_empty() {
+            return Err(ValidationError::new(format!(
+                "Category '{}' has no questions for a quiz",
+                category
+            ))
+            .into());
+        }
+
+        let session = QuizSession {
+            id: Uuid::new_v4().to_simple().to_string(),
+            category: category.to_string(),
+            player_id: player_id.map(|id| id.to_string()),
+            question_ids: questions.iter().map(|q| q.id.unwrap_or_default()).collect(),
+            answers: vec![],
+            started_on: Utc::now(),
+            finished_on: None,
+        };
+        (*self.repo).save_session(&session)?;
+
+        Ok(StartedQuiz {
+            session_id: session.id,
+            questions: questions.iter().map(QuizQuestion::new).collect(),
+        })
+    }
+
+    /// Grades the choice picked for a question of the quiz. Each question can be answered once.
+    pub fn submit_answer(
+        &self,
+        session_id: &str,
+        question_id: i32,
+        choice_id: i32,
+        player_id: Option<&str>,
+    ) -> Result<GradedAnswer, ServiceError> {
+        let session = self.existing_session(session_id, player_id)?;
+        if session.finished_on.is_some() {
+            return Err(
+                ValidationError::new(format!("Quiz session '{}' is finished", session_id)).into(),
+            );
+        }
+        if !session.question_ids.contains(&question_id) {
+            return Err(ValidationError::new(format!(
+                "Question {} is not part of quiz session '{}'",
+                question_id, session_id
+            ))
+            .into());
+        }
+        if session.answers.iter().any(|a| a.question_id == question_id) {
+            return Err(ValidationError::new(format!(
+                "Question {} has already been answered",
+                question_id
+            ))
+            .into());
+        }
+
+        let question =
+            (*self.questions)
+                .get_question(question_id)?
+                .ok_or(RepositoryError::NotFound(format!(
+                    "Question {} does not exist",
+                    question_id
+                )))?;
+        let choice = question
+            .choices
+            .iter()
+            .find(|choice| choice.id == Some(choice_id))
+            .ok_or(ValidationError::new(format!(
+                "Choice {} is not a choice of question {}",
+                choice_id, question_id
+            )))?;
+
+        let answer = QuizAnswer {
+            question_id: question_id,
+            choice_id: choice_id,
+            correct: choice.correct,
+            answered_on: Utc::now(),
+        };
+        // A concurrent request may have answered the question or finished the quiz since the session was read.
+        if !(*self.repo).save_answer(session_id, &answer)? {
+            return Err(ValidationError::new(format!(
+                "Question {} has already been answered or the quiz is finished",
+                question_id
+            ))
+            .into());
+        }
+
+        Ok(GradedAnswer {
+            question_id: question_id,
+            choice_id: choice_id,
+            correct: choice.correct,
+            correct_choice_ids: question
+                .choices
+                .iter()
+                .filter(|c| c.correct)
+                .filter_map(|c| c.id)
+                .collect(),
+            explanation: question.explanation.clone(),
+            choice_explanation: choice.explanation.clone(),
+        })
+    }
+
+    /// Finishes the quiz and returns its score. Finishing a finished quiz returns the same result again.
+    pub fn finish(
+        &self,
+        session_id: &str,
+        player_id: Option<&str>,
+    ) -> Result<QuizResult, ServiceError> {
+        self.existing_session(session_id, player_id)?;
+        (*self.repo).finish_session(session_id, Utc::now())?;
+
+        let session = self.existing_session(session_id, player_id)?;
+        let finished_on = session.finished_on.unwrap_or_else(Utc::now);
+        Ok(QuizResult::new(session, finished_on))
+    }
+
+    /// The results of the quizzes the player has finished, most recently started first.
+    pub fn history(&self, player_id: &str) -> Result<Vec<QuizResult>, ServiceError> {
+        Ok((*self.repo)
+            .list_sessions(player_id)?
+            .into_iter()
+            .filter_map(|session| {
+                session
+                    .finished_on
+                    .map(|finished_on| QuizResult::new(session, finished_on))
+            })
+            .collect())
     }
 }
 
+#[derive(Debug)]
 pub enum AuthorizationError {
     InvalidFormat,
-    MissingField { field: String },
+    MissingField {
+        field: String,
+    },
     IncorrectResponse,
+    /// Login with an unknown username or a wrong password.
+    IncorrectCredentials,
+    /// A player's bearer token is required but was not sent.
+    MissingToken,
+    InvalidToken,
 }
 
 impl std::fmt::Display for AuthorizationError {
@@ -106,6 +798,9 @@ impl std::fmt::Display for AuthorizationError {
             AuthorizationError::InvalidFormat => write!(f, "{}", "Expected digest format: 'Digest username=\"?\", realm=\"?\", nonce=\"?\", opaque=\"?\", uri=\"?\", response=\"?\"'."),
             AuthorizationError::MissingField{field} => write!(f, "Digest does not contain required field: '{}'", field),
             AuthorizationError::IncorrectResponse => write!(f, "{}", "The 'response' field is incorrect or expired."),
+            AuthorizationError::IncorrectCredentials => write!(f, "{}", "The username or password is incorrect."),
+            AuthorizationError::MissingToken => write!(f, "{}", "Log in and send the access token as 'Authorization: Bearer <token>'."),
+            AuthorizationError::InvalidToken => write!(f, "{}", "The token is invalid or expired."),
         }
     }
 }
@@ -160,7 +855,7 @@ impl AuthorizationService {
         raw_digest: &str,
         request_method: &str,
         uri: &str,
-    ) -> Result<(), AuthorizationError> {
+    ) -> Result<String, AuthorizationError> {
         let clean_digest = raw_digest.trim();
         let prefix = "Digest ";
 
@@ -200,9 +895,915 @@ impl AuthorizationService {
         let expected_response = Self::md5(format!("{}:{}:{}", a1, nonce, a2));
 
         if expected_response == response {
-            Ok(())
+            Ok(self.admin_username.clone())
         } else {
             Err(AuthorizationError::IncorrectResponse)
         }
     }
 }
+
+const ACCESS_TOKEN_LIFETIME_MINUTES: i64 = 15;
+const REFRESH_TOKEN_LIFETIME_DAYS: i64 = 30;
+
+/// Who made a request, from the bearer token in its `Authorization` header.
+#[derive(Debug, Clone)]
+pub enum Principal {
+    Anonymous,
+    /// A player, by id.
+    Player(String),
+    /// The request carried a bearer token that is malformed, forged or expired.
+    InvalidToken,
+}
+
+impl Principal {
+    /// The player who made the request, for resolvers that need one.
+    pub fn player_id(&self) -> Result<&str, AuthorizationError> {
+        match *self {
+            Principal::Player(ref id) => Ok(id),
+            Principal::Anonymous => Err(AuthorizationError::MissingToken),
+            Principal::InvalidToken => Err(AuthorizationError::InvalidToken),
+        }
+    }
+
+    /// The player who made the request, or `None` if it was made without a token.
+    pub fn optional_player_id(&self) -> Result<Option<&str>, AuthorizationError> {
+        match *self {
+            Principal::Anonymous => Ok(None),
+            _ => self.player_id().map(Some),
+        }
+    }
+}
+
+pub struct PlayersService {
+    pub repo: Arc<dyn PlayersRepository>,
+    signer: TokenSigner,
+}
+
+impl PlayersService {
+    pub fn new(repo: Arc<dyn PlayersRepository>, token_key: &[u8]) -> PlayersService {
+        return PlayersService {
+            repo: repo,
+            signer: TokenSigner::new(token_key),
+        };
+    }
+
+    pub fn register(&self, username: &str, password: &str) -> Result<AuthTokens, ServiceError> {
+        let username = Player::normalize_username(username);
+        Player::validate_username(&username)?;
+        Player::validate_password(password)?;
+
+        let player = Player {
+            id: Uuid::new_v4().to_simple().to_string(),
+            username: Some(username.clone()),
+            created_on: Utc::now(),
+        };
+        if !(*self.repo).save_player(&player, &hash_password(password)?)? {
+            return Err(ValidationError::new(format!("Username '{}' is taken", username)).into());
+        }
+
+        Ok(self.issue_tokens(player))
+    }
+
+    /// Logs in the anonymous player playing on the device, who is created the first time the device logs in.
+    pub fn device_login(&self, device_id: &str) -> Result<AuthTokens, ServiceError> {
+        let device_id = device_id.trim();
+        Player::validate_device_id(device_id)?;
+
+        let player = Player {
+            id: Uuid::new_v4().to_simple().to_string(),
+            username: None,
+            created_on: Utc::now(),
+        };
+        let player = (*self.repo).get_or_save_device_player(device_id, &player)?;
+
+        Ok(self.issue_tokens(player))
+    }
+
+    /// Moves the quiz history of an anonymous player to the account with the credentials, and removes the
+    /// anonymous player. Returns tokens for the account, which the device uses from then on.
+    pub fn link_account(
+        &self,
+        anonymous_id: &str,
+        username: &str,
+        password: &str,
+    ) -> Result<AuthTokens, ServiceError> {
+        let anonymous = (*self.repo)
+            .get_player(anonymous_id)?
+            .ok_or(AuthorizationError::InvalidToken)?;
+        if anonymous.username.is_some() {
+            return Err(ValidationError::new(
+                "Only anonymous players can be linked to an account".into(),
+            )
+            .into());
+        }
+
+        let player = self.check_credentials(username, password)?;
+
+        // Another request may have linked the same anonymous player since it was loaded.
+        (*self.repo)
+            .merge_player(anonymous_id, &player.id)?
+            .ok_or(AuthorizationError::InvalidToken)?;
+
+        Ok(self.issue_tokens(player))
+    }
+
+    pub fn login(&self, username: &str, password: &str) -> Result<AuthTokens, ServiceError> {
+        let player = self.check_credentials(username, password)?;
+        Ok(self.issue_tokens(player))
+    }
+
+    /// The player with the username, if the password is theirs.
+    fn check_credentials(&self, username: &str, password: &str) -> Result<Player, ServiceError> {
+        let username = Player::normalize_username(username);
+        match (*self.repo).get_player_by_username(&username)? {
+            Some((player, ref password_hash)) if verify_password(password, password_hash) => {
+                Ok(player)
+            }
+            // The same error whether the username or the password is wrong, so usernames cannot be probed.
+            Some(_) => Err(AuthorizationError::IncorrectCredentials.into()),
+            None => {
+                // A password is hashed either way, so unknown usernames take as long to reject as wrong passwords.
+                verify_password(password, DUMMY_PASSWORD_HASH);
+                Err(AuthorizationError::IncorrectCredentials.into())
+            }
+        }
+    }
+
+    /// Exchanges a refresh token for new tokens, as long as the player still exists.
+    pub fn refresh_tokens(&self, refresh_token: &str) -> Result<AuthTokens, ServiceError> {
+        let claims = self
+            .signer
+            .verify(refresh_token, TokenKind::Refresh, Utc::now())
+            .ok_or(AuthorizationError::InvalidToken)?;
+        let player = (*self.repo)
+            .get_player(&claims.sub)?
+            .ok_or(AuthorizationError::InvalidToken)?;
+
+        Ok(self.issue_tokens(player))
+    }
+
+    pub fn player(&self, id: &str) -> Result<Option<Player>, ServiceError> {
+        return (*self.repo).get_player(id).map_err(|e| e.into());
+    }
+
+    /// The player who made the request, or `None` if it was made without a token. Tokens outlive the players
+    /// they were issued to, e.g. anonymous players merged by `link_account`, so the player is looked up again.
+    pub fn existing_player_id<'a>(
+        &self,
+        principal: &'a Principal,
+    ) -> Result<Option<&'a str>, ServiceError> {
+        match principal.optional_player_id()? {
+            Some(id) => match (*self.repo).get_player(id)? {
+                Some(_) => Ok(Some(id)),
+                None => Err(AuthorizationError::InvalidToken.into()),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// The principal of a request with the given `Authorization` header, if any.
+    pub fn authenticate(&self, authorization: Option<&str>) -> Principal {
+        let prefix = "Bearer ";
+        match authorization.map(|header| header.trim()) {
+            Some(header) if header.starts_with(prefix) => {
+                match self
+                    .signer
+                    .verify(&header[prefix.len()..], TokenKind::Access, Utc::now())
+                {
+                    Some(claims) => Principal::Player(claims.sub),
+                    None => Principal::InvalidToken,
+                }
+            }
+            // Other schemes, e.g. the admin's digest, are not player tokens.
+            _ => Principal::Anonymous,
+        }
+    }
+
+    fn issue_tokens(&self, player: Player) -> AuthTokens {
+        let now = Utc::now();
+        let access_token_expires_on = now + Duration::minutes(ACCESS_TOKEN_LIFETIME_MINUTES);
+        let refresh_token_expires_on = now + Duration::days(REFRESH_TOKEN_LIFETIME_DAYS);
+
+        AuthTokens {
+            access_token: self.signer.sign(&TokenClaims {
+                sub: player.id.clone(),
+                kind: TokenKind::Access,
+                exp: access_token_expires_on.timestamp(),
+            }),
+            access_token_expires_on: access_token_expires_on,
+            refresh_token: self.signer.sign(&TokenClaims {
+                sub: player.id.clone(),
+                kind: TokenKind::Refresh,
+                exp: refresh_token_expires_on.timestamp(),
+            }),
+            refresh_token_expires_on: refresh_token_expires_on,
+            player: player,
+        }
+    }
+}
+
+/// The hash of a random password that was thrown away, made with the same parameters as `hash_password`.
+const DUMMY_PASSWORD_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$ldDqW8lMRu6Md0e8vqjx0Q$ted7GQK168OgrKT8KzoqbGx5eKZOm/RCMOFAQoxpQr8";
+
+/// Hashes a password with Argon2id and a random salt, as a PHC string that records the parameters used.
+fn hash_password(password: &str) -> Result<String, ServiceError> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| {
+            ValidationError::new(format!("The password could not be hashed: {}", e)).into()
+        })
+}
+
+fn verify_password(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash)
+        .map(|hash| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+/// Media stored by `MediaService::upload`.
+pub struct UploadedMedia {
+    pub media_id: String,
+    pub media_type: &'static MediaType,
+    pub size: usize,
+}
+
+/// A file received with a GraphQL multipart request, waiting in a temporary file until a mutation stores it.
+struct PendingUpload {
+    path: PathBuf,
+    mime_type: String,
+    /// Set once stored, so a file that the request references more than once is only stored once.
+    media_id: Option<String>,
+}
+
+pub struct MediaService {
+    pub storage: Arc<dyn MediaStorage>,
+    pub max_bytes: usize,
+    pending_uploads: Mutex<HashMap<String, PendingUpload>>,
+}
+
+impl MediaService {
+    pub fn new(storage: Arc<dyn MediaStorage>, max_bytes: usize) -> MediaService {
+        return MediaService {
+            storage: storage,
+            max_bytes: max_bytes,
+            pending_uploads: Mutex::new(HashMap::new()),
+        };
+    }
+
+    /// Registers a temporary file, which now belongs to the service, and returns the token that refers to it.
+    pub fn add_pending_upload(&self, path: PathBuf, mime_type: &str) -> Upload {
+        let token = format!("{}", Uuid::new_v4().to_simple());
+        let upload = PendingUpload {
+            path: path,
+            mime_type: mime_type.to_owned(),
+            media_id: None,
+        };
+        self.pending_uploads
+            .lock()
+            .unwrap()
+            .insert(token.clone(), upload);
+        Upload(token)
+    }
+
+    /// Forgets the upload and deletes its temporary file.
+    pub fn remove_pending_upload(&self, upload: &Upload) {
+        if let Some(pending) = self.pending_uploads.lock().unwrap().remove(&upload.0) {
+            let _ = fs::remove_file(&pending.path);
+        }
+    }
+
+    /// Stores the files uploaded with the request and replaces them with their media ids.
+    pub fn store_uploads(&self, attachments: Vec<&mut NewAttachment>) -> Result<(), ServiceError> {
+        for attachment in attachments {
+            let upload = match (attachment.upload.take(), &attachment.media_id) {
+                (Some(upload), &None) => upload,
+                (None, &Some(_)) => continue,
+                _ => {
+                    return Err(ValidationError::new(
+                        "An attachment needs either a mediaId or an upload".to_string(),
+                    )
+                    .into())
+                }
+            };
+            attachment.media_id = Some(self.store_upload(&upload)?);
+        }
+        Ok(())
+    }
+
+    fn store_upload(&self, upload: &Upload) -> Result<String, ServiceError> {
+        let (path, mime_type) = {
+            let pending_uploads = self.pending_uploads.lock().unwrap();
+            let pending = pending_uploads.get(&upload.0).ok_or_else(|| {
+                ValidationError::new("The upload is not part of this request".to_string())
+            })?;
+            if let Some(ref media_id) = pending.media_id {
+                return Ok(media_id.clone());
+            }
+            (pending.path.clone(), pending.mime_type.clone())
+        };
+
+        let media_type = MediaType::from_mime_type(&mime_type).ok_or_else(|| {
+            ValidationError::new(format!("Files of type '{}' cannot be uploaded", mime_type))
+        })?;
+        let data = fs::read(&path)
+            .map_err(|e| RepositoryError::IOError(format!("{}: {}", path.display(), e)))?;
+        let media = self.upload(media_type, &data)?;
+
+        if let Some(pending) = self.pending_uploads.lock().unwrap().get_mut(&upload.0) {
+            pending.media_id = Some(media.media_id.clone());
+        }
+        Ok(media.media_id)
+    }
+
+    /// Stores the media under a new id, after checking that its content matches the declared type.
+    pub fn upload(
+        &self,
+        media_type: &'static MediaType,
+        data: &[u8],
+    ) -> Result<UploadedMedia, ServiceError> {
+        if data.len() > self.max_bytes {
+            return Err(ValidationError::new(format!(
+                "Media may not be larger than {} bytes",
+                self.max_bytes
+            ))
+            .into());
+        }
+        if !media_type.matches(data) {
+            return Err(ValidationError::new(format!(
+                "The content is not {}",
+                media_type.mime_type
+            ))
+            .into());
+        }
+
+        let media_id = format!("{}.{}", Uuid::new_v4().to_simple(), media_type.extension);
+        (*self.storage).store(&media_id, data)?;
+
+        Ok(UploadedMedia {
+            media_id: media_id,
+            media_type: media_type,
+            size: data.len(),
+        })
+    }
+
+    /// Resized variants missing from the cache are generated on the way.
+    pub fn load(
+        &self,
+        media_id: &str,
+        variant: MediaVariant,
+    ) -> Result<Option<(&'static MediaType, Vec<u8>)>, ServiceError> {
+        let media_type = match MediaType::from_media_id(media_id) {
+            Some(media_type) => media_type,
+            None => return Ok(None),
+        };
+
+        let variant_id = match thumbnails::variant_id(media_id, variant) {
+            Some(variant_id) => variant_id,
+            None => {
+                let data = (*self.storage).load(media_id)?;
+                return Ok(data.map(|data| (media_type, data)));
+            }
+        };
+
+        if let Some(data) = (*self.storage).load(&variant_id)? {
+            return Ok(Some((thumbnails::variant_type(media_type), data)));
+        }
+        generate_variants(&*self.storage, media_id)?;
+
+        let data = (*self.storage).load(&variant_id)?;
+        Ok(data.map(|data| (thumbnails::variant_type(media_type), data)))
+    }
+}
+
+/// Stores every resized variant of an image that is not cached yet. Does nothing for audio.
+fn generate_variants(storage: &dyn MediaStorage, media_id: &str) -> Result<(), ServiceError> {
+    let missing = RESIZED_VARIANTS
+        .iter()
+        .filter_map(|variant| {
+            thumbnails::variant_id(media_id, *variant).map(|variant_id| (*variant, variant_id))
+        })
+        .map(|(variant, variant_id)| {
+            storage
+                .exists(&variant_id)
+                .map(|exists| (variant, variant_id, exists))
+        })
+        .collect::<Result<Vec<_>, RepositoryError>>()?
+        .into_iter()
+        .filter(|&(_, _, exists)| !exists)
+        .collect::<Vec<_>>();
+
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    let media_type = match MediaType::from_media_id(media_id) {
+        Some(media_type) => media_type,
+        None => return Ok(()),
+    };
+    let original = match storage.load(media_id)? {
+        Some(original) => original,
+        None => return Ok(()),
+    };
+
+    for (variant, variant_id, _) in missing {
+        if let Some(width) = variant.width() {
+            let resized = thumbnails::resize(media_id, media_type, &original, width)?;
+            storage.store(&variant_id, &resized)?;
+        }
+    }
+    Ok(())
+}
+
+/// Picks `count` questions selected by the filter, in an order and with choices shuffled as determined by the seed.
+fn sample_questions(
+    repo: &dyn QuestionsRepository,
+    filter: &QuestionFilter,
+    count: i32,
+    seed: i32,
+    exclude_ids: &[i32],
+) -> Result<Vec<Question>, ServiceError> {
+    let key = SampleKey::new(seed as u32 as u64);
+    let ids = repo.sample_question_ids(filter, &key, exclude_ids, i64::from(count))?;
+
+    let mut questions: HashMap<i32, Question> = repo
+        .get_questions_by_ids(&ids)?
+        .into_iter()
+        .map(|question| (question.id.unwrap_or_default(), question))
+        .collect();
+
+    // A question deleted since its id was picked is left out rather than replaced, so that the sample stays reproducible.
+    // The choices are sorted before they are shuffled, so that undoing the shuffle with the seed, which players know,
+    // does not reveal the order they were written in, where the correct choice often comes first.
+    Ok(ids
+        .iter()
+        .filter_map(|id| questions.remove(id))
+        .map(|mut question| {
+            question.choices.sort_by(|a, b| a.title.cmp(&b.title));
+            sample_shuffler(seed, question.id.unwrap_or_default()).shuffle(&mut question.choices);
+            question
+        })
+        .collect())
+}
+
+/// Shuffles the choices, ordering items and matching sides of a question picked with `seed`,
+/// the same way every time the seed picks it.
+pub fn sample_shuffler(seed: i32, question_id: i32) -> SplitMix64 {
+    SplitMix64::stream(seed as u32 as u64, question_id as u32 as u64)
+}
+
+/// Search text must contain a letter or digit, so that an empty search does not match nothing silently.
+fn validate_search(search: &SearchFilter) -> Result<(), ServiceError> {
+    if !search.text.chars().any(|c| c.is_alphanumeric()) {
+        return Err(
+            ValidationError::new("The search text must contain a letter or digit".into()).into(),
+        );
+    }
+    if search.text.chars().count() > MAX_SEARCH_LENGTH {
+        return Err(ValidationError::new(format!(
+            "The search text may not be longer than {} characters",
+            MAX_SEARCH_LENGTH
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn choice(title: &str, correct: bool) -> Choice {
+        Choice {
+            id: None,
+            title: title.to_string(),
+            correct: correct,
+            explanation: None,
+            media: vec![],
+        }
+    }
+
+    fn question(category: &str, text: &str, choices: Vec<Choice>) -> Question {
+        Question {
+            id: None,
+            question: text.to_string(),
+            category: category.to_string(),
+            difficulty: Difficulty::Medium,
+            question_type: QuestionType::SingleChoice,
+            choices: choices,
+            explanation: None,
+            sources: vec![],
+            media: vec![],
+            tags: vec![],
+            free_text: None,
+            ordering: None,
+            matching: None,
+            numeric: None,
+        }
+    }
+
+    fn filter(category: &str) -> QuestionFilter {
+        QuestionFilter {
+            category: category.to_string(),
+            include_descendants: false,
+            difficulties: vec![],
+            tags: vec![],
+            question_types: vec![],
+        }
+    }
+
+    fn services() -> (CategoriesService, QuestionsService) {
+        let store = MemoryStore::new();
+        // No media is attached in these tests, so the directory stays empty.
+        let media = LocalMediaStorage::new(
+            env::temp_dir()
+                .join("quizzical-tests-media")
+                .to_str()
+                .unwrap(),
+        )
+        .unwrap();
+        (
+            CategoriesService::new(Arc::new(InMemoryCategoriesRepository::new(store.clone()))),
+            QuestionsService::new(
+                Arc::new(InMemoryQuestionsRepository::new(store)),
+                Arc::new(media),
+            ),
+        )
+    }
+
+    fn category_titles(categories: Vec<Category>) -> Vec<String> {
+        categories.into_iter().map(|c| c.title).collect()
+    }
+
+    #[test]
+    fn inactive_categories_are_left_out_of_listings() {
+        let (categories, _) = services();
+        categories
+            .save_category_and_set_active("science", Some(true))
+            .unwrap();
+        categories
+            .save_category(&Category::new("physics".into(), Some("science".into())))
+            .unwrap();
+        categories
+            .save_category(&Category::new("chemistry".into(), Some("science".into())))
+            .unwrap();
+        categories
+            .save_category_and_set_active("chemistry", Some(false))
+            .unwrap();
+
+        assert_eq!(
+            category_titles(categories.categories().unwrap()),
+            vec!["physics", "science"]
+        );
+        assert_eq!(
+            category_titles(categories.children("science").unwrap()),
+            vec!["physics"]
+        );
+        let tree = categories.category_tree().unwrap();
+        assert_eq!(tree.len(), 1);
+        assert_eq!(tree[0].title, "science");
+        assert_eq!(tree[0].children.len(), 1);
+        assert_eq!(tree[0].children[0].title, "physics");
+
+        categories
+            .save_category_and_set_active("chemistry", Some(true))
+            .unwrap();
+        assert_eq!(
+            category_titles(categories.children("science").unwrap()),
+            vec!["chemistry", "physics"]
+        );
+    }
+
+    #[test]
+    fn save_category_requires_an_existing_parent() {
+        let (categories, _) = services();
+        match categories.save_category(&Category::new("physics".into(), Some("science".into()))) {
+            Err(ServiceError::RepositoryError(RepositoryError::NotFound(_))) => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert!(categories.category("physics").unwrap().is_none());
+    }
+
+    #[test]
+    fn questions_are_paginated() {
+        let (categories, questions) = services();
+        categories
+            .save_category_and_set_active("science", Some(true))
+            .unwrap();
+        for i in 1..4 {
+            questions
+                .save_question(
+                    &question(
+                        "science",
+                        &format!("q{}", i),
+                        vec![choice("a", true), choice("b", false)],
+                    ),
+                    "admin",
+                )
+                .unwrap();
+        }
+
+        let page = |page| -> Vec<String> {
+            questions
+                .questions(&filter("science"), page, 2)
+                .unwrap()
+                .into_iter()
+                .map(|q| q.question)
+                .collect()
+        };
+        assert_eq!(page(1), vec!["q1", "q2"]);
+        assert_eq!(page(2), vec!["q3"]);
+        assert!(page(3).is_empty());
+        assert_eq!(questions.count_questions(&filter("science")).unwrap(), 3);
+    }
+
+    #[test]
+    fn save_question_validates_choices() {
+        let (categories, questions) = services();
+        categories
+            .save_category_and_set_active("science", Some(true))
+            .unwrap();
+
+        match questions.save_question(
+            &question("science", "q", vec![choice("a", true), choice("b", true)]),
+            "admin",
+        ) {
+            Err(ServiceError::ValidationError(_)) => {}
+            result => panic!("unexpected result {:?}", result.map(|q| q.id)),
+        }
+        assert_eq!(questions.count_questions(&filter("science")).unwrap(), 0);
+    }
+
+    #[test]
+    fn update_question_patches_choices() {
+        let (categories, questions) = services();
+        categories
+            .save_category_and_set_active("science", Some(true))
+            .unwrap();
+        let saved = questions
+            .save_question(
+                &question(
+                    "science",
+                    "q",
+                    vec![choice("a", true), choice("b", false), choice("c", false)],
+                ),
+                "admin",
+            )
+            .unwrap();
+        let id = saved.id.unwrap();
+        let ids: Vec<i32> = saved.choices.iter().map(|c| c.id.unwrap()).collect();
+
+        let patch = QuestionPatch {
+            question: Some("q?".into()),
+            update_choices: Some(vec![ChoicePatch {
+                id: ids[1],
+                title: Some("B".into()),
+                correct: None,
+                explanation: Some("because".into()),
+                media: None,
+            }]),
+            remove_choices: Some(vec![ids[2]]),
+            add_choices: Some(vec![NewChoice {
+                title: "d".into(),
+                correct: false,
+                explanation: None,
+                media: None,
+            }]),
+            ..QuestionPatch::default()
+        };
+        let updated = questions.update_question(id, &patch, "admin").unwrap();
+
+        assert_eq!(updated.question, "q?");
+        let choices: Vec<(Option<i32>, String, bool)> = updated
+            .choices
+            .iter()
+            .map(|c| (c.id, c.title.clone(), c.correct))
+            .collect();
+        assert_eq!(
+            choices[..2].to_vec(),
+            vec![
+                (Some(ids[0]), "a".to_string(), true),
+                (Some(ids[1]), "B".to_string(), false),
+            ]
+        );
+        assert_eq!(choices[2].1, "d");
+        assert!(choices[2].0.is_some() && !ids.contains(&choices[2].0.unwrap()));
+        assert_eq!(updated.choices[1].explanation, Some("because".to_string()));
+
+        let stored = questions.repo.get_question(id).unwrap().unwrap();
+        assert_eq!(stored.choices.len(), 3);
+        assert_eq!(questions.revisions(id).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn update_question_rejects_invalid_patches() {
+        let (categories, questions) = services();
+        categories
+            .save_category_and_set_active("science", Some(true))
+            .unwrap();
+        let saved = questions
+            .save_question(
+                &question("science", "q", vec![choice("a", true), choice("b", false)]),
+                "admin",
+            )
+            .unwrap();
+        let id = saved.id.unwrap();
+
+        // Removing the only correct choice leaves a question without an answer.
+        let patch = QuestionPatch {
+            remove_choices: Some(vec![saved.choices[0].id.unwrap()]),
+            ..QuestionPatch::default()
+        };
+        match questions.update_question(id, &patch, "admin") {
+            Err(ServiceError::ValidationError(_)) => {}
+            result => panic!("unexpected result {:?}", result.map(|q| q.id)),
+        }
+
+        let patch = QuestionPatch {
+            remove_choices: Some(vec![-1]),
+            ..QuestionPatch::default()
+        };
+        match questions.update_question(id, &patch, "admin") {
+            Err(ServiceError::ValidationError(_)) => {}
+            result => panic!("unexpected result {:?}", result.map(|q| q.id)),
+        }
+
+        assert_eq!(questions.revisions(id).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn random_questions_are_determined_by_the_seed() {
+        let (categories, questions) = services();
+        categories
+            .save_category_and_set_active("science", Some(true))
+            .unwrap();
+        for i in 1..13 {
+            questions
+                .save_question(
+                    &question(
+                        "science",
+                        &format!("q{}", i),
+                        vec![
+                            choice("a", true),
+                            choice("b", false),
+                            choice("c", false),
+                            choice("d", false),
+                        ],
+                    ),
+                    "admin",
+                )
+                .unwrap();
+        }
+
+        let sample = |seed, exclude_ids: &[i32]| -> Vec<(i32, Vec<String>)> {
+            questions
+                .random_questions(&filter("science"), 4, seed, exclude_ids)
+                .unwrap()
+                .into_iter()
+                .map(|q| {
+                    (
+                        q.id.unwrap(),
+                        q.choices.into_iter().map(|c| c.title).collect(),
+                    )
+                })
+                .collect()
+        };
+
+        let first = sample(42, &[]);
+        assert_eq!(first, sample(42, &[]));
+        let ids: Vec<i32> = first.iter().map(|&(id, _)| id).collect();
+        assert_eq!(ids, vec![7, 9, 2, 10]);
+        assert!(first
+            .iter()
+            .any(|&(_, ref choices)| *choices != ["a", "b", "c", "d"]));
+
+        let excluded: Vec<i32> = sample(42, &[7, 9]).into_iter().map(|(id, _)| id).collect();
+        assert_eq!(excluded[..2].to_vec(), vec![2, 10]);
+        assert!(!excluded.contains(&7) && !excluded.contains(&9));
+
+        categories
+            .save_category_and_set_active("history", Some(true))
+            .unwrap();
+        for i in 1..4 {
+            let mut ordering = question("history", &format!("order {}", i), vec![]);
+            ordering.question_type = QuestionType::Ordering;
+            ordering.ordering = Some(OrderingAnswer {
+                items: vec!["f", "e", "d", "c", "b", "a"]
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+            });
+            questions.save_question(&ordering, "admin").unwrap();
+        }
+        let public_orderings = |seed| -> Vec<Vec<String>> {
+            questions
+                .random_questions(&filter("history"), 3, seed, &[])
+                .unwrap()
+                .into_iter()
+                .map(|q| {
+                    let mut shuffler = sample_shuffler(seed, q.id.unwrap());
+                    q.ordering.unwrap().public(&mut shuffler).items
+                })
+                .collect()
+        };
+        let orderings = public_orderings(42);
+        assert_eq!(orderings.len(), 3);
+        assert_eq!(orderings, public_orderings(42));
+        assert!(orderings.iter().any(|items| *items != orderings[0]));
+        assert!((1..10).any(|seed| public_orderings(seed) != orderings));
+
+        match questions.random_questions(&filter("science"), 0, 42, &[]) {
+            Err(ServiceError::ValidationError(_)) => {}
+            result => panic!("unexpected result {:?}", result.map(|q| q.len())),
+        }
+    }
+
+    #[test]
+    fn answers_are_only_checked_for_visible_questions() {
+        let (categories, questions) = services();
+        for name in &["science", "hidden", "deleted"] {
+            categories
+                .save_category_and_set_active(name, Some(true))
+                .unwrap();
+        }
+        let save = |category: &str| -> (i32, i32, i32) {
+            let choice_question = questions
+                .save_question(
+                    &question(
+                        category,
+                        "Which planet is the largest?",
+                        vec![choice("Jupiter", true), choice("Mars", false)],
+                    ),
+                    "admin",
+                )
+                .unwrap();
+            let mut text_question = question(category, "Which planet is the largest?", vec![]);
+            text_question.question_type = QuestionType::FreeText;
+            text_question.free_text = Some(FreeTextAnswer {
+                accepted: vec![AcceptedAnswer {
+                    answer: "Jupiter".to_string(),
+                    aliases: vec![],
+                }],
+                tolerance: None,
+            });
+            let text_question = questions.save_question(&text_question, "admin").unwrap();
+            (
+                choice_question.id.unwrap(),
+                choice_question.choices[0].id.unwrap(),
+                text_question.id.unwrap(),
+            )
+        };
+        let visible = save("science");
+        let hidden = save("hidden");
+        let deleted = save("deleted");
+        categories
+            .save_category_and_set_active("hidden", Some(false))
+            .unwrap();
+        assert!(categories.delete_category("deleted").unwrap());
+
+        let (question_id, choice_id, text_id) = visible;
+        assert!(
+            questions
+                .check_choice_answer(question_id, choice_id)
+                .unwrap()
+                .correct
+        );
+        assert!(
+            questions
+                .check_text_answer(text_id, "jupiter")
+                .unwrap()
+                .correct
+        );
+
+        for &(question_id, choice_id, text_id) in &[hidden, deleted] {
+            match questions.check_choice_answer(question_id, choice_id) {
+                Err(ServiceError::RepositoryError(RepositoryError::NotFound(_))) => {}
+                result => panic!("unexpected result {:?}", result.map(|c| c.correct)),
+            }
+            match questions.check_text_answer(text_id, "jupiter") {
+                Err(ServiceError::RepositoryError(RepositoryError::NotFound(_))) => {}
+                result => panic!("unexpected result {:?}", result),
+            }
+        }
+
+        categories
+            .save_category_and_set_active("hidden", Some(true))
+            .unwrap();
+        assert!(categories.restore_category("deleted").unwrap());
+        for &(question_id, choice_id, _) in &[hidden, deleted] {
+            assert!(
+                questions
+                    .check_choice_answer(question_id, choice_id)
+                    .unwrap()
+                    .correct
+            );
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::io::Cursor;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::io::{Limits, Reader};
+use image::{DynamicImage, ImageFormat, ImageOutputFormat};
+
+use models::{MediaKind, MediaType, MediaVariant, ValidationError, MEDIA_TYPES};
+
+/// Images with larger dimensions are rejected instead of decoded, so a small file cannot claim to be enormous.
+const MAX_DIMENSION: u32 = 10_000;
+const MAX_DECODE_BYTES: u64 = 256 * 1024 * 1024;
+const JPEG_QUALITY: u8 = 80;
+
+/// JPEG photos stay JPEG. Every other image is resized to PNG, which keeps transparency without the
+/// 256 colour limit of GIF or the native library a WebP encoder needs. Animated GIFs keep their first frame.
+pub fn variant_type(media_type: &MediaType) -> &'static MediaType {
+    let extension = if media_type.extension == "jpg" {
+        "jpg"
+    } else {
+        "png"
+    };
+    MEDIA_TYPES
+        .iter()
+        .find(|media_type| media_type.extension == extension)
+        .unwrap()
+}
+
+/// The id a resized variant is cached under, or `None` when the media is served as uploaded.
+pub fn variant_id(media_id: &str, variant: MediaVariant) -> Option<String> {
+    let media_type = MediaType::from_media_id(media_id)?;
+    if media_type.kind != MediaKind::Image || variant == MediaVariant::Original {
+        return None;
+    }
+
+    let stem = media_id.rsplitn(2, '.').last().unwrap_or(media_id);
+    Some(format!(
+        "{}-{}.{}",
+        stem,
+        variant.as_str(),
+        variant_type(media_type).extension
+    ))
+}
+
+/// Scales the image down to `width` pixels, keeping its aspect ratio, and encodes it as `variant_type`.
+pub fn resize(
+    media_id: &str,
+    media_type: &MediaType,
+    data: &[u8],
+    width: u32,
+) -> Result<Vec<u8>, ValidationError> {
+    let unreadable = |e: image::ImageError| {
+        ValidationError::new(format!("The image '{}' cannot be read: {}", media_id, e))
+    };
+
+    let format = ImageFormat::from_extension(media_type.extension)
+        .ok_or_else(|| ValidationError::new(format!("'{}' is not an image", media_id)))?;
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_BYTES);
+
+    let mut reader = Reader::new(Cursor::new(data));
+    reader.set_format(format);
+    reader.limits(limits);
+    let image = reader.decode().map_err(unreadable)?;
+
+    let image = if image.width() > width {
+        let height = (image.height() as u64 * width as u64 / image.width() as u64).max(1);
+        image.resize_exact(width, height as u32, FilterType::Triangle)
+    } else {
+        image
+    };
+
+    let mut output = Cursor::new(Vec::new());
+    if variant_type(media_type).extension == "jpg" {
+        let rgb = DynamicImage::ImageRgb8(image.to_rgb8());
+        JpegEncoder::new_with_quality(&mut output, JPEG_QUALITY)
+            .encode_image(&rgb)
+            .map_err(unreadable)?;
+    } else {
+        image
+            .write_to(&mut output, ImageOutputFormat::Png)
+            .map_err(unreadable)?;
+    }
+    Ok(output.into_inner())
+}
@@ -0,0 +1,72 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde_derive::{Deserialize, Serialize};
+use serde_json;
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenKind {
+    /// Sent with every request as `Authorization: Bearer <token>`.
+    Access,
+    /// Only exchanged for new tokens, so it can live longer than an access token.
+    Refresh,
+}
+
+/// What a token says about its bearer.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TokenClaims {
+    /// The player's id.
+    pub sub: String,
+    pub kind: TokenKind,
+    /// When the token expires, in seconds since the Unix epoch.
+    pub exp: i64,
+}
+
+/// Signs tokens as `<claims>.<signature>`, the base64url-encoded JSON claims and their HMAC-SHA256.
+/// Tokens are not stored, so every server sharing the key accepts them until they expire.
+pub struct TokenSigner {
+    key: Vec<u8>,
+}
+
+impl TokenSigner {
+    pub fn new(key: &[u8]) -> TokenSigner {
+        TokenSigner { key: key.to_vec() }
+    }
+
+    fn mac(&self) -> HmacSha256 {
+        HmacSha256::new_from_slice(&self.key).expect("HMAC accepts keys of any length")
+    }
+
+    pub fn sign(&self, claims: &TokenClaims) -> String {
+        let payload = URL_SAFE_NO_PAD
+            .encode(serde_json::to_vec(claims).expect("token claims are always serializable"));
+        let mut mac = self.mac();
+        mac.update(payload.as_bytes());
+        let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+        format!("{}.{}", payload, signature)
+    }
+
+    /// The claims of a token signed with this key, if it is of the expected kind and has not expired.
+    pub fn verify(&self, token: &str, kind: TokenKind, now: DateTime<Utc>) -> Option<TokenClaims> {
+        let mut parts = token.trim().splitn(2, '.');
+        let payload = parts.next()?;
+        let signature = URL_SAFE_NO_PAD.decode(parts.next()?).ok()?;
+
+        // Compared in constant time, so the signature cannot be guessed byte by byte.
+        let mut mac = self.mac();
+        mac.update(payload.as_bytes());
+        mac.verify_slice(&signature).ok()?;
+
+        let claims: TokenClaims =
+            serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).ok()?).ok()?;
+        if claims.kind != kind || claims.exp <= now.timestamp() {
+            return None;
+        }
+        Some(claims)
+    }
+}